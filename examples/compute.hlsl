@@ -0,0 +1,13 @@
+// hlsl_box: mode=compute
+#include "hlsl_box.hlsli"
+
+[numthreads(8, 8, 1)]
+void main(uint3 id: SV_DispatchThreadID) {
+    if (any(id.xy >= (uint2)HLSLBox.resolution)) {
+        return;
+    }
+    float2 p = normalized_position(id.xy + 0.5);
+    float d = length(p - normalized_mouse_position());
+    float c = 0.5 + 0.5 * cos(d * 20.0 - HLSLBox.time * 4.0);
+    HLSLBoxOutput[id.xy] = float4(c * 0.2, c * 0.6, c, 1.0);
+}
@@ -0,0 +1,9 @@
+// hlsl_box: mesh=cube.obj
+#include "hlsl_box.hlsli"
+
+float4 main(MeshInput input): SV_Target {
+    float3 light = normalize(float3(0.5, 1.0, 0.8));
+    float diffuse = saturate(dot(normalize(input.normal), light)) * 0.8 + 0.2;
+    float2 uv = input.coord / HLSLBox.resolution;
+    return float4(float3(uv, 1.0) * diffuse, 1.0);
+}
@@ -0,0 +1,18 @@
+#include "hlsl_box.hlsli"
+
+cbuffer User: register(b1) {
+    float radius; // @range(0.1, 1.0) @default(0.5)
+    float3 color; // @color @default(1.0, 0.5, 0.0)
+    int rings; // @range(1, 10) @default(3)
+    bool invert; // @toggle
+};
+
+float4 main(Input input): SV_Target {
+    const float2 p = normalized_position(input.coord);
+    const float d = length(p) / radius;
+    float v = frac(d * rings) < 0.5 && d < 1.0 ? 1.0 : 0.0;
+    if (invert) {
+        v = 1.0 - v;
+    }
+    return float4(color * v, 1.0);
+}
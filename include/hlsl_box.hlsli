@@ -3,14 +3,64 @@ struct Input {
     float2 coord: TEXCOORD0; // left-top 0 ..= rendering resolution
 };
 
+// the vertex of mesh=...
+struct MeshVertex {
+    float3 position: POSITION;
+    float3 normal: NORMAL;
+    float2 coord: TEXCOORD0; // left-top 0.0 ..= 1.0
+};
+
+// the output of the built-in vertex shader for mesh=...; the prefix is compatible with Input
+struct MeshInput {
+    float4 position: SV_Position;
+    float2 coord: TEXCOORD0; // texture coordinate * rendering resolution
+    float3 normal: NORMAL; // world space
+    float3 world_position: TEXCOORD1;
+};
+
+static const uint MOUSE_LEFT = 0x1;
+static const uint MOUSE_RIGHT = 0x2;
+static const uint MOUSE_MIDDLE = 0x4;
+
+// the layout must match pixel_shader::Parameters
 struct Parameters {
     float2 resolution;
     float2 mouse; // left-top 0.0 ..= 1.0
     float time;
+    float time_delta;
+    uint frame;
+    uint mouse_buttons; // MOUSE_LEFT | MOUSE_RIGHT | MOUSE_MIDDLE
+    float4 date; // year, month, day, seconds since midnight
+    float2 click; // left-top 0.0 ..= 1.0
+    float4x4 model; // column vectors: mul(HLSLBox.model, float4(p, 1.0))
+    float4x4 view; // the orbit camera
+    float4x4 projection;
 };
 
 ConstantBuffer<Parameters> HLSLBox: register(b0);
 
+// x: virtual-key code, y: 0 = held, 1 = pressed in this frame, 2 = toggled
+Texture2D<float> HLSLBoxKeyboard: register(t0, space1);
+
+// the output of pass_a ..= pass_d; the pass itself and later passes read the previous frame
+Texture2D<float4> HLSLBoxPassA: register(t0, space2);
+Texture2D<float4> HLSLBoxPassB: register(t1, space2);
+Texture2D<float4> HLSLBoxPassC: register(t2, space2);
+Texture2D<float4> HLSLBoxPassD: register(t3, space2);
+SamplerState HLSLBoxPassSampler: register(s0, space2); // linear, clamp
+
+// the rendering result written by a compute shader (mode=compute)
+RWTexture2D<float4> HLSLBoxOutput: register(u0);
+
+static const uint KEY_SHIFT = 0x10;
+static const uint KEY_CTRL = 0x11;
+static const uint KEY_ALT = 0x12;
+static const uint KEY_SPACE = 0x20;
+static const uint KEY_LEFT = 0x25;
+static const uint KEY_UP = 0x26;
+static const uint KEY_RIGHT = 0x27;
+static const uint KEY_DOWN = 0x28;
+
 float2 normalized_position(float2 coord) {
     return float2(coord.x * 2.0 - HLSLBox.resolution.x, HLSLBox.resolution.y - coord.y * 2.0)
         / min(HLSLBox.resolution.x, HLSLBox.resolution.y);
@@ -18,4 +68,16 @@ float2 normalized_position(float2 coord) {
 
 float2 normalized_mouse_position() {
     return float2(HLSLBox.mouse.x * 2.0 - 1.0, 1.0 - HLSLBox.mouse.y * 2.0);
-}
\ No newline at end of file
+}
+
+bool key_held(uint key) {
+    return HLSLBoxKeyboard.Load(int3(key, 0, 0)) > 0.5;
+}
+
+bool key_pressed(uint key) {
+    return HLSLBoxKeyboard.Load(int3(key, 1, 0)) > 0.5;
+}
+
+bool key_toggled(uint key) {
+    return HLSLBoxKeyboard.Load(int3(key, 2, 0)) > 0.5;
+}
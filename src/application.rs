@@ -1,6 +1,10 @@
+mod camera;
+mod compile_worker;
 mod error_message;
 mod frame_counter;
+mod headless;
 mod message_board;
+mod parameter_panel;
 
 use crate::*;
 use std::{
@@ -11,9 +15,13 @@ use std::{
 };
 use windows::Win32::Graphics::{Direct3D::*, Direct3D12::*};
 
+use camera::*;
+use compile_worker::*;
 use error_message::*;
 use frame_counter::*;
+pub use headless::{render, RenderOptions};
 use message_board::*;
+use parameter_panel::*;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Method {
@@ -23,6 +31,12 @@ pub enum Method {
     Play,
     Head,
     RecordVideo,
+    Flatten,
+    ParameterPanel,
+    SaveParams,
+    AddPreset,
+    NextPreset,
+    CloseWarnings,
     Exit,
 }
 
@@ -114,13 +128,65 @@ impl UiProperties {
     }
 }
 
+fn mouse_button_bit(button: wita::MouseButton) -> u32 {
+    match button {
+        wita::MouseButton::Left => pixel_shader::MOUSE_LEFT,
+        wita::MouseButton::Right => pixel_shader::MOUSE_RIGHT,
+        wita::MouseButton::Middle => pixel_shader::MOUSE_MIDDLE,
+        _ => 0,
+    }
+}
+
 struct Rendering {
     path: PathBuf,
     parameters: pixel_shader::Parameters,
     ps: pixel_shader::Pipeline,
+    passes: Vec<pixel_shader::Pass>,
     frame_counter: FrameCounter,
     show_frame_counter: Rc<Cell<bool>>,
     message_board: MessageBoard,
+    warnings: Option<ErrorMessage>,
+    parameter_panel: ParameterPanel,
+    show_parameter_panel: Rc<Cell<bool>>,
+    params: settings::Params,
+    project: Option<project::Project>,
+    camera: Option<Camera>,
+}
+
+impl Rendering {
+    fn save_params(&mut self, new_preset: bool) -> anyhow::Result<()> {
+        if self.parameter_panel.is_empty() {
+            return Ok(());
+        }
+        let preset = self.parameter_panel.parameters().preset();
+        let name = if new_preset {
+            self.params.add(preset)
+        } else {
+            self.params.set_current(preset)
+        }
+        .to_string();
+        match self.params.save(settings::Params::path(&self.path)) {
+            Ok(_) => {
+                info!("save params: {}", name);
+                self.message_board
+                    .write(format!("{} ({})", MESSAGES.save_params, name))?;
+            }
+            Err(e) => {
+                error!("save params: {}", e);
+                self.message_board.write(MESSAGES.save_params_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn next_preset(&mut self) -> anyhow::Result<()> {
+        if let Some((name, preset)) = self.params.next() {
+            self.parameter_panel.apply_preset(preset)?;
+            self.message_board
+                .write(format!("{}: {}", MESSAGES.preset, name))?;
+        }
+        Ok(())
+    }
 }
 
 enum State {
@@ -129,15 +195,38 @@ enum State {
     Error(Box<ErrorMessage>),
 }
 
+impl State {
+    fn message_mut(&mut self) -> Option<&mut ErrorMessage> {
+        match self {
+            State::Rendering(r) => r.warnings.as_mut(),
+            State::Error(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    fn parameter_panel_mut(&mut self) -> Option<&mut ParameterPanel> {
+        match self {
+            State::Rendering(r) if r.show_parameter_panel.get() => Some(&mut r.parameter_panel),
+            _ => None,
+        }
+    }
+}
+
 impl RenderUi for State {
     fn render(&self, cmd: &mltg::DrawCommand, size: wita::LogicalSize<f32>) {
         match &self {
             State::Init => {}
             State::Rendering(r) => {
+                if let Some(warnings) = &r.warnings {
+                    warnings.draw(cmd, size);
+                }
                 r.frame_counter.update().unwrap();
                 if r.show_frame_counter.get() {
                     r.frame_counter.draw(cmd, [10.0, 10.0]);
                 }
+                if r.show_parameter_panel.get() {
+                    r.parameter_panel.draw(cmd);
+                }
                 r.message_board.draw(cmd, size);
             }
             State::Error(e) => {
@@ -252,22 +341,103 @@ impl ScreenShot {
     }
 }
 
+fn create_compiler(
+    shader: &settings::Shader,
+    cache: &settings::Cache,
+) -> Result<hlsl::Compiler, Error> {
+    let mut compiler = match shader.dxc_path.as_ref() {
+        Some(path) => {
+            info!("dxc: {}", path);
+            hlsl::Compiler::with_dxc(path)
+        }
+        None => hlsl::Compiler::new()?,
+    };
+    compiler.set_include_paths(
+        shader
+            .include_paths
+            .iter()
+            .map(|path| EXE_DIR_PATH.join(path))
+            .collect(),
+    );
+    if cache.enabled {
+        compiler.set_cache(Some(hlsl::Cache::new(
+            &*CACHE_PATH,
+            cache.max_size_mb * 1024 * 1024,
+        )));
+    }
+    Ok(compiler)
+}
+
+fn shader_directives(
+    path: &Path,
+    project: Option<&hlsl::Directives>,
+) -> Result<hlsl::Directives, Error> {
+    let header = hlsl::Directives::from_file(path)?;
+    Ok(match project {
+        Some(directives) => directives.merge(&header),
+        None => header,
+    })
+}
+
+fn flatten_file(
+    compiler: &hlsl::Compiler,
+    path: &Path,
+    project: Option<&hlsl::Directives>,
+    output: Option<&Path>,
+    target: hlsl::Target,
+    args: &[String],
+) -> Result<PathBuf, Error> {
+    let directives = shader_directives(path, project)?;
+    let output = output.map_or_else(|| path.with_extension("flat.hlsl"), |p| p.to_path_buf());
+    let src = compiler.flatten_file(path, &output, &directives, target, args)?;
+    std::fs::write(&output, src).map_err(|_| Error::CreateFile(output.clone()))?;
+    info!("flatten: {} -> {}", path.display(), output.display());
+    Ok(output)
+}
+
+pub fn flatten(path: &Path, output: Option<&Path>) -> anyhow::Result<PathBuf> {
+    let settings = Settings::load(&*SETTINGS_PATH).unwrap_or_else(|e| {
+        warn!("flatten: {}", e);
+        Settings::default()
+    });
+    let compiler = create_compiler(&settings.shader, &settings.cache)?;
+    let shader_model = match &settings.shader.version {
+        Some(version) => hlsl::ShaderModel::specify(version)?,
+        None => hlsl::ShaderModel::latest(),
+    };
+    let project = project::is_project(path)
+        .then(|| project::Project::load(path))
+        .transpose()?;
+    Ok(flatten_file(
+        &compiler,
+        project.as_ref().map_or(path, |p| &p.shader),
+        project.as_ref().map(|p| &p.directives),
+        output,
+        hlsl::Target::PS(shader_model),
+        &settings.shader.ps_args,
+    )?)
+}
+
 pub struct Application {
     d3d12_device: ID3D12Device,
     settings: Settings,
     shader_model: hlsl::ShaderModel,
     compiler: hlsl::Compiler,
+    compile_worker: CompileWorker,
     window_manager: WindowManager,
     renderer: Renderer,
     clear_color: [f32; 4],
     mouse: [f32; 2],
     play: bool,
     timer: Timer,
-    exe_dir_monitor: DirMonitor,
-    hlsl_dir_monitor: Option<DirMonitor>,
+    reloader: Reloader,
     state: State,
     ui_props: UiProperties,
     show_frame_counter: Rc<Cell<bool>>,
+    show_parameter_panel: Rc<Cell<bool>>,
+    user_parameters: Option<(PathBuf, UserParameters)>,
+    params: Option<(PathBuf, settings::Params)>,
+    project: Option<(PathBuf, project::Project)>,
     screen_shot: ScreenShot,
     video_file_gen: FileNameGenerator,
 }
@@ -279,7 +449,7 @@ impl Application {
     ) -> anyhow::Result<Self> {
         let default_settings = Settings::default();
         let settings = src_settings.as_ref().unwrap_or(&default_settings);
-        let compiler = hlsl::Compiler::new()?;
+        let compiler = create_compiler(&settings.shader, &settings.cache)?;
         let debug_layer = ENV_ARGS.debuglayer;
         if debug_layer {
             unsafe {
@@ -316,7 +486,12 @@ impl Application {
         let factory = renderer.mltg_factory();
         let ui_props = UiProperties::new(settings, &factory)?;
         let show_frame_counter = Rc::new(Cell::new(settings.frame_counter));
-        let exe_dir_monitor = DirMonitor::new(&*EXE_DIR_PATH)?;
+        let reloader = Reloader::new(
+            &*SETTINGS_PATH,
+            &*WINDOW_SETTING_PATH,
+            (&settings.monitor).into(),
+        )?;
+        let compile_worker = CompileWorker::new(&settings.shader, &settings.cache);
         let screen_shot = ScreenShot::new();
         let state = match src_settings.as_ref() {
             Ok(_) => State::Init,
@@ -338,21 +513,25 @@ impl Application {
             window_manager,
             shader_model,
             compiler,
+            compile_worker,
             renderer,
             clear_color,
             mouse: [0.0, 0.0],
             play: false,
             timer: Timer::new(),
-            exe_dir_monitor,
-            hlsl_dir_monitor: None,
+            reloader,
             state,
             ui_props,
             show_frame_counter,
+            show_parameter_panel: Rc::new(Cell::new(true)),
+            user_parameters: None,
+            params: None,
+            project: None,
             screen_shot,
             video_file_gen: FileNameGenerator::new(&*VIDEO_PATH),
         };
         if let Some(path) = ENV_ARGS.input_file.as_ref().map(Path::new) {
-            if let Err(e) = this.load_file(path).await {
+            if let Err(e) = this.load_file(path) {
                 this.set_error(path, e).await?;
             }
         }
@@ -367,43 +546,199 @@ impl Application {
         Ok(this)
     }
 
-    async fn load_file(&mut self, path: &Path) -> Result<(), Error> {
+    fn load_file(&mut self, path: &Path) -> Result<(), Error> {
         if !path.is_file() {
             return Err(Error::ReadFile(path.into()));
         }
         let path = path.canonicalize().unwrap();
-        let parent = path.parent().unwrap();
-        let same_dir_monitor = self
-            .hlsl_dir_monitor
-            .as_ref()
-            .map_or(true, |d| d.path() != parent);
-        if same_dir_monitor {
-            debug!("load_file: DirMonitor::new: {}", parent.display());
-            self.hlsl_dir_monitor = Some(DirMonitor::new(parent)?);
-        }
-        let blob = self.compiler.compile_from_file(
-            &path,
-            "main",
+        let project = if project::is_project(&path) {
+            match project::Project::load(&path) {
+                Ok(project) => Some(project),
+                Err(e) => {
+                    self.reloader.watch(vec![path])?;
+                    return Err(e);
+                }
+            }
+        } else {
+            None
+        };
+        let params = settings::Params::load(settings::Params::path(&path)).unwrap_or_else(|e| {
+            warn!("load params: {}", e);
+            settings::Params::default()
+        });
+        self.params = Some((path.clone(), params));
+        debug!("compile: {}", path.display());
+        self.compile_worker.compile(
+            path.clone(),
+            project.as_ref(),
             hlsl::Target::PS(self.shader_model),
             &self.settings.shader.ps_args,
+        );
+        self.project = project.map(|project| (path, project));
+        if let State::Rendering(r) = &mut self.state {
+            if let Err(e) = r.message_board.show_status(MESSAGES.compiling) {
+                error!("show_status: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn apply_compile_result(&mut self, result: CompileResult) -> Result<(), Error> {
+        if let State::Rendering(r) = &mut self.state {
+            r.message_board.hide_status();
+        }
+        self.reloader.watch(result.dependencies)?;
+        let shader = result.shader.map_err(Error::Compile)?;
+        let path = result.path;
+        let project = self
+            .project
+            .take()
+            .filter(|(p, _)| *p == path)
+            .map(|(_, project)| project);
+        let resolution = project
+            .as_ref()
+            .and_then(|p| p.resolution)
+            .unwrap_or(self.settings.resolution);
+        if self.renderer.resolution() != resolution.into() {
+            if let Err(e) = self.renderer.set_resolution(resolution.into()).await {
+                error!("set_resolution: {}", e);
+            }
+            self.fit_window(resolution).await?;
+        }
+        let ps = self.renderer.create_pixel_shader_pipeline(
+            &format!("{}", path.display()),
+            &shader.blob,
+            &shader.reflection,
+            shader
+                .vertex
+                .as_ref()
+                .map(|(blob, reflection)| (blob, reflection)),
+            shader.mesh.is_some(),
         )?;
-        let ps = self
-            .renderer
-            .create_pixel_shader_pipeline(&format!("{}", path.display()), &blob)?;
-        let resolution = self.settings.resolution;
-        let parameters = pixel_shader::Parameters {
+        self.renderer.set_mesh(shader.mesh.as_ref()).await?;
+        self.renderer.set_channels(&shader.channels).await?;
+        let mut passes = Vec::with_capacity(shader.passes.len());
+        let mut pass_sizes = [None; hlsl::PASS_COUNT];
+        for pass in shader.passes.iter() {
+            let pipeline = self.renderer.create_pass_pipeline(
+                &format!("{}", pass.path.display()),
+                &pass.blob,
+                &pass.reflection,
+            )?;
+            let mut user_parameters = UserParameters::new(
+                pixel_shader::user_constant_buffer(&pass.reflection),
+                &pass.annotations,
+                None,
+            );
+            if let Some(project) = &project {
+                user_parameters.apply_preset(&project.uniforms);
+            }
+            pass_sizes[pass.index] = Some(pass.resolution.map_or_else(
+                || resolution.into(),
+                |[width, height]| wita::PhysicalSize::new(width, height),
+            ));
+            passes.push(pixel_shader::Pass {
+                index: pass.index,
+                pipeline,
+                user_parameters: user_parameters.data().to_vec(),
+            });
+        }
+        self.renderer.set_passes(pass_sizes).await?;
+        let loaded_params = self
+            .params
+            .take()
+            .filter(|(p, _)| *p == path)
+            .map(|(_, params)| params);
+        let uniforms = project.as_ref().map(|p| &p.uniforms);
+        let (params, previous, uniforms_changed) = match &self.state {
+            State::Rendering(r) if r.path == path => (
+                loaded_params.unwrap_or_else(|| r.params.clone()),
+                Some(r.parameter_panel.parameters()),
+                r.project.as_ref().map(|p| &p.uniforms) != uniforms,
+            ),
+            _ => (
+                loaded_params.unwrap_or_default(),
+                self.user_parameters
+                    .as_ref()
+                    .filter(|(p, _)| *p == path)
+                    .map(|(_, params)| params),
+                false,
+            ),
+        };
+        let mut user_parameters = UserParameters::new(
+            pixel_shader::user_constant_buffer(&shader.reflection),
+            &shader.annotations,
+            previous,
+        );
+        if previous.is_none() || uniforms_changed {
+            if let Some(uniforms) = uniforms {
+                user_parameters.apply_preset(uniforms);
+            }
+        }
+        if previous.is_none() {
+            if let Some(preset) = params.current() {
+                user_parameters.apply_preset(preset);
+            }
+        }
+        let parameter_panel = ParameterPanel::new(&self.ui_props, user_parameters, [10.0, 40.0])?;
+        let mut parameters = pixel_shader::Parameters {
             resolution: [resolution.width as _, resolution.height as _],
             mouse: self.mouse,
-            time: 0.0,
+            ..Default::default()
         };
+        let camera = shader.mesh.as_ref().map(|mesh| {
+            let bounds = mesh.bounds();
+            match &self.state {
+                State::Rendering(r) if r.path == path => r.camera.clone(),
+                _ => None,
+            }
+            .filter(|camera| camera.bounds() == bounds)
+            .unwrap_or_else(|| Camera::new(bounds))
+        });
+        if let Some(camera) = &camera {
+            camera.apply(&mut parameters);
+        }
         let frame_counter = FrameCounter::new(&self.ui_props)?;
+        for w in result.warnings.iter() {
+            warn!("{}", w);
+        }
+        let warnings = if result.warnings.is_empty() {
+            None
+        } else {
+            let dpi = self.window_manager.main_window.dpi();
+            let size = self
+                .window_manager
+                .main_window
+                .inner_size()
+                .to_logical(dpi)
+                .cast::<f32>();
+            match ErrorMessage::from_diagnostics(
+                path.clone(),
+                &result.warnings,
+                &self.ui_props,
+                size,
+            ) {
+                Ok(warnings) => Some(warnings),
+                Err(e) => {
+                    error!("warnings: {}", e);
+                    None
+                }
+            }
+        };
         self.set_state(State::Rendering(Box::new(Rendering {
             path: path.to_path_buf(),
             parameters,
             ps,
+            passes,
             frame_counter,
             show_frame_counter: self.show_frame_counter.clone(),
             message_board: MessageBoard::new(&self.renderer.mltg_factory(), &self.ui_props, 10.0),
+            warnings,
+            parameter_panel,
+            show_parameter_panel: self.show_parameter_panel.clone(),
+            params,
+            project,
+            camera,
         })))
         .await;
         self.play = self.settings.auto_play;
@@ -420,20 +755,18 @@ impl Application {
 
     pub async fn run(&mut self) -> anyhow::Result<()> {
         loop {
-            if let Some(path) = self.exe_dir_monitor.try_recv() {
-                if path.as_path() == SETTINGS_PATH.as_path() {
-                    self.reload_settings().await?;
-                }
+            let reloads = self.reloader.poll();
+            if reloads.contains(&Reload::Settings) {
+                self.reload_settings().await?;
             }
             let cursor_position = self.window_manager.get_cursor_position();
             match self.window_manager.try_recv() {
                 Some(WindowEvent::LoadFile(path)) => {
                     debug!("WindowEvent::LoadFile");
                     match &self.state {
-                        State::Error(e)
-                            if e.path() == *SETTINGS_PATH || e.path() == *WINDOW_SETTING_PATH => {}
+                        State::Error(e) if self.reloader.is_setting(e.path()) => {}
                         _ => {
-                            if let Err(e) = self.load_file(&path).await {
+                            if let Err(e) = self.load_file(&path) {
                                 self.set_error(&path, e).await?;
                             }
                         }
@@ -443,14 +776,12 @@ impl Application {
                     debug!("WindowEvent::KeyInput");
                     match m {
                         Method::OpenDialog => match &mut self.state {
-                            State::Error(e)
-                                if e.path() == *SETTINGS_PATH
-                                    || e.path() == *WINDOW_SETTING_PATH => {}
+                            State::Error(e) if self.reloader.is_setting(e.path()) => {}
                             _ => {
                                 let dlg = ifdlg::FileOpenDialog::new();
                                 match dlg.show::<PathBuf>() {
                                     Ok(Some(path)) => {
-                                        if let Err(e) = self.load_file(&path).await {
+                                        if let Err(e) = self.load_file(&path) {
                                             self.set_error(&path, e).await?;
                                         }
                                     }
@@ -474,14 +805,19 @@ impl Application {
                             self.play = !self.play;
                             if self.play {
                                 self.timer.start();
+                                if let State::Rendering(r) = &mut self.state {
+                                    r.parameters.time = self.timer.get().as_secs_f32();
+                                }
                             } else {
                                 self.timer.stop();
                             }
                         }
                         Method::Head => {
                             self.timer = Timer::new();
+                            self.renderer.clear_passes();
                             if let State::Rendering(r) = &mut self.state {
                                 r.parameters.time = 0.0;
+                                r.parameters.frame = 0;
                             }
                         }
                         Method::RecordVideo => {
@@ -491,17 +827,21 @@ impl Application {
                                 }
                                 self.timer = Timer::new();
                                 r.parameters.time = 0.0;
+                                r.parameters.frame = 0;
+                                self.renderer.clear_passes();
                                 if self.renderer.is_writing_video() {
                                     self.renderer.stop_video();
                                     info!("record video stop");
                                     r.message_board.write(MESSAGES.record_video_end)?;
                                 } else {
-                                    let frame_rate = self.settings.video.frame_rate;
-                                    let end_frame =
-                                        Some(self.settings.video.end_frame).filter(|i| *i > 0);
+                                    let video = r.project.as_ref().map_or_else(
+                                        || self.settings.video.clone(),
+                                        |p| p.video(&self.settings.video),
+                                    );
+                                    let end_frame = Some(video.end_frame).filter(|i| *i > 0);
                                     if let Err(e) = self.renderer.start_video(
                                         self.video_file_gen.get(".mp4"),
-                                        frame_rate,
+                                        video.frame_rate,
                                         end_frame,
                                     ) {
                                         error!("record_video: {}", e);
@@ -511,6 +851,40 @@ impl Application {
                                 }
                             }
                         }
+                        Method::Flatten => {
+                            if let State::Rendering(r) = &self.state {
+                                self.compile_worker.flatten(
+                                    r.path.clone(),
+                                    r.project.as_ref(),
+                                    hlsl::Target::PS(self.shader_model),
+                                    &self.settings.shader.ps_args,
+                                );
+                            }
+                        }
+                        Method::ParameterPanel => {
+                            self.show_parameter_panel
+                                .set(!self.show_parameter_panel.get());
+                        }
+                        Method::SaveParams => {
+                            if let State::Rendering(r) = &mut self.state {
+                                r.save_params(false)?;
+                            }
+                        }
+                        Method::AddPreset => {
+                            if let State::Rendering(r) = &mut self.state {
+                                r.save_params(true)?;
+                            }
+                        }
+                        Method::NextPreset => {
+                            if let State::Rendering(r) = &mut self.state {
+                                r.next_preset()?;
+                            }
+                        }
+                        Method::CloseWarnings => {
+                            if let State::Rendering(r) = &mut self.state {
+                                r.warnings = None;
+                            }
+                        }
                         Method::Exit => {
                             self.window_manager.main_window.close();
                             break;
@@ -519,21 +893,55 @@ impl Application {
                 }
                 Some(WindowEvent::MouseInput(button, state)) => {
                     debug!("WindowEvent::MouseInput");
-                    if let State::Error(em) = &mut self.state {
-                        let main_window = &self.window_manager.main_window;
-                        let dpi = main_window.dpi();
-                        let size = main_window.inner_size().to_logical(dpi).cast::<f32>();
-                        let mouse_pos = cursor_position.to_logical(dpi as _).cast::<f32>();
-                        em.mouse_event(mouse_pos, Some((button, state)), size)?;
+                    let main_window = &self.window_manager.main_window;
+                    let dpi = main_window.dpi();
+                    let size = main_window.inner_size().to_logical(dpi).cast::<f32>();
+                    let mouse_pos = cursor_position.to_logical(dpi as _).cast::<f32>();
+                    let handled = match self.state.parameter_panel_mut() {
+                        Some(panel) => panel.mouse_event(mouse_pos, Some((button, state)))?,
+                        None => false,
+                    };
+                    if let State::Rendering(r) = &mut self.state {
+                        let bit = mouse_button_bit(button);
+                        match state {
+                            wita::KeyState::Pressed if !handled => {
+                                r.parameters.mouse_buttons |= bit;
+                                if button == wita::MouseButton::Left {
+                                    r.parameters.click =
+                                        [mouse_pos.x / size.width, mouse_pos.y / size.height];
+                                    if let Some(camera) = &mut r.camera {
+                                        camera.begin_drag(cursor_position);
+                                    }
+                                }
+                            }
+                            wita::KeyState::Released => {
+                                r.parameters.mouse_buttons &= !bit;
+                                if button == wita::MouseButton::Left {
+                                    if let Some(camera) = &mut r.camera {
+                                        camera.end_drag();
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    if !handled {
+                        if let Some(em) = self.state.message_mut() {
+                            em.mouse_event(mouse_pos, Some((button, state)), size)?;
+                        }
                     }
                 }
                 Some(WindowEvent::Wheel(d)) => {
                     debug!("WindowEvent::Wheel");
-                    if let State::Error(em) = &mut self.state {
+                    if let Some(em) = self.state.message_mut() {
                         let main_window = &self.window_manager.main_window;
                         let dpi = main_window.dpi();
                         let size = main_window.inner_size().to_logical(dpi).cast::<f32>();
                         em.offset(size, d)?;
+                    } else if let State::Rendering(r) = &mut self.state {
+                        if let Some(camera) = &mut r.camera {
+                            camera.zoom(d);
+                        }
                     }
                 }
                 Some(WindowEvent::Resized(size)) => {
@@ -541,7 +949,7 @@ impl Application {
                     if let Err(e) = self.renderer.resize(size).await {
                         error!("{}", e);
                     }
-                    if let State::Error(e) = &mut self.state {
+                    if let Some(e) = self.state.message_mut() {
                         let main_window = &self.window_manager.main_window;
                         let dpi = main_window.dpi();
                         let size = main_window.inner_size().to_logical(dpi).cast::<f32>();
@@ -553,7 +961,7 @@ impl Application {
                     if let Err(e) = self.renderer.restore(size).await {
                         error!("{}", e);
                     }
-                    if let State::Error(e) = &mut self.state {
+                    if let Some(e) = self.state.message_mut() {
                         let main_window = &self.window_manager.main_window;
                         let dpi = main_window.dpi();
                         let size = size.to_logical(dpi).cast::<f32>();
@@ -568,7 +976,7 @@ impl Application {
                     if let Err(e) = self.renderer.maximize(size).await {
                         error!("{}", e);
                     }
-                    if let State::Error(e) = &mut self.state {
+                    if let Some(e) = self.state.message_mut() {
                         let main_window = &self.window_manager.main_window;
                         let dpi = main_window.dpi();
                         let size = size.to_logical(dpi).cast::<f32>();
@@ -594,42 +1002,57 @@ impl Application {
                     break;
                 }
                 _ => {
-                    if let State::Error(em) = &mut self.state {
-                        let main_window = &self.window_manager.main_window;
-                        let dpi = main_window.dpi();
-                        let size = main_window.inner_size().to_logical(dpi).cast::<f32>();
-                        let mouse_pos = cursor_position.to_logical(dpi as _).cast::<f32>();
-                        em.mouse_event(mouse_pos, None, size)?;
+                    let main_window = &self.window_manager.main_window;
+                    let dpi = main_window.dpi();
+                    let size = main_window.inner_size().to_logical(dpi).cast::<f32>();
+                    let mouse_pos = cursor_position.to_logical(dpi as _).cast::<f32>();
+                    let handled = match self.state.parameter_panel_mut() {
+                        Some(panel) => panel.mouse_event(mouse_pos, None)?,
+                        None => false,
+                    };
+                    if !handled {
+                        if let Some(em) = self.state.message_mut() {
+                            em.mouse_event(mouse_pos, None, size)?;
+                        }
                     }
                 }
             }
-            if let Some(path) = self
-                .hlsl_dir_monitor
-                .as_ref()
-                .and_then(|dir| dir.try_recv())
-            {
-                match &self.state {
-                    State::Rendering(r) => {
-                        if r.path == path {
-                            if let Err(e) = self.load_file(&path).await {
-                                self.set_error(&path, e).await?;
-                            }
-                        }
+            if reloads.contains(&Reload::Shader) {
+                let current = match &self.state {
+                    State::Rendering(r) => Some(r.path.as_path()),
+                    State::Error(e) => Some(e.path()),
+                    _ => None,
+                };
+                if let Some(path) = self.reloader.shader_path(current).map(Path::to_path_buf) {
+                    if let Err(e) = self.load_file(&path) {
+                        self.set_error(&path, e).await?;
                     }
-                    State::Error(e) => {
-                        if e.path() != *SETTINGS_PATH
-                            && e.path() != *WINDOW_SETTING_PATH
-                            && e.path() == path
-                        {
-                            if let Err(e) = self.load_file(&path).await {
-                                self.set_error(&path, e).await?;
-                            }
-                        }
+                }
+            }
+            if let Some(result) = self.compile_worker.try_recv() {
+                let path = result.path.clone();
+                if let Err(e) = self.apply_compile_result(result).await {
+                    self.set_error(&path, e).await?;
+                }
+            }
+            if let Some(result) = self.compile_worker.try_recv_flatten() {
+                if let Err(diagnostics) = &result.output {
+                    for d in diagnostics {
+                        error!("flatten: {}: {}", result.path.display(), d);
+                    }
+                }
+                if let State::Rendering(r) = &mut self.state {
+                    match result.output {
+                        Ok(_) => r.message_board.write(MESSAGES.flatten)?,
+                        Err(_) => r.message_board.write(MESSAGES.flatten_error)?,
                     }
-                    _ => {}
                 }
             }
             if let State::Rendering(r) = &mut self.state {
+                let keyboard = self.window_manager.next_keyboard_frame();
+                if let Err(e) = self.renderer.update_keyboard(&keyboard) {
+                    error!("update_keyboard: {}", e);
+                }
                 if self.play {
                     r.parameters.mouse = {
                         let size = self.window_manager.main_window.inner_size().cast::<f32>();
@@ -638,7 +1061,19 @@ impl Application {
                             cursor_position.y as f32 / size.height,
                         ]
                     };
-                    r.parameters.time = self.timer.get().as_secs_f32();
+                    let time = self.timer.get().as_secs_f32();
+                    r.parameters.time_delta = time - r.parameters.time;
+                    r.parameters.time = time;
+                    r.parameters.set_date(chrono::Local::now());
+                } else {
+                    r.parameters.time_delta = 0.0;
+                }
+                if let Some(camera) = &mut r.camera {
+                    camera.update_drag(
+                        cursor_position,
+                        self.window_manager.main_window.inner_size(),
+                    );
+                    camera.apply(&mut r.parameters);
                 }
             }
             let ret = match &self.state {
@@ -647,6 +1082,8 @@ impl Application {
                     self.clear_color,
                     Some(&r.ps),
                     Some(&r.parameters),
+                    r.parameter_panel.parameters().data(),
+                    if self.play { &r.passes } else { &[] },
                     &self.state,
                 ),
                 _ => self.renderer.render(
@@ -654,12 +1091,19 @@ impl Application {
                     self.clear_color,
                     None,
                     None,
+                    &[],
+                    &[],
                     &self.state,
                 ),
             };
             if let Err(e) = ret.await {
                 error!("render: {}", e);
             }
+            if let State::Rendering(r) = &mut self.state {
+                if self.play {
+                    r.parameters.frame = r.parameters.frame.wrapping_add(1);
+                }
+            }
         }
         Ok(())
     }
@@ -691,7 +1135,25 @@ impl Application {
 
     async fn set_state(&mut self, new_state: State) {
         self.renderer.wait_all_signals().await;
-        self.state = new_state;
+        if let State::Rendering(r) = std::mem::replace(&mut self.state, new_state) {
+            self.user_parameters = Some((r.path, r.parameter_panel.into_parameters()));
+        }
+    }
+
+    async fn fit_window(
+        &mut self,
+        resolution: settings::Resolution,
+    ) -> Result<wita::PhysicalSize<u32>, Error> {
+        self.window_manager.update_resolution(resolution);
+        let mut size = self.window_manager.main_window.inner_size();
+        if self.window_manager.main_window.is_maximized() {
+            self.renderer.maximize(size).await?;
+        } else {
+            size.height = size.width * resolution.height / resolution.width;
+            self.window_manager.main_window.set_inner_size(size);
+            self.renderer.resize(size).await?;
+        }
+        Ok(size)
     }
 
     async fn reload_settings(&mut self) -> anyhow::Result<()> {
@@ -713,36 +1175,43 @@ impl Application {
             0.0,
         ];
         let ui_props = UiProperties::new(&settings, &self.ui_props.factory)?;
+        let recreate_compiler = settings.shader.dxc_path != self.settings.shader.dxc_path
+            || settings.shader.include_paths != self.settings.shader.include_paths
+            || settings.cache != self.settings.cache;
+        if recreate_compiler {
+            self.compiler = create_compiler(&settings.shader, &settings.cache)?;
+            self.compile_worker = CompileWorker::new(&settings.shader, &settings.cache);
+        }
+        let resolution = match &self.state {
+            State::Rendering(r) => r.project.as_ref().and_then(|p| p.resolution),
+            _ => None,
+        }
+        .unwrap_or(settings.resolution);
         self.renderer
             .recreate(
-                settings.resolution,
+                resolution,
                 &self.compiler,
                 shader_model,
                 Some(settings.max_frame_rate).filter(|v| *v > 0),
                 &settings.swap_chain,
             )
             .await?;
-        self.window_manager.update_resolution(settings.resolution);
-        let mut size = self.window_manager.main_window.inner_size();
-        if self.window_manager.main_window.is_maximized() {
-            self.renderer.maximize(size).await?;
-        } else {
-            size.height = size.width * settings.resolution.height / settings.resolution.width;
-            self.window_manager.main_window.set_inner_size(size);
-            self.renderer.resize(size).await?;
-        }
+        self.window_manager
+            .update_key_map(KeyboardMap::from_shortcuts(&settings.shortcuts));
+        let size = self.fit_window(resolution).await?;
         match &mut self.state {
             State::Rendering(r) => {
-                r.parameters.resolution = [
-                    settings.resolution.width as f32,
-                    settings.resolution.height as f32,
-                ];
+                r.parameters.resolution = [resolution.width as f32, resolution.height as f32];
+                if let Some(warnings) = &mut r.warnings {
+                    let dpi = self.window_manager.main_window.dpi();
+                    let size = size.to_logical(dpi as _).cast::<f32>();
+                    warnings.reset(&ui_props, size)?;
+                }
+                r.parameter_panel.reset(&ui_props)?;
             }
-            State::Error(em)
-                if em.path() == *SETTINGS_PATH || em.path() == *WINDOW_SETTING_PATH =>
-            {
+            State::Error(em) if self.reloader.is_setting(em.path()) => {
                 if let Some(path) = em.hlsl_path().cloned() {
-                    if let Err(e) = self.load_file(&path).await {
+                    if let Err(e) = self.load_file(&path) {
                         self.set_error(&path, e).await?;
                     }
                 } else {
@@ -756,9 +1225,16 @@ impl Application {
             }
             _ => {}
         }
+        self.reloader.set_options((&settings.monitor).into())?;
         self.settings = settings;
         self.ui_props = ui_props;
         self.clear_color = clear_color;
+        if let State::Rendering(r) = &self.state {
+            let path = r.path.clone();
+            if let Err(e) = self.load_file(&path) {
+                self.set_error(&path, e).await?;
+            }
+        }
         info!("reload settings.toml");
         Ok(())
     }
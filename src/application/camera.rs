@@ -0,0 +1,197 @@
+use super::*;
+
+const FOV_Y: f32 = std::f32::consts::FRAC_PI_4;
+const PITCH_LIMIT: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = dot(v, v).sqrt();
+    [v[0] / len, v[1] / len, v[2] / len]
+}
+
+fn look_at(eye: [f32; 3], center: [f32; 3], up: [f32; 3]) -> pixel_shader::Matrix {
+    let f = normalize(sub(center, eye));
+    let s = normalize(cross(f, up));
+    let u = cross(s, f);
+    [
+        [s[0], u[0], -f[0], 0.0],
+        [s[1], u[1], -f[1], 0.0],
+        [s[2], u[2], -f[2], 0.0],
+        [-dot(s, eye), -dot(u, eye), dot(f, eye), 1.0],
+    ]
+}
+
+fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> pixel_shader::Matrix {
+    let y = 1.0 / (fov_y / 2.0).tan();
+    let x = y / aspect;
+    let z = far / (near - far);
+    [
+        [x, 0.0, 0.0, 0.0],
+        [0.0, y, 0.0, 0.0],
+        [0.0, 0.0, z, -1.0],
+        [0.0, 0.0, near * z, 0.0],
+    ]
+}
+
+#[derive(Clone, Debug)]
+struct Drag {
+    position: wita::PhysicalPosition<i32>,
+    yaw: f32,
+    pitch: f32,
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct Camera {
+    bounds: [[f32; 3]; 2],
+    center: [f32; 3],
+    radius: f32,
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+    drag: Option<Drag>,
+}
+
+impl Camera {
+    pub fn new(bounds: [[f32; 3]; 2]) -> Self {
+        let [min, max] = bounds;
+        let center = [
+            (min[0] + max[0]) / 2.0,
+            (min[1] + max[1]) / 2.0,
+            (min[2] + max[2]) / 2.0,
+        ];
+        let radius = (dot(sub(max, min), sub(max, min)).sqrt() / 2.0).max(f32::EPSILON);
+        Self {
+            bounds,
+            center,
+            radius,
+            yaw: 0.0,
+            pitch: 0.0,
+            distance: radius / (FOV_Y / 2.0).sin(),
+            drag: None,
+        }
+    }
+
+    pub fn bounds(&self) -> [[f32; 3]; 2] {
+        self.bounds
+    }
+
+    pub fn begin_drag(&mut self, position: wita::PhysicalPosition<i32>) {
+        self.drag = Some(Drag {
+            position,
+            yaw: self.yaw,
+            pitch: self.pitch,
+        });
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag = None;
+    }
+
+    pub fn update_drag(
+        &mut self,
+        position: wita::PhysicalPosition<i32>,
+        size: wita::PhysicalSize<u32>,
+    ) {
+        if let Some(drag) = &self.drag {
+            let dx = (position.x - drag.position.x) as f32 / size.width.max(1) as f32;
+            let dy = (position.y - drag.position.y) as f32 / size.height.max(1) as f32;
+            self.yaw = drag.yaw - dx * std::f32::consts::TAU;
+            self.pitch = (drag.pitch + dy * std::f32::consts::PI).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+        }
+    }
+
+    pub fn zoom(&mut self, d: i32) {
+        let min = self.radius * 0.01;
+        let max = self.radius * 100.0;
+        self.distance = (self.distance * 1.1f32.powi(d)).clamp(min, max);
+    }
+
+    pub fn eye(&self) -> [f32; 3] {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        [
+            self.center[0] + self.distance * cos_pitch * sin_yaw,
+            self.center[1] + self.distance * sin_pitch,
+            self.center[2] + self.distance * cos_pitch * cos_yaw,
+        ]
+    }
+
+    pub fn apply(&self, parameters: &mut pixel_shader::Parameters) {
+        let [width, height] = parameters.resolution;
+        let aspect = if height > 0.0 { width / height } else { 1.0 };
+        parameters.model = pixel_shader::IDENTITY;
+        parameters.view = look_at(self.eye(), self.center, [0.0, 1.0, 0.0]);
+        parameters.projection = perspective(
+            FOV_Y,
+            aspect,
+            self.distance * 0.01,
+            (self.distance + self.radius) * 10.0,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform(m: &pixel_shader::Matrix, v: [f32; 3]) -> [f32; 4] {
+        let mut ret = [0.0; 4];
+        for (r, x) in ret.iter_mut().enumerate() {
+            *x = m[0][r] * v[0] + m[1][r] * v[1] + m[2][r] * v[2] + m[3][r];
+        }
+        ret
+    }
+
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn camera_matrices() {
+        let mut camera = Camera::new([[-1.0, -1.0, -1.0], [1.0, 3.0, 1.0]]);
+        assert!(camera.center == [0.0, 1.0, 0.0]);
+        let eye = camera.eye();
+        assert!(approx(eye[0], 0.0) && approx(eye[1], 1.0) && eye[2] > 0.0);
+        let mut parameters = pixel_shader::Parameters {
+            resolution: [1280.0, 720.0],
+            ..Default::default()
+        };
+        camera.apply(&mut parameters);
+        let v = transform(&parameters.view, camera.center);
+        assert!(approx(v[0], 0.0) && approx(v[1], 0.0) && approx(v[2], -camera.distance));
+        let near = camera.distance * 0.01;
+        let far = (camera.distance + camera.radius) * 10.0;
+        let p = transform(&parameters.projection, [0.0, 0.0, -near]);
+        assert!(approx(p[2] / p[3], 0.0));
+        let p = transform(&parameters.projection, [0.0, 0.0, -far]);
+        assert!(approx(p[2] / p[3], 1.0));
+        let p = transform(&parameters.projection, [1.0, 1.0, -1.0]);
+        assert!(approx(p[0] * 1280.0, p[1] * 720.0));
+        let distance = camera.distance;
+        camera.zoom(1);
+        assert!(approx(camera.distance, distance * 1.1));
+        camera.begin_drag(wita::PhysicalPosition::new(0, 0));
+        camera.update_drag(
+            wita::PhysicalPosition::new(0, 10000),
+            wita::PhysicalSize::new(100, 100),
+        );
+        assert!(camera.pitch == PITCH_LIMIT);
+        camera.end_drag();
+        assert!(camera.drag.is_none());
+    }
+}
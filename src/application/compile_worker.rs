@@ -0,0 +1,391 @@
+use super::*;
+use std::sync::mpsc;
+
+struct Request {
+    id: u64,
+    path: PathBuf,
+    shader_path: PathBuf,
+    directives: Option<hlsl::Directives>,
+    target: hlsl::Target,
+    args: Vec<String>,
+}
+
+struct FlattenRequest {
+    path: PathBuf,
+    shader_path: PathBuf,
+    directives: Option<hlsl::Directives>,
+    target: hlsl::Target,
+    args: Vec<String>,
+}
+
+enum Job {
+    Compile(Box<Request>),
+    Flatten(FlattenRequest),
+}
+
+pub(super) struct Shader {
+    pub blob: hlsl::Blob,
+    pub reflection: hlsl::Reflection,
+    pub annotations: hlsl::Annotations,
+    pub channels: Vec<Option<pixel_shader::Channel>>,
+    pub passes: Vec<PassShader>,
+    pub vertex: Option<(hlsl::Blob, hlsl::Reflection)>,
+    pub mesh: Option<mesh::Mesh>,
+}
+
+pub(super) struct PassShader {
+    pub index: usize,
+    pub path: PathBuf,
+    pub blob: hlsl::Blob,
+    pub reflection: hlsl::Reflection,
+    pub annotations: hlsl::Annotations,
+    pub resolution: Option<[u32; 2]>,
+}
+
+pub(super) struct CompileResult {
+    id: u64,
+    pub path: PathBuf,
+    pub shader: Result<Shader, Vec<hlsl::Diagnostic>>,
+    pub warnings: Vec<hlsl::Diagnostic>,
+    pub dependencies: Vec<PathBuf>,
+}
+
+pub(super) struct FlattenResult {
+    pub path: PathBuf,
+    pub output: Result<PathBuf, Vec<hlsl::Diagnostic>>,
+}
+
+impl Request {
+    fn new(
+        id: u64,
+        path: PathBuf,
+        project: Option<&project::Project>,
+        target: hlsl::Target,
+        args: &[String],
+    ) -> Self {
+        Self {
+            id,
+            shader_path: project.map_or_else(|| path.clone(), |p| p.shader.clone()),
+            directives: project.map(|p| p.directives.clone()),
+            path,
+            target,
+            args: args.to_vec(),
+        }
+    }
+}
+
+fn into_diagnostics(e: Error) -> Vec<hlsl::Diagnostic> {
+    match e {
+        Error::Compile(diagnostics) => diagnostics,
+        e => vec![hlsl::Diagnostic::new(hlsl::Severity::Error, e.to_string())],
+    }
+}
+
+fn compile_request(compiler: &hlsl::Compiler, req: Request) -> CompileResult {
+    let directives = shader_directives(&req.shader_path, req.directives.as_ref()).map(|mut d| {
+        for path in d
+            .channels
+            .iter_mut()
+            .filter_map(|c| c.path.as_mut())
+            .chain(d.passes.iter_mut().filter_map(|p| p.path.as_mut()))
+            .chain(d.mesh.as_mut())
+        {
+            if let Ok(canonical) = path.canonicalize() {
+                *path = canonical;
+            }
+        }
+        d
+    });
+    let mut dependencies = vec![req.path.clone()];
+    dependencies.extend(directives.iter().flat_map(|d| {
+        d.channels
+            .iter()
+            .filter_map(|c| c.path.clone())
+            .chain(d.passes.iter().filter_map(|p| p.path.clone()))
+            .chain(d.mesh.clone())
+    }));
+    let (pass_target, pass_args) = match &req.directives {
+        Some(directives) => {
+            let (_, target, args) = directives.apply("main", req.target, &req.args);
+            (req.target.with_shader_model(target.shader_model()), args)
+        }
+        None => (req.target, req.args.clone()),
+    };
+    let mut warnings = vec![];
+    let mut compile = |path: &Path, entry_point: &str, target, args: &[String]| {
+        let blob = compiler.compile_from_file(path, entry_point, target, args);
+        warnings.extend(compiler.warnings());
+        dependencies.extend(compiler.dependencies());
+        blob
+    };
+    let shader = directives.and_then(|directives| {
+        let (entry_point, target, args) = directives.apply("main", req.target, &req.args);
+        let blob = compile(&req.shader_path, &entry_point, target, &args)?;
+        let mut passes = vec![];
+        for (index, pass) in directives.passes.iter().enumerate() {
+            let path = match &pass.path {
+                Some(path) => path,
+                None => continue,
+            };
+            let (entry_point, target, args) = hlsl::Directives::from_file(path)?.apply(
+                pass.entry_point.as_deref().unwrap_or("main"),
+                pass_target,
+                &pass_args,
+            );
+            let blob = compile(path, &entry_point, target, &args)?;
+            passes.push(PassShader {
+                index,
+                path: path.clone(),
+                reflection: compiler.reflect(&blob)?,
+                annotations: hlsl::Annotations::from_file(path)?,
+                resolution: pass.resolution,
+                blob,
+            });
+        }
+        let pixel = !matches!(target, hlsl::Target::CS(_));
+        let vertex = match &directives.vertex_entry_point {
+            Some(_) if pixel => {
+                let target = hlsl::Target::VS(target.shader_model());
+                let (entry_point, target, args) = directives.apply("main", target, &req.args);
+                let blob = compile(&req.shader_path, &entry_point, target, &args)?;
+                let reflection = compiler.reflect(&blob)?;
+                Some((blob, reflection))
+            }
+            _ => None,
+        };
+        let mesh = match &directives.mesh {
+            Some(path) if pixel => Some(mesh::Mesh::load(path)?),
+            _ => vertex.as_ref().map(|_| mesh::Mesh::plane()),
+        };
+        Ok(Shader {
+            reflection: compiler.reflect(&blob)?,
+            annotations: hlsl::Annotations::from_file(&req.shader_path)?,
+            channels: directives
+                .channels
+                .iter()
+                .map(pixel_shader::Channel::load)
+                .collect::<Result<_, _>>()?,
+            passes,
+            vertex,
+            mesh,
+            blob,
+        })
+    });
+    dependencies.sort();
+    dependencies.dedup();
+    CompileResult {
+        id: req.id,
+        path: req.path,
+        shader: shader.map_err(into_diagnostics),
+        warnings,
+        dependencies,
+    }
+}
+
+pub(super) fn compile(
+    compiler: &hlsl::Compiler,
+    path: PathBuf,
+    project: Option<&project::Project>,
+    target: hlsl::Target,
+    args: &[String],
+) -> CompileResult {
+    compile_request(compiler, Request::new(0, path, project, target, args))
+}
+
+pub(super) struct CompileWorker {
+    tx: mpsc::Sender<Job>,
+    rx: mpsc::Receiver<CompileResult>,
+    flatten_rx: mpsc::Receiver<FlattenResult>,
+    current: Option<u64>,
+    next_id: u64,
+}
+
+impl CompileWorker {
+    pub fn new(shader: &settings::Shader, cache: &settings::Cache) -> Self {
+        let (tx, request_rx) = mpsc::channel::<Job>();
+        let (result_tx, rx) = mpsc::channel();
+        let (flatten_tx, flatten_rx) = mpsc::channel();
+        let shader = shader.clone();
+        let cache = cache.clone();
+        std::thread::spawn(move || {
+            debug!("start compile worker");
+            let com = coinit::init(coinit::MULTITHREADED | coinit::DISABLE_OLE1DDE);
+            let compiler = match &com {
+                Ok(_) => create_compiler(&shader, &cache).map_err(|e| e.to_string()),
+                Err(e) => {
+                    error!("compile worker: {}", e);
+                    Err(e.to_string())
+                }
+            };
+            let error = |e: &String| vec![hlsl::Diagnostic::new(hlsl::Severity::Error, e.clone())];
+            'worker: while let Ok(job) = request_rx.recv() {
+                let jobs = std::iter::once(job)
+                    .chain(request_rx.try_iter())
+                    .collect::<Vec<_>>();
+                let last_compile = jobs.iter().rposition(|j| matches!(j, Job::Compile(_)));
+                for (i, job) in jobs.into_iter().enumerate() {
+                    let ret = match job {
+                        Job::Compile(req) if Some(i) != last_compile => {
+                            debug!("compile worker: skip {}", req.path.display());
+                            Ok(())
+                        }
+                        Job::Compile(req) => {
+                            let result = match &compiler {
+                                Ok(compiler) => compile_request(compiler, *req),
+                                Err(e) => CompileResult {
+                                    id: req.id,
+                                    path: req.path,
+                                    shader: Err(error(e)),
+                                    warnings: vec![],
+                                    dependencies: vec![],
+                                },
+                            };
+                            result_tx.send(result).map_err(|_| ())
+                        }
+                        Job::Flatten(req) => {
+                            let output = match &compiler {
+                                Ok(compiler) => flatten_file(
+                                    compiler,
+                                    &req.shader_path,
+                                    req.directives.as_ref(),
+                                    None,
+                                    req.target,
+                                    &req.args,
+                                )
+                                .map_err(into_diagnostics),
+                                Err(e) => Err(error(e)),
+                            };
+                            let result = FlattenResult {
+                                path: req.path,
+                                output,
+                            };
+                            flatten_tx.send(result).map_err(|_| ())
+                        }
+                    };
+                    if ret.is_err() {
+                        break 'worker;
+                    }
+                }
+            }
+            debug!("end compile worker");
+        });
+        Self {
+            tx,
+            rx,
+            flatten_rx,
+            current: None,
+            next_id: 0,
+        }
+    }
+
+    pub fn compile(
+        &mut self,
+        path: PathBuf,
+        project: Option<&project::Project>,
+        target: hlsl::Target,
+        args: &[String],
+    ) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.current = Some(id);
+        self.tx
+            .send(Job::Compile(Box::new(Request::new(
+                id, path, project, target, args,
+            ))))
+            .ok();
+    }
+
+    pub fn flatten(
+        &self,
+        path: PathBuf,
+        project: Option<&project::Project>,
+        target: hlsl::Target,
+        args: &[String],
+    ) {
+        self.tx
+            .send(Job::Flatten(FlattenRequest {
+                shader_path: project.map_or_else(|| path.clone(), |p| p.shader.clone()),
+                directives: project.map(|p| p.directives.clone()),
+                path,
+                target,
+                args: args.to_vec(),
+            }))
+            .ok();
+    }
+
+    pub fn try_recv_flatten(&self) -> Option<FlattenResult> {
+        self.flatten_rx.try_recv().ok()
+    }
+
+    pub fn try_recv(&mut self) -> Option<CompileResult> {
+        while let Ok(result) = self.rx.try_recv() {
+            if Some(result.id) == self.current {
+                self.current = None;
+                return Some(result);
+            }
+            debug!("discard stale compile result: {}", result.path.display());
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_request_compute_mode() {
+        let dir = Path::new("./target/compile_worker_test");
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join("compute.hlsl");
+        std::fs::write(
+            &path,
+            "// hlsl_box: mode=compute, vertex=vs_main\n\
+             #include \"hlsl_box.hlsli\"\n\
+             float4 vs_main(float4 pos: POSITION): SV_Position { return pos; }\n\
+             [numthreads(8, 8, 1)]\n\
+             void main(uint3 id: SV_DispatchThreadID) { HLSLBoxOutput[id.xy] = 1.0; }\n",
+        )
+        .unwrap();
+        let compiler = hlsl::Compiler::new().unwrap();
+        let target = hlsl::Target::PS(hlsl::ShaderModel::specify("6_0").unwrap());
+        let result = compile(&compiler, path, None, target, &[]);
+        let shader = result.shader.unwrap();
+        assert!(shader.reflection.thread_group_size == Some([8, 8, 1]));
+        assert!(shader.vertex.is_none());
+        assert!(shader.mesh.is_none());
+    }
+
+    #[test]
+    fn flatten_project_directives() {
+        let dir = Path::new("./target/compile_worker_test");
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join("flatten.hlsl");
+        std::fs::write(
+            &path,
+            "#ifdef FOO\n\
+             float4 ps_main(): SV_Target { return 1.0; }\n\
+             #endif\n",
+        )
+        .unwrap();
+        let project = project::Project::parse(
+            "shader = \"flatten.hlsl\"\nentry = \"ps_main\"\ndefines = [\"FOO\"]\n",
+            &dir.join("flatten.hlslbox.toml"),
+        )
+        .unwrap();
+        let compiler = hlsl::Compiler::new().unwrap();
+        let target = hlsl::Target::PS(hlsl::ShaderModel::specify("6_0").unwrap());
+        let output = flatten_file(
+            &compiler,
+            &project.shader,
+            Some(&project.directives),
+            Some(&dir.join("flatten.flat.hlsl")),
+            target,
+            &[],
+        )
+        .unwrap();
+        let src = std::fs::read_to_string(output).unwrap();
+        assert!(src.starts_with("// hlsl_box: entry=ps_main"));
+        assert!(src.contains("return 1.0;"));
+    }
+}
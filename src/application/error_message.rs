@@ -1,6 +1,5 @@
 use super::*;
 use gecl::Collision as _;
-use regex::Regex;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 enum TextColor {
@@ -32,12 +31,49 @@ enum ScrollBarState {
     Moving,
 }
 
-static RE: Lazy<Regex> = Lazy::new(|| Regex::new("(^.+:[0-9]+:[0-9]+: )(\\w+)(: )(.+)").unwrap());
+enum Line {
+    Text(String),
+    Diagnostic {
+        location: String,
+        severity: hlsl::Severity,
+        message: String,
+    },
+    UnderLine(String),
+}
+
+impl Line {
+    fn from_text(text: &str) -> Vec<Self> {
+        text.split('\n')
+            .map(|t| Self::Text(t.to_string()))
+            .collect()
+    }
+
+    fn from_diagnostic(d: &hlsl::Diagnostic) -> Vec<Self> {
+        let mut lines = match &d.file {
+            Some(file) => vec![Self::Diagnostic {
+                location: format!("{}:{}:{}: ", file.display(), d.line, d.column),
+                severity: d.severity,
+                message: d.message.clone(),
+            }],
+            None => Self::from_text(&d.message),
+        };
+        for s in d.snippet.iter() {
+            if s.chars()
+                .all(|c| c.is_ascii_whitespace() || c == '~' || c == '^')
+            {
+                lines.push(Self::UnderLine(s.clone()));
+            } else {
+                lines.push(Self::Text(s.clone()));
+            }
+        }
+        lines
+    }
+}
 
 pub(super) struct ErrorMessage {
     path: PathBuf,
     ui_props: UiProperties,
-    text: Vec<String>,
+    lines: Vec<Line>,
     layouts: VecDeque<Vec<Layout>>,
     current_line: usize,
     scroll_bar_state: ScrollBarState,
@@ -54,17 +90,40 @@ impl ErrorMessage {
         view_size: wita::LogicalSize<f32>,
         hlsl_path: Option<PathBuf>,
     ) -> anyhow::Result<Self> {
-        let text = if path == *SETTINGS_PATH || path == *WINDOW_SETTING_PATH {
-            format!("{}:\n{}", path.display(), e)
-        } else {
-            format!("{}", e)
+        let lines = match e {
+            _ if path == *SETTINGS_PATH || path == *WINDOW_SETTING_PATH => {
+                Line::from_text(&format!("{}:\n{}", path.display(), e))
+            }
+            Error::Compile(diagnostics) => {
+                diagnostics.iter().flat_map(Line::from_diagnostic).collect()
+            }
+            _ => Line::from_text(&e.to_string()),
         };
-        let text = text.split('\n').map(|t| t.to_string()).collect::<Vec<_>>();
+        Self::with_lines(path, lines, ui_props, view_size, hlsl_path)
+    }
+
+    pub fn from_diagnostics(
+        path: PathBuf,
+        diagnostics: &[hlsl::Diagnostic],
+        ui_props: &UiProperties,
+        view_size: wita::LogicalSize<f32>,
+    ) -> anyhow::Result<Self> {
+        let lines = diagnostics.iter().flat_map(Line::from_diagnostic).collect();
+        Self::with_lines(path, lines, ui_props, view_size, None)
+    }
+
+    fn with_lines(
+        path: PathBuf,
+        lines: Vec<Line>,
+        ui_props: &UiProperties,
+        view_size: wita::LogicalSize<f32>,
+        hlsl_path: Option<PathBuf>,
+    ) -> anyhow::Result<Self> {
         let layouts = VecDeque::new();
         let mut this = Self {
             path,
             ui_props: ui_props.clone(),
-            text,
+            lines,
             layouts,
             current_line: 0,
             scroll_bar_state: ScrollBarState::None,
@@ -74,9 +133,9 @@ impl ErrorMessage {
         };
         let mut index = 0;
         let mut height = 0.0;
-        while index < this.text.len() && height < view_size.height {
+        while index < this.lines.len() && height < view_size.height {
             let mut buffer = Vec::new();
-            this.parse_text(&mut buffer, &this.text[index], view_size)?;
+            this.parse_text(&mut buffer, &this.lines[index], view_size)?;
             height += buffer
                 .iter()
                 .fold(0.0, |h, l| h + l.new_line_height(this.line_height));
@@ -111,7 +170,7 @@ impl ErrorMessage {
                 line -= d;
             }
         } else {
-            line = (line + d as usize).min(self.text.len() - 1);
+            line = (line + d as usize).min(self.lines.len().saturating_sub(1));
         }
         if self.current_line == line {
             return Ok(());
@@ -120,7 +179,7 @@ impl ErrorMessage {
             let mut index = self.current_line as isize - 1;
             while index >= line as _ {
                 let mut buffer = Vec::new();
-                self.parse_text(&mut buffer, &self.text[index as usize], size)?;
+                self.parse_text(&mut buffer, &self.lines[index as usize], size)?;
                 self.layouts.push_front(buffer);
                 index -= 1;
             }
@@ -155,9 +214,9 @@ impl ErrorMessage {
                 .flatten()
                 .fold(0.0, |h, l| h + l.new_line_height(self.line_height));
             let mut index = line as usize + self.layouts.len() - 1;
-            while index < self.text.len() && height < size.height {
+            while index < self.lines.len() && height < size.height {
                 let mut buffer = Vec::new();
-                self.parse_text(&mut buffer, &self.text[index], size)?;
+                self.parse_text(&mut buffer, &self.lines[index], size)?;
                 height += buffer
                     .iter()
                     .fold(0.0, |h, l| h + l.new_line_height(self.line_height));
@@ -178,7 +237,7 @@ impl ErrorMessage {
         let props = &self.ui_props.scroll_bar;
         let line_height = self.ui_props.line_height;
         let x = view_size.width - props.width;
-        let a = self.text.len() as f32 + view_size.height / line_height - 1.0;
+        let a = self.lines.len() as f32 + view_size.height / line_height - 1.0;
         let thumb_origin = [x, self.current_line as f32 * view_size.height / a];
         let thumb_size = [
             props.width,
@@ -208,7 +267,7 @@ impl ErrorMessage {
                 }
             }
             ScrollBarState::Moving => {
-                let max_line = self.text.len() - 1;
+                let max_line = self.lines.len().saturating_sub(1);
                 let line = ((mouse_pos.y - self.dy) * max_line as f32
                     / (view_size.height - thumb_size[1]))
                     .floor()
@@ -266,7 +325,7 @@ impl ErrorMessage {
         let bg_origin = [view_size.width - props.width, 0.0];
         let bg_size = [props.width, view_size.height];
         cmd.fill(&mltg::Rect::new(bg_origin, bg_size), &props.bg_color);
-        let a = self.text.len() as f32 + view_size.height / line_height - 1.0;
+        let a = self.lines.len() as f32 + view_size.height / line_height - 1.0;
         let thumb_origin = [
             bg_origin[0],
             self.current_line as f32 * view_size.height / a,
@@ -287,9 +346,9 @@ impl ErrorMessage {
         let mut height = 0.0;
         let mut index = self.current_line as usize;
         self.layouts.clear();
-        while index < self.text.len() && height < view_size.height {
+        while index < self.lines.len() && height < view_size.height {
             let mut buffer = Vec::new();
-            self.parse_text(&mut buffer, &self.text[index], view_size)?;
+            self.parse_text(&mut buffer, &self.lines[index], view_size)?;
             height += buffer
                 .iter()
                 .fold(0.0, |h, l| h + l.new_line_height(self.line_height));
@@ -311,52 +370,53 @@ impl ErrorMessage {
     fn parse_text(
         &self,
         buffer: &mut Vec<Layout>,
-        text: &str,
+        line: &Line,
         view_size: wita::LogicalSize<f32>,
     ) -> anyhow::Result<()> {
-        if let Some(m) = RE.captures(text) {
-            let x = self.create_text_layouts(
-                buffer,
-                m.get(1).unwrap().as_str(),
-                view_size,
-                TextColor::Text,
-                0.0,
-                false,
-            )?;
-            let t = m.get(2).unwrap().as_str();
-            let color = if t.starts_with("error") {
-                TextColor::Error
-            } else if t.starts_with("warning") {
-                TextColor::Warn
-            } else if t.starts_with("info") {
-                TextColor::Info
-            } else {
-                TextColor::Text
-            };
-            let x = self.create_text_layouts(buffer, t, view_size, color, x, true)?;
-            let x = self.create_text_layouts(
-                buffer,
-                m.get(3).unwrap().as_str(),
-                view_size,
-                TextColor::Text,
-                x,
-                true,
-            )?;
-            self.create_text_layouts(
-                buffer,
-                m.get(4).unwrap().as_str(),
-                view_size,
-                TextColor::Text,
-                x,
-                true,
-            )?;
-        } else if text
-            .chars()
-            .all(|c| c.is_ascii_whitespace() || c == '~' || c == '^')
-        {
-            self.create_text_layouts(buffer, text, view_size, TextColor::UnderLine, 0.0, false)?;
-        } else {
-            self.create_text_layouts(buffer, text, view_size, TextColor::Text, 0.0, false)?;
+        match line {
+            Line::Text(text) => {
+                self.create_text_layouts(buffer, text, view_size, TextColor::Text, 0.0, false)?;
+            }
+            Line::UnderLine(text) => {
+                self.create_text_layouts(
+                    buffer,
+                    text,
+                    view_size,
+                    TextColor::UnderLine,
+                    0.0,
+                    false,
+                )?;
+            }
+            Line::Diagnostic {
+                location,
+                severity,
+                message,
+            } => {
+                let x = self.create_text_layouts(
+                    buffer,
+                    location,
+                    view_size,
+                    TextColor::Text,
+                    0.0,
+                    false,
+                )?;
+                let color = match severity {
+                    hlsl::Severity::Error => TextColor::Error,
+                    hlsl::Severity::Warning => TextColor::Warn,
+                    hlsl::Severity::Note => TextColor::Info,
+                };
+                let x = self.create_text_layouts(
+                    buffer,
+                    &severity.to_string(),
+                    view_size,
+                    color,
+                    x,
+                    true,
+                )?;
+                let x =
+                    self.create_text_layouts(buffer, ": ", view_size, TextColor::Text, x, true)?;
+                self.create_text_layouts(buffer, message, view_size, TextColor::Text, x, true)?;
+            }
         }
         buffer.push(Layout::NewLine);
         Ok(())
@@ -0,0 +1,194 @@
+use super::*;
+use std::ops::Range;
+
+pub struct RenderOptions {
+    pub input_file: PathBuf,
+    pub output: PathBuf,
+    pub resolution: Option<settings::Resolution>,
+    pub start: f32,
+    pub end: Option<f32>,
+    pub frame_rate: Option<u32>,
+}
+
+fn frame_range(start: f32, end: Option<f32>, frame_rate: u32, end_frame: u64) -> Range<u64> {
+    let first = (start.max(0.0) * frame_rate as f32).round() as u64;
+    let last = end.map_or(end_frame, |end| {
+        (end.max(0.0) * frame_rate as f32).round() as u64
+    });
+    first..last.max(first + 1)
+}
+
+fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("mp4"))
+}
+
+pub async fn render(options: &RenderOptions) -> anyhow::Result<()> {
+    let settings = Settings::load(&*SETTINGS_PATH).unwrap_or_else(|e| {
+        warn!("render: {}", e);
+        Settings::default()
+    });
+    let path = options
+        .input_file
+        .canonicalize()
+        .map_err(|_| Error::ReadFile(options.input_file.clone()))?;
+    let project = project::is_project(&path)
+        .then(|| project::Project::load(&path))
+        .transpose()?;
+    let compiler = create_compiler(&settings.shader, &settings.cache)?;
+    let d3d12_device: ID3D12Device = unsafe {
+        let mut device = None;
+        D3D12CreateDevice(None, D3D_FEATURE_LEVEL_12_1, &mut device).map(|_| device.unwrap())?
+    };
+    let shader_model = hlsl::ShaderModel::new(&d3d12_device, settings.shader.version.as_ref())?;
+    let result = compile(
+        &compiler,
+        path.clone(),
+        project.as_ref(),
+        hlsl::Target::PS(shader_model),
+        &settings.shader.ps_args,
+    );
+    for w in result.warnings.iter() {
+        warn!("{}", w);
+    }
+    let shader = result.shader.map_err(Error::Compile)?;
+    let resolution = options
+        .resolution
+        .or_else(|| project.as_ref().and_then(|p| p.resolution))
+        .unwrap_or(settings.resolution);
+    let video = match &project {
+        Some(project) => project.video(&settings.video),
+        None => settings.video.clone(),
+    };
+    let frame_rate = options.frame_rate.unwrap_or(video.frame_rate).max(1);
+    let mut renderer =
+        Headless::new(&d3d12_device, resolution.into(), &compiler, shader_model).await?;
+    let ps = renderer.create_pixel_shader_pipeline(
+        &format!("{}", path.display()),
+        &shader.blob,
+        &shader.reflection,
+        shader
+            .vertex
+            .as_ref()
+            .map(|(blob, reflection)| (blob, reflection)),
+        shader.mesh.is_some(),
+    )?;
+    renderer.set_mesh(shader.mesh.as_ref()).await?;
+    renderer.set_channels(&shader.channels).await?;
+    let mut passes = Vec::with_capacity(shader.passes.len());
+    let mut pass_sizes = [None; hlsl::PASS_COUNT];
+    for pass in shader.passes.iter() {
+        let pipeline = renderer.create_pass_pipeline(
+            &format!("{}", pass.path.display()),
+            &pass.blob,
+            &pass.reflection,
+        )?;
+        let mut user_parameters = UserParameters::new(
+            pixel_shader::user_constant_buffer(&pass.reflection),
+            &pass.annotations,
+            None,
+        );
+        if let Some(project) = &project {
+            user_parameters.apply_preset(&project.uniforms);
+        }
+        pass_sizes[pass.index] = Some(pass.resolution.map_or_else(
+            || resolution.into(),
+            |[width, height]| wita::PhysicalSize::new(width, height),
+        ));
+        passes.push(pixel_shader::Pass {
+            index: pass.index,
+            pipeline,
+            user_parameters: user_parameters.data().to_vec(),
+        });
+    }
+    renderer.set_passes(pass_sizes)?;
+    let mut user_parameters = UserParameters::new(
+        pixel_shader::user_constant_buffer(&shader.reflection),
+        &shader.annotations,
+        None,
+    );
+    if let Some(project) = &project {
+        user_parameters.apply_preset(&project.uniforms);
+    }
+    let params = settings::Params::load(settings::Params::path(&path)).unwrap_or_else(|e| {
+        warn!("load params: {}", e);
+        settings::Params::default()
+    });
+    if let Some(preset) = params.current() {
+        user_parameters.apply_preset(preset);
+    }
+    let mut parameters = pixel_shader::Parameters {
+        resolution: [resolution.width as _, resolution.height as _],
+        time_delta: 1.0 / frame_rate as f32,
+        ..Default::default()
+    };
+    parameters.set_date(chrono::Local::now());
+    if let Some(mesh) = &shader.mesh {
+        Camera::new(mesh.bounds()).apply(&mut parameters);
+    }
+    let range = frame_range(options.start, options.end, frame_rate, video.end_frame);
+    let mut video_file = if is_video(&options.output) {
+        Some(VideoFile::new(
+            &options.output,
+            resolution.into(),
+            frame_rate,
+        )?)
+    } else {
+        std::fs::create_dir_all(&options.output)
+            .map_err(|_| Error::CreateFile(options.output.clone()))?;
+        None
+    };
+    let begin = if passes.is_empty() { range.start } else { 0 };
+    info!(
+        "render: {} frames {}..{} at {}x{}",
+        path.display(),
+        range.start,
+        range.end,
+        resolution.width,
+        resolution.height
+    );
+    for frame in begin..range.end {
+        parameters.time = frame as f32 / frame_rate as f32;
+        parameters.frame = frame as u32;
+        let img = renderer
+            .render(&ps, &parameters, user_parameters.data(), &passes)
+            .await?;
+        if frame < range.start {
+            continue;
+        }
+        match video_file.as_mut() {
+            Some(video_file) => video_file.write(&img)?,
+            None => {
+                let file = options.output.join(format!("{:06}.png", frame));
+                img.save(&file).map_err(|_| Error::CreateFile(file))?;
+            }
+        }
+    }
+    if let Some(video_file) = video_file {
+        video_file.finish()?;
+    }
+    info!("render: {}", options.output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_frame_range() {
+        assert!(frame_range(0.0, Some(2.0), 30, 300) == (0..60));
+        assert!(frame_range(1.0, None, 60, 300) == (60..300));
+        assert!(frame_range(1.5, Some(1.5), 30, 300) == (45..46));
+        assert!(frame_range(-1.0, Some(0.1), 10, 300) == (0..1));
+    }
+
+    #[test]
+    fn render_output_kind() {
+        assert!(is_video(Path::new("out/video.mp4")));
+        assert!(is_video(Path::new("VIDEO.MP4")));
+        assert!(!is_video(Path::new("out/frames")));
+        assert!(!is_video(Path::new("out/frame.png")));
+    }
+}
@@ -87,6 +87,21 @@ impl Anim for Rest {
     }
 }
 
+struct Hold {
+    rest: Rest,
+    done: Rc<Cell<bool>>,
+}
+
+impl Anim for Hold {
+    fn draw(&self, cmd: &mltg::DrawCommand, size: wita::LogicalSize<f32>) {
+        self.rest.draw(cmd, size);
+    }
+
+    fn update(&mut self, _d: std::time::Duration) -> bool {
+        self.done.get()
+    }
+}
+
 struct Message {
     anim: Vec<Box<dyn Anim>>,
     p: usize,
@@ -123,6 +138,7 @@ pub(super) struct MessageBoard {
     ui_props: UiProperties,
     messages: RefCell<Vec<(Message, usize)>>,
     y_offset: f32,
+    status: Option<Rc<Cell<bool>>>,
 }
 
 impl MessageBoard {
@@ -132,10 +148,31 @@ impl MessageBoard {
             ui_props: ui_props.clone(),
             messages: RefCell::new(Vec::new()),
             y_offset,
+            status: None,
         }
     }
 
     pub fn write(&mut self, text: impl AsRef<str>) -> anyhow::Result<()> {
+        self.push(text, None)
+    }
+
+    pub fn show_status(&mut self, text: impl AsRef<str>) -> anyhow::Result<()> {
+        if self.status.is_some() {
+            return Ok(());
+        }
+        let done = Rc::new(Cell::new(false));
+        self.push(text, Some(done.clone()))?;
+        self.status = Some(done);
+        Ok(())
+    }
+
+    pub fn hide_status(&mut self) {
+        if let Some(done) = self.status.take() {
+            done.set(true);
+        }
+    }
+
+    fn push(&mut self, text: impl AsRef<str>, hold: Option<Rc<Cell<bool>>>) -> anyhow::Result<()> {
         let text = self.factory.create_text_layout(
             text,
             &self.ui_props.text_format,
@@ -179,7 +216,10 @@ impl MessageBoard {
         };
         let anim = vec![
             Box::new(slide_in) as Box<dyn Anim>,
-            Box::new(rest),
+            match hold {
+                Some(done) => Box::new(Hold { rest, done }),
+                None => Box::new(rest),
+            },
             Box::new(slide_out),
         ];
         messages.push((Message::new(anim), iy));
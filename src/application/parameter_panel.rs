@@ -0,0 +1,592 @@
+use super::*;
+use gecl::Collision as _;
+
+const MARGIN: f32 = 5.0;
+const LABEL_WIDTH: f32 = 110.0;
+const TRACK_WIDTH: f32 = 120.0;
+const TRACK_HEIGHT: f32 = 6.0;
+const VALUE_WIDTH: f32 = 60.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ScalarType {
+    Float,
+    Int,
+    Uint,
+    Bool,
+}
+
+fn parse_type(type_name: &str) -> Option<(ScalarType, usize)> {
+    const TYPES: &[(&str, ScalarType)] = &[
+        ("float", ScalarType::Float),
+        ("half", ScalarType::Float),
+        ("int", ScalarType::Int),
+        ("uint", ScalarType::Uint),
+        ("dword", ScalarType::Uint),
+        ("bool", ScalarType::Bool),
+    ];
+    let (scalar, n) = TYPES
+        .iter()
+        .find_map(|(name, t)| type_name.strip_prefix(name).map(|n| (*t, n)))?;
+    match n {
+        "" => Some((scalar, 1)),
+        "1" | "2" | "3" | "4" => Some((scalar, n.parse().unwrap())),
+        _ => None,
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Parameter {
+    name: String,
+    scalar: ScalarType,
+    offset: usize,
+    widget: hlsl::Widget,
+    range: [f32; 2],
+    step: Option<f32>,
+    values: Vec<f32>,
+}
+
+impl Parameter {
+    fn new(v: &hlsl::Variable, annotations: &hlsl::Annotations) -> Option<Self> {
+        let (scalar, n) = parse_type(&v.type_name)?;
+        let name = v.name.rsplit('.').next().unwrap_or(&v.name).to_string();
+        let annotation = annotations.get(&name).cloned().unwrap_or_default();
+        let widget = match scalar {
+            ScalarType::Bool => hlsl::Widget::Toggle,
+            _ => annotation.widget,
+        };
+        let range = match widget {
+            hlsl::Widget::Slider => {
+                let [a, b] = annotation.range.unwrap_or(match scalar {
+                    ScalarType::Float => [0.0, 1.0],
+                    _ => [0.0, 10.0],
+                });
+                [a.min(b), a.max(b)]
+            }
+            _ => [0.0, 1.0],
+        };
+        let step = match (widget, scalar) {
+            (hlsl::Widget::Toggle, _) => Some(1.0),
+            (_, ScalarType::Float) => annotation.step,
+            _ => Some(annotation.step.map_or(1.0, |s| s.round().max(1.0))),
+        };
+        let mut this = Self {
+            name,
+            scalar,
+            offset: v.offset as _,
+            widget,
+            range,
+            step,
+            values: vec![],
+        };
+        this.values = (0..n)
+            .map(|i| {
+                let v = match annotation.default.as_slice() {
+                    [v] => *v,
+                    default => default.get(i).copied().unwrap_or(0.0),
+                };
+                this.quantize(v)
+            })
+            .collect();
+        Some(this)
+    }
+
+    fn quantize(&self, v: f32) -> f32 {
+        let [min, max] = self.range;
+        let v = match self.step {
+            Some(step) => min + ((v - min) / step).round() * step,
+            None => v,
+        };
+        v.clamp(min, max)
+    }
+
+    fn is_compatible(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.scalar == other.scalar
+            && self.values.len() == other.values.len()
+    }
+
+    fn write(&self, data: &mut [u8]) {
+        for (i, v) in self.values.iter().enumerate() {
+            let bytes = match self.scalar {
+                ScalarType::Float => v.to_le_bytes(),
+                ScalarType::Int => (v.round() as i32).to_le_bytes(),
+                ScalarType::Uint => (v.round().max(0.0) as u32).to_le_bytes(),
+                ScalarType::Bool => ((*v != 0.0) as u32).to_le_bytes(),
+            };
+            let offset = self.offset + i * 4;
+            if let Some(dest) = data.get_mut(offset..offset + 4) {
+                dest.copy_from_slice(&bytes);
+            }
+        }
+    }
+
+    fn label(&self, component: usize) -> String {
+        if self.values.len() == 1 {
+            return self.name.clone();
+        }
+        let suffix = match self.widget {
+            hlsl::Widget::Color => "rgba",
+            _ => "xyzw",
+        };
+        format!("{}.{}", self.name, &suffix[component..component + 1])
+    }
+
+    fn value_text(&self, component: usize) -> String {
+        let v = self.values[component];
+        match (self.widget, self.scalar) {
+            (hlsl::Widget::Toggle, _) => String::new(),
+            (_, ScalarType::Float) => format!("{:.3}", v),
+            _ => format!("{}", v.round()),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub(super) struct UserParameters {
+    params: Vec<Parameter>,
+    data: Vec<u8>,
+}
+
+impl UserParameters {
+    pub fn new(
+        cb: Option<&hlsl::ConstantBuffer>,
+        annotations: &hlsl::Annotations,
+        previous: Option<&UserParameters>,
+    ) -> Self {
+        let cb = match cb {
+            Some(cb) => cb,
+            None => return Self::default(),
+        };
+        let params = cb
+            .variables
+            .iter()
+            .filter_map(|v| Parameter::new(v, annotations))
+            .map(|mut p| {
+                let prev = previous
+                    .and_then(|prev| prev.params.iter().find(|prev| prev.is_compatible(&p)));
+                if let Some(prev) = prev {
+                    p.values = prev.values.iter().map(|v| p.quantize(*v)).collect();
+                }
+                p
+            })
+            .collect();
+        let mut this = Self {
+            params,
+            data: vec![0; cb.size as _],
+        };
+        this.update();
+        this
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn preset(&self) -> settings::Preset {
+        self.params
+            .iter()
+            .map(|p| (p.name.clone(), settings::ParamValue::new(&p.values)))
+            .collect()
+    }
+
+    pub fn apply_preset(&mut self, preset: &settings::Preset) {
+        for param in self.params.iter_mut() {
+            let values = match preset.get(&param.name).map(|v| v.values()) {
+                Some(&[v]) => vec![v; param.values.len()],
+                Some(values) if values.len() == param.values.len() => values.to_vec(),
+                _ => continue,
+            };
+            param.values = values.iter().map(|v| param.quantize(*v)).collect();
+        }
+        self.update();
+    }
+
+    fn set(&mut self, index: usize, component: usize, v: f32) {
+        let param = &mut self.params[index];
+        param.values[component] = param.quantize(v);
+        self.update();
+    }
+
+    fn update(&mut self) {
+        for param in self.params.iter() {
+            param.write(&mut self.data);
+        }
+    }
+}
+
+struct Row {
+    param: usize,
+    component: usize,
+    label: mltg::TextLayout,
+    value: mltg::TextLayout,
+}
+
+pub(super) struct ParameterPanel {
+    ui_props: UiProperties,
+    parameters: UserParameters,
+    rows: Vec<Row>,
+    swatches: Vec<Option<mltg::Brush>>,
+    position: mltg::Point,
+    hover: Option<usize>,
+    dragging: Option<usize>,
+}
+
+impl ParameterPanel {
+    pub fn new(
+        ui_props: &UiProperties,
+        parameters: UserParameters,
+        position: impl Into<mltg::Point>,
+    ) -> Result<Self, Error> {
+        let mut this = Self {
+            ui_props: ui_props.clone(),
+            parameters,
+            rows: vec![],
+            swatches: vec![],
+            position: position.into(),
+            hover: None,
+            dragging: None,
+        };
+        this.create_layouts()?;
+        Ok(this)
+    }
+
+    pub fn parameters(&self) -> &UserParameters {
+        &self.parameters
+    }
+
+    pub fn into_parameters(self) -> UserParameters {
+        self.parameters
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parameters.is_empty()
+    }
+
+    pub fn apply_preset(&mut self, preset: &settings::Preset) -> Result<(), Error> {
+        self.parameters.apply_preset(preset);
+        self.create_layouts()
+    }
+
+    pub fn reset(&mut self, ui_props: &UiProperties) -> Result<(), Error> {
+        self.ui_props = ui_props.clone();
+        self.create_layouts()
+    }
+
+    fn create_text(&self, text: &str) -> Result<mltg::TextLayout, Error> {
+        Ok(self.ui_props.factory.create_text_layout(
+            text,
+            &self.ui_props.text_format,
+            mltg::TextAlignment::Leading,
+            None,
+        )?)
+    }
+
+    fn create_swatch(&self, index: usize) -> Result<Option<mltg::Brush>, Error> {
+        let param = &self.parameters.params[index];
+        if param.widget != hlsl::Widget::Color {
+            return Ok(None);
+        }
+        let v = |i: usize| param.values.get(i).copied().unwrap_or(1.0);
+        let brush = self
+            .ui_props
+            .factory
+            .create_solid_color_brush([v(0), v(1), v(2), v(3)])?;
+        Ok(Some(brush))
+    }
+
+    fn create_layouts(&mut self) -> Result<(), Error> {
+        let mut rows = vec![];
+        for (i, param) in self.parameters.params.iter().enumerate() {
+            for c in 0..param.values.len() {
+                rows.push(Row {
+                    param: i,
+                    component: c,
+                    label: self.create_text(&param.label(c))?,
+                    value: self.create_text(&param.value_text(c))?,
+                });
+            }
+        }
+        self.rows = rows;
+        self.swatches = (0..self.parameters.params.len())
+            .map(|i| self.create_swatch(i))
+            .collect::<Result<_, Error>>()?;
+        Ok(())
+    }
+
+    fn row_height(&self) -> f32 {
+        self.ui_props.line_height + MARGIN
+    }
+
+    fn panel_size(&self) -> mltg::Size {
+        mltg::size(
+            LABEL_WIDTH + TRACK_WIDTH + VALUE_WIDTH + self.ui_props.line_height + MARGIN * 5.0,
+            self.row_height() * self.rows.len() as f32 + MARGIN,
+        )
+    }
+
+    fn row_origin(&self, row: usize) -> mltg::Point {
+        mltg::point(
+            self.position.x + MARGIN,
+            self.position.y + MARGIN + self.row_height() * row as f32,
+        )
+    }
+
+    fn track_origin(&self, row: usize) -> mltg::Point {
+        let origin = self.row_origin(row);
+        mltg::point(origin.x + LABEL_WIDTH + MARGIN, origin.y)
+    }
+
+    fn set_from_x(&mut self, row: usize, x: f32) -> Result<(), Error> {
+        let (param, component) = (self.rows[row].param, self.rows[row].component);
+        let [min, max] = self.parameters.params[param].range;
+        let t = ((x - self.track_origin(row).x) / TRACK_WIDTH).clamp(0.0, 1.0);
+        self.set_value(row, param, component, min + t * (max - min))
+    }
+
+    fn set_value(
+        &mut self,
+        row: usize,
+        param: usize,
+        component: usize,
+        v: f32,
+    ) -> Result<(), Error> {
+        self.parameters.set(param, component, v);
+        let text = self.parameters.params[param].value_text(component);
+        self.rows[row].value = self.create_text(&text)?;
+        self.swatches[param] = self.create_swatch(param)?;
+        Ok(())
+    }
+
+    pub fn mouse_event(
+        &mut self,
+        mouse_pos: wita::LogicalPosition<f32>,
+        button: Option<(wita::MouseButton, wita::KeyState)>,
+    ) -> Result<bool, Error> {
+        if self.is_empty() {
+            return Ok(false);
+        }
+        if let Some(row) = self.dragging {
+            self.set_from_x(row, mouse_pos.x)?;
+            if let Some((wita::MouseButton::Left, wita::KeyState::Released)) = button {
+                self.dragging = None;
+            }
+            return Ok(true);
+        }
+        let pos = gecl::point(mouse_pos.x, mouse_pos.y);
+        let line_height = self.ui_props.line_height;
+        self.hover = (0..self.rows.len()).find(|&row| {
+            let origin = self.track_origin(row);
+            gecl::rect([origin.x, origin.y], [TRACK_WIDTH, line_height]).is_crossing(&pos)
+        });
+        if let (Some(row), Some((wita::MouseButton::Left, wita::KeyState::Pressed))) =
+            (self.hover, button)
+        {
+            let (param, component) = (self.rows[row].param, self.rows[row].component);
+            if self.parameters.params[param].widget == hlsl::Widget::Toggle {
+                let v = self.parameters.params[param].values[component];
+                self.set_value(row, param, component, 1.0 - v)?;
+            } else {
+                self.dragging = Some(row);
+                self.set_from_x(row, mouse_pos.x)?;
+            }
+            return Ok(true);
+        }
+        let size = self.panel_size();
+        let panel = gecl::rect(
+            [self.position.x, self.position.y],
+            [size.width, size.height],
+        );
+        Ok(button.is_some() && panel.is_crossing(&pos))
+    }
+
+    pub fn draw(&self, cmd: &mltg::DrawCommand) {
+        if self.is_empty() {
+            return;
+        }
+        let props = &self.ui_props;
+        let line_height = props.line_height;
+        cmd.fill(
+            &mltg::Rect::new(self.position, self.panel_size()),
+            &props.bg_color,
+        );
+        for (i, row) in self.rows.iter().enumerate() {
+            let param = &self.parameters.params[row.param];
+            let origin = self.row_origin(i);
+            let track = self.track_origin(i);
+            cmd.draw_text_layout(&row.label, &props.text_color, origin);
+            let color = if self.dragging == Some(i) {
+                &props.scroll_bar.thumb_moving_color
+            } else if self.hover == Some(i) {
+                &props.scroll_bar.thumb_hover_color
+            } else {
+                &props.scroll_bar.thumb_color
+            };
+            let v = param.values[row.component];
+            if param.widget == hlsl::Widget::Toggle {
+                let rc = mltg::rect([track.x, track.y], [line_height, line_height]);
+                cmd.fill(&rc, &props.scroll_bar.bg_color);
+                if v != 0.0 {
+                    let rc = mltg::rect(
+                        [rc.origin.x + 3.0, rc.origin.y + 3.0],
+                        [line_height - 6.0, line_height - 6.0],
+                    );
+                    cmd.fill(&rc, color);
+                }
+            } else {
+                let [min, max] = param.range;
+                let t = if max > min {
+                    (v - min) / (max - min)
+                } else {
+                    0.0
+                };
+                let y = track.y + (line_height - TRACK_HEIGHT) / 2.0;
+                cmd.fill(
+                    &mltg::rect([track.x, y], [TRACK_WIDTH, TRACK_HEIGHT]),
+                    &props.scroll_bar.bg_color,
+                );
+                cmd.fill(
+                    &mltg::rect([track.x, y], [TRACK_WIDTH * t, TRACK_HEIGHT]),
+                    color,
+                );
+                cmd.draw_text_layout(
+                    &row.value,
+                    &props.text_color,
+                    [track.x + TRACK_WIDTH + MARGIN, track.y],
+                );
+            }
+            if row.component == 0 {
+                if let Some(swatch) = &self.swatches[row.param] {
+                    let x = track.x + TRACK_WIDTH + VALUE_WIDTH + MARGIN * 2.0;
+                    cmd.fill(
+                        &mltg::rect([x, track.y], [line_height, line_height]),
+                        swatch,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(name: &str, type_name: &str, offset: u32, size: u32) -> hlsl::Variable {
+        hlsl::Variable {
+            name: name.to_string(),
+            type_name: type_name.to_string(),
+            offset,
+            size,
+        }
+    }
+
+    #[test]
+    fn user_parameters_data() {
+        let cb = hlsl::ConstantBuffer {
+            name: "User".to_string(),
+            size: 32,
+            variables: vec![
+                variable("speed", "float", 0, 4),
+                variable("count", "int", 4, 4),
+                variable("enabled", "bool", 8, 4),
+                variable("tint", "float3", 16, 12),
+                variable("transform", "float4x4", 32, 64),
+            ],
+        };
+        let annotations = hlsl::Annotations::parse(
+            "\
+cbuffer User: register(b1) {
+    float speed; // @range(0, 10) @default(2.5)
+    int count; // @default(3)
+    bool enabled; // @default(1)
+    float3 tint; // @color @default(1, 0.5, 0)
+};
+",
+            None,
+        )
+        .unwrap();
+        let mut params = UserParameters::new(Some(&cb), &annotations, None);
+        assert!(params.params.len() == 4);
+        let f32_at = |data: &[u8], offset: usize| {
+            f32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+        };
+        let u32_at = |data: &[u8], offset: usize| {
+            u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+        };
+        assert!(params.data().len() == 32);
+        assert!(f32_at(params.data(), 0) == 2.5);
+        assert!(u32_at(params.data(), 4) == 3);
+        assert!(u32_at(params.data(), 8) == 1);
+        assert!(f32_at(params.data(), 20) == 0.5);
+        params.set(0, 0, 20.0);
+        params.set(1, 0, 4.4);
+        assert!(f32_at(params.data(), 0) == 10.0);
+        assert!(u32_at(params.data(), 4) == 4);
+    }
+
+    #[test]
+    fn user_parameters_keep_values() {
+        let cb = hlsl::ConstantBuffer {
+            name: "User".to_string(),
+            size: 16,
+            variables: vec![
+                variable("speed", "float", 0, 4),
+                variable("scale", "float", 4, 4),
+            ],
+        };
+        let annotations = hlsl::Annotations::default();
+        let mut prev = UserParameters::new(Some(&cb), &annotations, None);
+        prev.set(0, 0, 0.25);
+        prev.set(1, 0, 0.75);
+        let cb = hlsl::ConstantBuffer {
+            name: "User".to_string(),
+            size: 16,
+            variables: vec![
+                variable("offset", "float", 0, 4),
+                variable("speed", "float", 4, 4),
+                variable("scale", "float2", 8, 8),
+            ],
+        };
+        let params = UserParameters::new(Some(&cb), &annotations, Some(&prev));
+        assert!(params.params[0].values == [0.0]);
+        assert!(params.params[1].values == [0.25]);
+        assert!(params.params[2].values == [0.0, 0.0]);
+    }
+
+    #[test]
+    fn user_parameters_preset() {
+        let cb = hlsl::ConstantBuffer {
+            name: "User".to_string(),
+            size: 32,
+            variables: vec![
+                variable("speed", "float", 0, 4),
+                variable("count", "int", 4, 4),
+                variable("tint", "float3", 16, 12),
+            ],
+        };
+        let annotations = hlsl::Annotations::default();
+        let mut params = UserParameters::new(Some(&cb), &annotations, None);
+        params.set(0, 0, 0.5);
+        params.set(2, 1, 0.25);
+        let preset = params.preset();
+        assert!(preset["speed"] == settings::ParamValue::Scalar(0.5));
+        assert!(preset["tint"] == settings::ParamValue::Vector(vec![0.0, 0.25, 0.0]));
+        let mut preset = settings::Preset::new();
+        preset.insert("speed".to_string(), settings::ParamValue::Scalar(2.0));
+        preset.insert(
+            "count".to_string(),
+            settings::ParamValue::Vector(vec![1.0, 2.0]),
+        );
+        preset.insert("tint".to_string(), settings::ParamValue::Scalar(0.75));
+        let mut params = UserParameters::new(Some(&cb), &annotations, None);
+        params.apply_preset(&preset);
+        assert!(params.params[0].values == [1.0]);
+        assert!(params.params[1].values == [0.0]);
+        assert!(params.params[2].values == [0.75, 0.75, 0.75]);
+        assert!(params.data()[0..4] == 1.0f32.to_le_bytes());
+    }
+}
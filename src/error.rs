@@ -1,15 +1,25 @@
 use crate::*;
 use std::path::PathBuf;
+#[cfg(windows)]
 use windows::Win32::Graphics::Direct3D::Dxc::*;
 
 struct ErrorMessages {
     read_file: &'static str,
+    decode_file: &'static str,
     create_file: &'static str,
     remove_file: &'static str,
     file_too_large: &'static str,
     unsupported_version: &'static str,
     invalid_version: &'static str,
     unexpected_eof: &'static str,
+    execute_compiler: &'static str,
+    #[cfg(windows)]
+    load_image: &'static str,
+    #[cfg(windows)]
+    load_mesh: &'static str,
+    #[cfg(windows)]
+    invalid_project: &'static str,
+    #[cfg(windows)]
     unknown_error: &'static str,
 }
 
@@ -18,35 +28,63 @@ impl ErrorMessages {
         match loc {
             Some("ja-JP") => Self {
                 read_file: "ファイルを読み込めません",
+                decode_file: "ファイルの文字コードを変換できません",
                 create_file: "ファイルを作成できません",
                 remove_file: "ファイルを削除できません",
                 file_too_large: "ファイルが大き過ぎます",
                 unsupported_version: "サポートされていないバージョンです",
                 invalid_version: "settings.tomlにおけるバージョンの書き方に誤りがあります",
                 unexpected_eof: "ファイルの途中に終端記号がありました",
+                execute_compiler: "コンパイラを実行できません",
+                #[cfg(windows)]
+                load_image: "画像を読み込めません",
+                #[cfg(windows)]
+                load_mesh: "メッシュを読み込めません",
+                #[cfg(windows)]
+                invalid_project: "プロジェクトファイルの書き方に誤りがあります",
+                #[cfg(windows)]
                 unknown_error: "特定できないエラーです",
             },
             _ => Self {
                 read_file: "cannot read the file",
+                decode_file: "cannot convert the encoding of the file",
                 create_file: "cannot create the file",
                 remove_file: "cannot remove the file",
                 file_too_large: "file too large",
                 unsupported_version: "unsupporrted version",
                 invalid_version: "invalid the version written in settings.toml",
                 unexpected_eof: "unexpected EOF",
+                execute_compiler: "cannot execute the compiler",
+                #[cfg(windows)]
+                load_image: "cannot load the image",
+                #[cfg(windows)]
+                load_mesh: "cannot load the mesh",
+                #[cfg(windows)]
+                invalid_project: "invalid the project file",
+                #[cfg(windows)]
                 unknown_error: "unknown error",
             },
         }
     }
 }
 
+fn join_diagnostics(diagnostics: &[hlsl::Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 static ERROR_MESSAGES: Lazy<ErrorMessages> =
     Lazy::new(|| ErrorMessages::new(LOCALE.as_ref().map(|l| l.as_str())));
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    #[cfg(windows)]
     #[error("{0}")]
     Api(#[from] windows::core::Error),
+    #[cfg(windows)]
     #[error("mltg: {0}")]
     Mltg(#[from] mltg::Error),
     #[error("{}", .0)]
@@ -54,9 +92,13 @@ pub enum Error {
     #[error("{}", .0)]
     Deserialize(#[from] toml::de::Error),
     #[error("{0}")]
-    Compile(String),
+    Io(#[from] std::io::Error),
+    #[error("{}", join_diagnostics(.0))]
+    Compile(Vec<hlsl::Diagnostic>),
     #[error("{}({})", ERROR_MESSAGES.read_file, .0.display())]
     ReadFile(PathBuf),
+    #[error("{}({}: {})", ERROR_MESSAGES.decode_file, .0.display(), .1)]
+    DecodeFile(PathBuf, hlsl::Encoding),
     #[error("{}({})", ERROR_MESSAGES.create_file, .0.display())]
     CreateFile(PathBuf),
     #[error("{}({})", ERROR_MESSAGES.remove_file, .0.display())]
@@ -69,20 +111,41 @@ pub enum Error {
     InvalidVersion,
     #[error("{}", ERROR_MESSAGES.unexpected_eof)]
     UnexceptedEof,
+    #[error("{}({})", ERROR_MESSAGES.execute_compiler, .0.display())]
+    ExecuteCompiler(PathBuf),
+    #[cfg(windows)]
+    #[error("{}({}: {})", ERROR_MESSAGES.load_image, .0.display(), .1)]
+    LoadImage(PathBuf, String),
+    #[cfg(windows)]
+    #[error("{}({}: {})", ERROR_MESSAGES.load_mesh, .0.display(), .1)]
+    LoadMesh(PathBuf, String),
+    #[cfg(windows)]
+    #[error("{}({}: {})", ERROR_MESSAGES.invalid_project, .0.display(), .1)]
+    InvalidProject(PathBuf, String),
+    #[cfg(windows)]
     #[error("{}", ERROR_MESSAGES.unknown_error)]
     UnknownError,
+    #[cfg(windows)]
     #[error("{}", .0)]
     TestErrorMessage(String),
 }
 
-impl From<IDxcBlobUtf8> for Error {
-    fn from(src: IDxcBlobUtf8) -> Self {
-        unsafe {
+#[cfg(windows)]
+impl Error {
+    pub fn from_dxc_errors(src: IDxcBlobUtf8, e: windows::core::Error) -> Self {
+        let diagnostics = unsafe {
             let slice = std::slice::from_raw_parts(
                 src.GetBufferPointer() as *const u8,
-                src.GetBufferSize() - 1,
+                src.GetBufferSize().saturating_sub(1),
             );
-            Self::Compile(String::from_utf8_lossy(slice).to_string())
+            hlsl::Diagnostic::parse(&String::from_utf8_lossy(slice))
+        };
+        if diagnostics.is_empty() {
+            return Self::Compile(vec![hlsl::Diagnostic::new(
+                hlsl::Severity::Error,
+                format!("{} (0x{:08X})", e.message(), e.code().0),
+            )]);
         }
+        Self::Compile(diagnostics)
     }
 }
@@ -1,85 +1,76 @@
+mod annotation;
+mod cache;
+mod diagnostic;
+mod directive;
+#[cfg(windows)]
+mod dxc_api;
+mod dxc_process;
+mod encoding;
+mod include;
+#[cfg(windows)]
+mod reflection;
+
 use crate::*;
 use regex::Regex;
 use std::fs::File;
-use std::io::{BufReader, Read};
-use std::path::Path;
-use windows::core::{Interface, GUID, PWSTR};
-use windows::Win32::{
-    Foundation::E_INVALIDARG,
-    Graphics::{Direct3D::Dxc::*, Direct3D12::*},
-};
-
-#[derive(Clone, PartialEq, Eq)]
-#[repr(transparent)]
-pub struct Blob(IDxcBlob);
+use std::path::{Path, PathBuf};
+#[cfg(windows)]
+use windows::Win32::{Foundation::E_INVALIDARG, Graphics::Direct3D12::*};
+
+#[cfg(windows)]
+pub use annotation::*;
+use cache::cache_key;
+pub use cache::Cache;
+pub use diagnostic::*;
+pub use directive::*;
+#[cfg(windows)]
+use dxc_api::DxcApi;
+use dxc_process::DxcProcess;
+pub use encoding::Encoding;
+#[cfg(windows)]
+pub use reflection::*;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Blob(Vec<u8>);
 
 impl Blob {
+    #[cfg(windows)]
     pub fn as_shader_bytecode(&self) -> D3D12_SHADER_BYTECODE {
-        unsafe {
-            D3D12_SHADER_BYTECODE {
-                pShaderBytecode: self.0.GetBufferPointer() as _,
-                BytecodeLength: self.0.GetBufferSize(),
-            }
+        D3D12_SHADER_BYTECODE {
+            pShaderBytecode: self.0.as_ptr() as _,
+            BytecodeLength: self.0.len(),
         }
     }
 }
 
-fn create_instance<T: Interface>(clsid: &GUID) -> Result<T, Error> {
-    unsafe { DxcCreateInstance(clsid).map_err(|e| e.into()) }
-}
-
 fn create_args(
     entry_point: &str,
     target: Target,
-    path: Option<&str>,
+    include_paths: &[PathBuf],
     opts: &[String],
-) -> (Vec<PWSTR>, Vec<Vec<u16>>) {
-    static INCLUDE: Lazy<String> =
-        Lazy::new(|| EXE_DIR_PATH.join("include").to_string_lossy().to_string());
-    let target = target.to_string();
+) -> Vec<String> {
     let mut args = vec![
-        "-E",
-        entry_point,
-        "-T",
-        &target,
-        "-I",
-        &INCLUDE,
-        "-I",
-        "./include",
+        "-E".to_string(),
+        entry_point.to_string(),
+        "-T".to_string(),
+        target.to_string(),
     ];
-    for opt in opts.iter() {
-        args.push(opt.as_ref());
-    }
-    if let Some(path) = path {
-        args.push(path);
-    }
-    let mut tmp = args
-        .iter()
-        .map(|a| a.encode_utf16().chain(Some(0)).collect::<Vec<_>>())
-        .collect::<Vec<_>>();
-    let args = tmp
-        .iter_mut()
-        .map(|t| PWSTR(t.as_mut_ptr()))
-        .collect::<Vec<_>>();
-    (args, tmp)
+    for path in include_paths.iter().chain(Some(&*include::EMBEDDED_DIR)) {
+        args.push("-I".to_string());
+        args.push(path.to_string_lossy().to_string());
+    }
+    args.extend(opts.iter().cloned());
+    args
 }
 
-const SHADER_MODELS: &[D3D_SHADER_MODEL] = &[
-    D3D_SHADER_MODEL_5_1,
-    D3D_SHADER_MODEL_6_0,
-    D3D_SHADER_MODEL_6_1,
-    D3D_SHADER_MODEL_6_2,
-    D3D_SHADER_MODEL_6_3,
-    D3D_SHADER_MODEL_6_4,
-    D3D_SHADER_MODEL_6_5,
-    D3D_SHADER_MODEL_6_6,
-    D3D_SHADER_MODEL_6_7,
-];
+// The same values as `D3D_SHADER_MODEL`.
+const SHADER_MODELS: &[i32] = &[0x51, 0x60, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67];
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub struct ShaderModel(D3D_SHADER_MODEL);
+pub struct ShaderModel(i32);
 
 impl ShaderModel {
+    #[cfg(windows)]
     pub fn new<T>(device: &ID3D12Device, version: Option<&T>) -> Result<Self, Error>
     where
         T: AsRef<str>,
@@ -90,28 +81,33 @@ impl ShaderModel {
         )
     }
 
-    fn specify(version: &str) -> Result<Self, Error> {
+    pub fn specify(version: &str) -> Result<Self, Error> {
         let re = Regex::new(r"(\d+)_(\d+)").unwrap();
         let cap = re.captures(version).ok_or(Error::InvalidVersion)?;
         let v = i32::from_str_radix(&format!("{}{}", &cap[1], &cap[2]), 16).unwrap();
-        if !SHADER_MODELS.contains(&D3D_SHADER_MODEL(v)) {
+        if !SHADER_MODELS.contains(&v) {
             return Err(Error::UnsupportedVersion);
         }
-        Ok(Self(D3D_SHADER_MODEL(v)))
+        Ok(Self(v))
     }
 
+    pub fn latest() -> Self {
+        Self(*SHADER_MODELS.last().unwrap())
+    }
+
+    #[cfg(windows)]
     fn highest(device: &ID3D12Device) -> Result<Self, Error> {
         unsafe {
             let mut data = D3D12_FEATURE_DATA_SHADER_MODEL::default();
             for sm in SHADER_MODELS.iter().rev() {
-                data.HighestShaderModel = *sm;
+                data.HighestShaderModel = D3D_SHADER_MODEL(*sm);
                 let ret = device.CheckFeatureSupport(
                     D3D12_FEATURE_SHADER_MODEL,
                     &mut data as *mut _ as _,
                     std::mem::size_of_val(&data) as _,
                 );
                 match ret {
-                    Ok(_) => return Ok(Self(data.HighestShaderModel)),
+                    Ok(_) => return Ok(Self(data.HighestShaderModel.0)),
                     Err(e) if e.code() != E_INVALIDARG => return Err(e.into()),
                     _ => {}
                 }
@@ -119,26 +115,11 @@ impl ShaderModel {
             Err(Error::UnsupportedVersion)
         }
     }
-
-    fn as_str(&self) -> &str {
-        match self.0 {
-            D3D_SHADER_MODEL_5_1 => "5_1",
-            D3D_SHADER_MODEL_6_0 => "6_0",
-            D3D_SHADER_MODEL_6_1 => "6_1",
-            D3D_SHADER_MODEL_6_2 => "6_2",
-            D3D_SHADER_MODEL_6_3 => "6_3",
-            D3D_SHADER_MODEL_6_4 => "6_4",
-            D3D_SHADER_MODEL_6_5 => "6_5",
-            D3D_SHADER_MODEL_6_6 => "6_6",
-            D3D_SHADER_MODEL_6_7 => "6_7",
-            _ => unimplemented!(),
-        }
-    }
 }
 
 impl std::fmt::Display for ShaderModel {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(fmt, "{}", self.as_str())
+        write!(fmt, "{:x}_{:x}", self.0 >> 4, self.0 & 0xf)
     }
 }
 
@@ -146,6 +127,23 @@ impl std::fmt::Display for ShaderModel {
 pub enum Target {
     VS(ShaderModel),
     PS(ShaderModel),
+    CS(ShaderModel),
+}
+
+impl Target {
+    pub fn shader_model(&self) -> ShaderModel {
+        match self {
+            Self::VS(version) | Self::PS(version) | Self::CS(version) => *version,
+        }
+    }
+
+    pub fn with_shader_model(self, version: ShaderModel) -> Self {
+        match self {
+            Self::VS(_) => Self::VS(version),
+            Self::PS(_) => Self::PS(version),
+            Self::CS(_) => Self::CS(version),
+        }
+    }
 }
 
 impl ToString for Target {
@@ -153,77 +151,119 @@ impl ToString for Target {
         match self {
             Self::VS(version) => format!("vs_{}", version),
             Self::PS(version) => format!("ps_{}", version),
+            Self::CS(version) => format!("cs_{}", version),
         }
     }
 }
 
+trait Backend {
+    fn version(&self) -> String;
+    fn compile(
+        &self,
+        data: &str,
+        path: Option<&Path>,
+        args: &[String],
+    ) -> Result<(Blob, String), Error>;
+    fn preprocess(&self, data: &str, path: Option<&Path>, args: &[String])
+        -> Result<String, Error>;
+    #[cfg(windows)]
+    fn reflect(&self, blob: &Blob) -> Result<Reflection, Error>;
+    fn dependencies(&self) -> Vec<PathBuf>;
+}
+
 pub struct Compiler {
-    utils: IDxcUtils,
-    compiler: IDxcCompiler3,
-    default_include_handler: IDxcIncludeHandler,
+    backend: Box<dyn Backend>,
+    cache: Option<Cache>,
+    include_paths: Vec<PathBuf>,
+    warnings: RefCell<Vec<Diagnostic>>,
 }
 
 impl Compiler {
+    #[cfg(windows)]
     pub fn new() -> Result<Self, Error> {
-        unsafe {
-            let utils: IDxcUtils = create_instance(&CLSID_DxcLibrary)?;
-            let compiler: IDxcCompiler3 = create_instance(&CLSID_DxcCompiler)?;
-            let default_include_handler = utils.CreateDefaultIncludeHandler()?;
-            Ok(Self {
-                utils,
-                compiler,
-                default_include_handler,
-            })
+        Ok(Self {
+            backend: Box::new(DxcApi::new()?),
+            cache: None,
+            include_paths: Vec::new(),
+            warnings: RefCell::new(Vec::new()),
+        })
+    }
+
+    pub fn with_dxc(path: impl AsRef<Path>) -> Self {
+        Self {
+            backend: Box::new(DxcProcess::new(path)),
+            cache: None,
+            include_paths: Vec::new(),
+            warnings: RefCell::new(Vec::new()),
         }
     }
 
-    fn compile_impl(&self, data: &str, args: &[PWSTR]) -> Result<Blob, Error> {
+    pub fn set_cache(&mut self, cache: Option<Cache>) {
+        self.cache = cache;
+    }
+
+    pub fn set_include_paths(&mut self, paths: Vec<PathBuf>) {
+        self.include_paths = paths;
+    }
+
+    pub fn dependencies(&self) -> Vec<PathBuf> {
+        self.backend.dependencies()
+    }
+
+    pub fn warnings(&self) -> Vec<Diagnostic> {
+        self.warnings.borrow().clone()
+    }
+
+    #[cfg(windows)]
+    pub fn reflect(&self, blob: &Blob) -> Result<Reflection, Error> {
+        self.backend.reflect(blob)
+    }
+
+    fn compile_impl(
+        &self,
+        data: &str,
+        path: Option<&Path>,
+        args: &[String],
+    ) -> Result<Blob, Error> {
+        self.compile_with_cache(data, path, args)
+            .map_err(|e| annotate_error(e, data, path, args))
+    }
+
+    fn compile_with_cache(
+        &self,
+        data: &str,
+        path: Option<&Path>,
+        args: &[String],
+    ) -> Result<Blob, Error> {
         if data.bytes().len() >= u32::MAX as _ {
             return Err(Error::FileTooLarge);
         }
         if data.chars().any(|c| c == '\0') {
             return Err(Error::UnexceptedEof);
         }
-        unsafe {
-            let src =
-                self.utils
-                    .CreateBlob(data.as_ptr() as _, data.bytes().len() as _, DXC_CP_UTF8)?;
-            let buffer = DxcBuffer {
-                Ptr: src.GetBufferPointer(),
-                Size: src.GetBufferSize(),
-                Encoding: DXC_CP_UTF8.0,
-            };
-            let result = {
-                let mut result: Option<IDxcResult> = None;
-                self.compiler
-                    .Compile(
-                        &buffer,
-                        args,
-                        &self.default_include_handler,
-                        &IDxcResult::IID,
-                        &mut result as *mut _ as _,
-                    )
-                    .map(|_| result.unwrap())?
-            };
-            if let Err(e) = result.GetStatus()?.ok() {
-                let mut blob: Option<IDxcBlobUtf8> = None;
-                result.GetOutput(
-                    DXC_OUT_ERRORS,
-                    &IDxcBlobUtf8::IID,
-                    &mut blob as *mut _ as _,
-                    std::ptr::null_mut(),
-                )?;
-                return Err(blob.map_or_else(|| e.into(), |b| b.into()));
+        self.warnings.borrow_mut().clear();
+        let (blob, messages) = match self.cache.as_ref() {
+            Some(cache) => {
+                let src = self.backend.preprocess(data, path, args)?;
+                let key = cache_key(&self.backend.version(), &src, args);
+                match cache.get(key) {
+                    Some(ret) => {
+                        debug!("cache hit: {:016x}", key);
+                        ret
+                    }
+                    None => {
+                        let (blob, messages) = self.backend.compile(data, path, args)?;
+                        if let Err(e) = cache.insert(key, &blob, &messages) {
+                            warn!("cache: {}", e);
+                        }
+                        (blob, messages)
+                    }
+                }
             }
-            let mut blob: Option<IDxcBlob> = None;
-            result.GetOutput(
-                DXC_OUT_OBJECT,
-                &IDxcBlob::IID,
-                &mut blob as *mut _ as _,
-                std::ptr::null_mut(),
-            )?;
-            Ok(Blob(blob.unwrap()))
-        }
+            None => self.backend.compile(data, path, args)?,
+        };
+        *self.warnings.borrow_mut() = Diagnostic::parse(&messages);
+        Ok(blob)
     }
 
     pub fn compile_from_str(
@@ -233,8 +273,8 @@ impl Compiler {
         target: Target,
         args: &[String],
     ) -> Result<Blob, Error> {
-        let (args, _tmp) = create_args(entry_point, target, None, args);
-        self.compile_impl(data, &args)
+        let args = create_args(entry_point, target, &self.include_paths, args);
+        self.compile_impl(data, None, &args)
     }
 
     pub fn compile_from_file(
@@ -245,17 +285,59 @@ impl Compiler {
         args: &[String],
     ) -> Result<Blob, Error> {
         let path = path.as_ref();
-        let data = {
-            let file = File::open(path).map_err(|_| Error::ReadFile(path.into()))?;
-            let mut reader = BufReader::new(file);
-            let mut data = String::new();
-            reader
-                .read_to_string(&mut data)
-                .map_err(|_| Error::ReadFile(path.into()))?;
-            data
-        };
-        let (args, _tmp) = create_args(entry_point, target, path.to_str(), args);
-        self.compile_impl(&data, &args)
+        let data = read_source(path)?;
+        let args = create_args(entry_point, target, &self.include_paths, args);
+        self.compile_impl(&data, Some(path), &args)
+    }
+
+    /// Paths in the header are written relative to `output`, where the result is saved.
+    pub fn flatten_file(
+        &self,
+        path: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+        directives: &Directives,
+        target: Target,
+        args: &[String],
+    ) -> Result<String, Error> {
+        let path = path.as_ref();
+        let data = read_source(path)?;
+        let (entry_point, target, args) = directives.apply("main", target, args);
+        let args = create_args(&entry_point, target, &self.include_paths, &args);
+        let src = self
+            .backend
+            .preprocess(&data, Some(path), &args)
+            .map_err(|e| annotate_error(e, &data, Some(path), &args))?;
+        let header = Directives {
+            entry_point: Some(entry_point),
+            ..directives.clone()
+        }
+        .to_header(output.as_ref().parent().unwrap_or_else(|| Path::new("")));
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().to_string(),
+        );
+        Ok(format!("{}// flattened from {}\n{}", header, name, src))
+    }
+}
+
+fn read_source(path: &Path) -> Result<String, Error> {
+    let data = std::fs::read(path).map_err(|_| Error::ReadFile(path.into()))?;
+    let encoding = Encoding::detect(&data);
+    debug!("encoding: {}: {}", path.display(), encoding);
+    encoding
+        .decode(&data)
+        .ok_or_else(|| Error::DecodeFile(path.into(), encoding))
+}
+
+fn annotate_error(e: Error, data: &str, path: Option<&Path>, args: &[String]) -> Error {
+    match e {
+        Error::Compile(diagnostics) => Error::Compile(include::annotate_missing_includes(
+            diagnostics,
+            data,
+            path,
+            &include::include_dirs(args),
+        )),
+        e => e,
     }
 }
 
@@ -263,9 +345,12 @@ impl Compiler {
 mod tests {
     use super::*;
 
+    #[cfg(windows)]
     const DATA_PATH: &'static str = "src/shader/copy_texture.hlsl";
+    #[cfg(windows)]
     const DATA: &'static str = include_str!("shader/copy_texture.hlsl");
 
+    #[cfg(windows)]
     #[test]
     fn compile_from_str() {
         let compiler = Compiler::new().unwrap();
@@ -278,6 +363,7 @@ mod tests {
             .unwrap();
     }
 
+    #[cfg(windows)]
     #[test]
     fn compile_from_file() {
         let compiler = Compiler::new().unwrap();
@@ -290,12 +376,186 @@ mod tests {
             .unwrap();
     }
 
+    #[cfg(windows)]
+    #[test]
+    fn compile_error_diagnostics() {
+        let compiler = Compiler::new().unwrap();
+        let version = ShaderModel::specify("6_0").unwrap();
+        let ret = compiler.compile_from_str(
+            "float4 main(): SV_Target { return x; }",
+            "main",
+            Target::PS(version),
+            &[],
+        );
+        match ret {
+            Err(Error::Compile(diagnostics)) => {
+                assert!(diagnostics[0].severity == Severity::Error);
+                assert!(diagnostics[0].line == 1);
+                assert!(diagnostics[0].column > 0);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn compile_warnings() {
+        let compiler = Compiler::new().unwrap();
+        let version = ShaderModel::specify("6_0").unwrap();
+        compiler
+            .compile_from_str(
+                "float4 main(): SV_Target { float4 v = 1; float2 a = v; return a.xyxy; }",
+                "main",
+                Target::PS(version),
+                &[],
+            )
+            .unwrap();
+        let warnings = compiler.warnings();
+        assert!(!warnings.is_empty());
+        assert!(warnings[0].severity == Severity::Warning);
+        compiler
+            .compile_from_str(DATA, "ps_main", Target::PS(version), &[])
+            .unwrap();
+        assert!(compiler.warnings().is_empty());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn compile_with_directives() {
+        let dir = Path::new("./target/compile_with_directives_test");
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join("directives.hlsl");
+        std::fs::write(
+            &path,
+            "// hlsl_box: entry=ps_main, define=VALUE=1\n\
+             float4 ps_main(): SV_Target { return VALUE; }\n",
+        )
+        .unwrap();
+        let compiler = Compiler::new().unwrap();
+        let version = ShaderModel::specify("6_0").unwrap();
+        let directives = Directives::from_file(&path).unwrap();
+        let (entry_point, target, args) = directives.apply("main", Target::PS(version), &[]);
+        compiler
+            .compile_from_file(&path, &entry_point, target, &args)
+            .unwrap();
+        assert!(matches!(
+            compiler.compile_from_file(&path, "main", Target::PS(version), &[]),
+            Err(Error::Compile(_))
+        ));
+        std::fs::write(&path, "// hlsl_box: unknown=1\n").unwrap();
+        assert!(matches!(
+            Directives::from_file(&path),
+            Err(Error::Compile(_))
+        ));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn compile_with_cache() {
+        let dir = Path::new("./target/compile_with_cache_test");
+        Cache::clear(dir).unwrap();
+        let mut compiler = Compiler::new().unwrap();
+        compiler.set_cache(Some(Cache::new(dir, 1024 * 1024)));
+        let version = ShaderModel::specify("6_0").unwrap();
+        let a = compiler
+            .compile_from_file(DATA_PATH, "ps_main", Target::PS(version), &[])
+            .unwrap();
+        let b = compiler
+            .compile_from_file(DATA_PATH, "ps_main", Target::PS(version), &[])
+            .unwrap();
+        assert!(a == b);
+        assert!(dir.read_dir().unwrap().count() == 1);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn dependencies() {
+        let mut compiler = Compiler::new().unwrap();
+        compiler.set_include_paths(vec![PathBuf::from("include")]);
+        let version = ShaderModel::specify("6_0").unwrap();
+        compiler
+            .compile_from_file("examples/fill.hlsl", "main", Target::PS(version), &[])
+            .unwrap();
+        let dependencies = compiler.dependencies();
+        assert!(dependencies.len() == 2);
+        assert!(dependencies[0] == Path::new("examples/fill.hlsl").canonicalize().unwrap());
+        assert!(dependencies[1] == Path::new("include/hlsl_box.hlsli").canonicalize().unwrap());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn flatten_file() {
+        let compiler = Compiler::new().unwrap();
+        let version = ShaderModel::specify("6_0").unwrap();
+        let path = Path::new("examples/fill.hlsl");
+        let output = path.with_extension("flat.hlsl");
+        let directives = Directives::from_file(path).unwrap();
+        let src = compiler
+            .flatten_file(path, &output, &directives, Target::PS(version), &[])
+            .unwrap();
+        assert!(src.starts_with("// hlsl_box: entry=main\n"));
+        assert!(!src.contains("#include"));
+        compiler
+            .compile_from_str(&src, "main", Target::PS(version), &[])
+            .unwrap();
+        let dir = Path::new("./target/flatten_test");
+        std::fs::create_dir_all(dir.join("out")).unwrap();
+        let path = dir.join("directives.hlsl");
+        std::fs::write(
+            &path,
+            "// hlsl_box: entry=ps_main, vertex=vs_main, mesh=bunny.obj\n\
+             // hlsl_box: channel0=noise.png, channel0.wrap=mirror, channel0.filter=point\n\
+             // hlsl_box: pass_a=buffer_a.hlsl, pass_a.entry=simulate, pass_a.resolution=256x256\n\
+             float4 vs_main(float4 pos: POSITION): SV_Position { return pos; }\n\
+             float4 ps_main(): SV_Target { return 0; }\n",
+        )
+        .unwrap();
+        for name in ["bunny.obj", "noise.png", "buffer_a.hlsl"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let directives = Directives::from_file(&path).unwrap();
+        for output in [
+            dir.join("directives.flat.hlsl"),
+            dir.join("out/directives.hlsl"),
+        ] {
+            let src = compiler
+                .flatten_file(&path, &output, &directives, Target::PS(version), &[])
+                .unwrap();
+            let flattened = Directives::parse(&src, Some(&output)).unwrap();
+            assert!(flattened.vertex_entry_point == directives.vertex_entry_point);
+            assert!(flattened.channels[0].wrap == Wrap::Mirror);
+            assert!(flattened.channels[0].filter == Filter::Point);
+            assert!(flattened.passes[0].entry_point.as_deref() == Some("simulate"));
+            assert!(flattened.passes[0].resolution == Some([256, 256]));
+            let paths = |d: &Directives| {
+                [&d.mesh, &d.channels[0].path, &d.passes[0].path]
+                    .map(|p| p.as_ref().unwrap().canonicalize().unwrap())
+            };
+            assert!(paths(&flattened) == paths(&directives));
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn embedded_headers() {
+        let compiler = Compiler::new().unwrap();
+        let version = ShaderModel::specify("6_0").unwrap();
+        compiler
+            .compile_from_file("examples/fill.hlsl", "main", Target::PS(version), &[])
+            .unwrap();
+        let dependencies = compiler.dependencies();
+        assert!(dependencies == [Path::new("examples/fill.hlsl").canonicalize().unwrap()]);
+    }
+
     #[test]
     fn specify_target_version() {
         assert!(ShaderModel::specify("6_0").is_ok());
         assert!(ShaderModel::specify("5_0").is_err());
+        assert!(ShaderModel::specify("6_6").unwrap().to_string() == "6_6");
+        assert!(Target::PS(ShaderModel::specify("5_1").unwrap()).to_string() == "ps_5_1");
     }
 
+    #[cfg(windows)]
     #[test]
     fn highest_target_version() {
         use windows::Win32::Graphics::{Direct3D::*, Dxgi::*};
@@ -310,6 +570,6 @@ mod tests {
             device.unwrap()
         };
         let version = ShaderModel::highest(&device).unwrap();
-        assert!(version.0 .0 >= D3D_SHADER_MODEL_5_1.0);
+        assert!(version.0 >= D3D_SHADER_MODEL_5_1.0);
     }
 }
@@ -0,0 +1,230 @@
+use super::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Widget {
+    Slider,
+    Color,
+    Toggle,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Annotation {
+    pub widget: Widget,
+    pub range: Option<[f32; 2]>,
+    pub default: Vec<f32>,
+    pub step: Option<f32>,
+}
+
+impl Default for Annotation {
+    fn default() -> Self {
+        Self {
+            widget: Widget::Slider,
+            range: None,
+            default: vec![],
+            step: None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Annotations(HashMap<String, Annotation>);
+
+impl Annotations {
+    pub fn parse(data: &str, path: Option<&Path>) -> Result<Self, Error> {
+        static MEMBER: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(
+                r"^\s*(?:\w+\s+)*?\w+\s+(\w+)\s*(?:\[[^\]]*\])?\s*(?::\s*packoffset\s*\([^)]*\))?\s*;\s*//(.*)$",
+            )
+            .unwrap()
+        });
+        static ITEM: Lazy<Regex> = Lazy::new(|| Regex::new(r"@(\w+)(?:\s*\(([^)]*)\))?").unwrap());
+        static CBUFFER: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"\bcbuffer\s+\w+\s*:\s*register\s*\(\s*b1\s*(?:,\s*space0\s*)?\)\s*\{")
+                .unwrap()
+        });
+        static CONSTANT_BUFFER: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(
+                r"\bConstantBuffer\s*<\s*(\w+)\s*>\s*\w+\s*:\s*register\s*\(\s*b1\s*(?:,\s*space0\s*)?\)",
+            )
+            .unwrap()
+        });
+        let mut this = HashMap::new();
+        let mut diagnostics = vec![];
+        let start = CBUFFER.find(data).map(|m| m.end()).or_else(|| {
+            let name = &CONSTANT_BUFFER.captures(data)?[1];
+            let s = Regex::new(&format!(r"\bstruct\s+{}\s*\{{", name)).unwrap();
+            s.find(data).map(|m| m.end())
+        });
+        let body = start.and_then(|start| {
+            let end = start + data[start..].find('}')?;
+            Some(start..end)
+        });
+        let body = match body {
+            Some(body) => body,
+            None => return Ok(Self(this)),
+        };
+        let first = data[..body.start].matches('\n').count();
+        let last = data[..body.end].matches('\n').count();
+        for (i, line) in data.lines().enumerate().skip(first).take(last - first + 1) {
+            let cap = match MEMBER.captures(line) {
+                Some(cap) => cap,
+                None => continue,
+            };
+            let comment = cap.get(2).unwrap();
+            let mut annotation = Annotation::default();
+            let mut found = false;
+            for item in ITEM.captures_iter(comment.as_str()) {
+                found = true;
+                let error = |message: String| Diagnostic {
+                    file: path.map(|p| p.to_path_buf()),
+                    line: i as u32 + 1,
+                    column: (comment.start() + item.get(0).unwrap().start()) as u32 + 1,
+                    severity: Severity::Error,
+                    message,
+                    snippet: vec![line.to_string()],
+                };
+                let key = &item[1];
+                let args = match item.get(2).map(|args| parse_numbers(args.as_str())) {
+                    Some(Some(args)) => args,
+                    Some(None) => {
+                        diagnostics.push(error(format!("invalid arguments of '@{}'", key)));
+                        continue;
+                    }
+                    None => vec![],
+                };
+                match (key, args.len()) {
+                    ("range", 2) => annotation.range = Some([args[0], args[1]]),
+                    ("default", n) if n > 0 => annotation.default = args,
+                    ("step", 1) if args[0] > 0.0 => annotation.step = Some(args[0]),
+                    ("color", 0) | ("toggle", 0) => {
+                        if annotation.widget != Widget::Slider {
+                            diagnostics.push(error(
+                                "'@color' and '@toggle' cannot be used together".to_string(),
+                            ));
+                            continue;
+                        }
+                        annotation.widget = if key == "color" {
+                            Widget::Color
+                        } else {
+                            Widget::Toggle
+                        };
+                    }
+                    ("range" | "default" | "step" | "color" | "toggle", _) => {
+                        diagnostics.push(error(format!("invalid arguments of '@{}'", key)))
+                    }
+                    _ => diagnostics.push(error(format!("unknown annotation '@{}'", key))),
+                }
+            }
+            if found {
+                this.insert(cap[1].to_string(), annotation);
+            }
+        }
+        if !diagnostics.is_empty() {
+            return Err(Error::Compile(diagnostics));
+        }
+        Ok(Self(this))
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let data = read_source(path)?;
+        Self::parse(&data, Some(path))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Annotation> {
+        self.0.get(name)
+    }
+}
+
+fn parse_numbers(args: &str) -> Option<Vec<f32>> {
+    args.split(',').map(|a| a.trim().parse().ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_annotations() {
+        let data = "\
+static const float other = 1.0; // @range(0, 1)
+cbuffer Other: register(b2) {
+    float ignored; // @range(0, 1)
+};
+cbuffer User: register(b1) {
+    float speed; // @range(0, 10) @default(1) @step(0.5)
+    float3 tint; // tint color @color @default(1, 0.5, 0)
+    bool enabled; // @toggle
+    float plain;
+    float4 offsets[2]; // @range(-1, 1)
+};
+";
+        let annotations = Annotations::parse(data, None).unwrap();
+        let speed = annotations.get("speed").unwrap();
+        assert!(speed.widget == Widget::Slider);
+        assert!(speed.range == Some([0.0, 10.0]));
+        assert!(speed.default == [1.0]);
+        assert!(speed.step == Some(0.5));
+        let tint = annotations.get("tint").unwrap();
+        assert!(tint.widget == Widget::Color);
+        assert!(tint.default == [1.0, 0.5, 0.0]);
+        assert!(annotations.get("enabled").unwrap().widget == Widget::Toggle);
+        assert!(annotations.get("plain").is_none());
+        assert!(annotations.get("offsets").unwrap().range == Some([-1.0, 1.0]));
+        assert!(annotations.get("other").is_none());
+        assert!(annotations.get("ignored").is_none());
+    }
+
+    #[test]
+    fn parse_constant_buffer_annotations() {
+        let data = "\
+struct Other {
+    float ignored; // @range(0, 1)
+};
+struct User {
+    float speed; // @range(0, 10)
+    float3 tint; // @color
+};
+ConstantBuffer<Other> other: register(b2);
+ConstantBuffer<User> user: register(b1);
+";
+        let annotations = Annotations::parse(data, None).unwrap();
+        assert!(annotations.get("speed").unwrap().range == Some([0.0, 10.0]));
+        assert!(annotations.get("tint").unwrap().widget == Widget::Color);
+        assert!(annotations.get("ignored").is_none());
+        let data = "ConstantBuffer<User> user: register(b1);\n";
+        assert!(Annotations::parse(data, None).unwrap() == Annotations::default());
+    }
+
+    #[test]
+    fn annotations_from_file() {
+        let annotations = Annotations::from_file("examples/parameters.hlsl").unwrap();
+        assert!(annotations.get("radius").unwrap().range == Some([0.1, 1.0]));
+        assert!(annotations.get("color").unwrap().widget == Widget::Color);
+        assert!(annotations.get("invert").unwrap().widget == Widget::Toggle);
+    }
+
+    #[test]
+    fn parse_annotations_error() {
+        let path = Path::new("test.hlsl");
+        let data = "\
+cbuffer User: register(b1) {
+float a; // @range(0)
+float b; // @slider @color @toggle
+};
+";
+        match Annotations::parse(data, Some(path)) {
+            Err(Error::Compile(diagnostics)) => {
+                assert!(diagnostics.len() == 3);
+                assert!(diagnostics[0].file.as_deref() == Some(path));
+                assert!(diagnostics[0].line == 2);
+                assert!(diagnostics[0].column == 13);
+                assert!(diagnostics[0].message == "invalid arguments of '@range'");
+                assert!(diagnostics[1].message == "unknown annotation '@slider'");
+                assert!(diagnostics[2].line == 3);
+            }
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use super::*;
+use std::time::SystemTime;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// FNV-1a keeps the keys stable across Rust releases, unlike `DefaultHasher`.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(FNV_OFFSET_BASIS)
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        for b in data {
+            self.0 = (self.0 ^ *b as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_str(&mut self, s: &str) {
+        self.write(&(s.len() as u64).to_le_bytes());
+        self.write(s.as_bytes());
+    }
+}
+
+pub(super) fn cache_key(backend_version: &str, src: &str, args: &[String]) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write_str(env!("CARGO_PKG_VERSION"));
+    hasher.write_str(backend_version);
+    hasher.write_str(src);
+    hasher.write(&(args.len() as u64).to_le_bytes());
+    for arg in args {
+        hasher.write_str(arg);
+    }
+    hasher.0
+}
+
+pub struct Cache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl Cache {
+    pub fn new(dir: impl AsRef<Path>, max_size: u64) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            max_size,
+        }
+    }
+
+    pub fn clear(dir: impl AsRef<Path>) -> Result<(), Error> {
+        let dir = dir.as_ref();
+        if dir.is_dir() {
+            std::fs::remove_dir_all(dir).map_err(|_| Error::RemoveFile(dir.to_path_buf()))?;
+        }
+        Ok(())
+    }
+
+    fn file_path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.dxil", key))
+    }
+
+    pub fn get(&self, key: u64) -> Option<(Blob, String)> {
+        let path = self.file_path(key);
+        let data = std::fs::read(&path).ok()?;
+        let messages = std::fs::read_to_string(path.with_extension("log")).unwrap_or_default();
+        File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+            .ok();
+        Some((Blob(data), messages))
+    }
+
+    pub fn insert(&self, key: u64, blob: &Blob, messages: &str) -> Result<(), Error> {
+        std::fs::create_dir_all(&self.dir).map_err(|_| Error::CreateFile(self.dir.clone()))?;
+        let path = self.file_path(key);
+        let log = path.with_extension("log");
+        if messages.is_empty() {
+            std::fs::remove_file(&log).ok();
+        } else {
+            std::fs::write(&log, messages).map_err(|_| Error::CreateFile(log.clone()))?;
+        }
+        let tmp = self
+            .dir
+            .join(format!("{:016x}.{}.tmp", key, std::process::id()));
+        std::fs::write(&tmp, &blob.0).map_err(|_| Error::CreateFile(tmp.clone()))?;
+        std::fs::rename(&tmp, &path).map_err(|_| Error::CreateFile(path.clone()))?;
+        self.evict()
+    }
+
+    fn evict(&self) -> Result<(), Error> {
+        let mut entries = std::fs::read_dir(&self.dir)?
+            .flatten()
+            .filter(|entry| entry.path().extension().map_or(false, |ext| ext == "dxil"))
+            .filter_map(|entry| {
+                let path = entry.path();
+                let metadata = entry.metadata().ok()?;
+                let log_len = std::fs::metadata(path.with_extension("log")).map_or(0, |m| m.len());
+                Some((path, metadata.len() + log_len, metadata.modified().ok()?))
+            })
+            .collect::<Vec<_>>();
+        let mut size = entries.iter().map(|(_, len, _)| len).sum::<u64>();
+        if size <= self.max_size {
+            return Ok(());
+        }
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, len, _) in entries {
+            if size <= self.max_size {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                std::fs::remove_file(path.with_extension("log")).ok();
+                debug!("cache: remove {}", path.display());
+                size -= len;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir = Path::new("./target/cache_test").join(name);
+        Cache::clear(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn cache_insert_and_get() {
+        let cache = Cache::new(cache_dir("insert_and_get"), 1024);
+        let key = cache_key("test", "float4 main(): SV_Target { return 0; }", &[]);
+        assert!(cache.get(key).is_none());
+        cache.insert(key, &Blob(vec![1, 2, 3]), "").unwrap();
+        assert!(cache.get(key) == Some((Blob(vec![1, 2, 3]), String::new())));
+    }
+
+    #[test]
+    fn cache_messages() {
+        let cache = Cache::new(cache_dir("messages"), 1024);
+        let messages = "hlsl.hlsl:1:1: warning: test";
+        cache.insert(0, &Blob(vec![0; 4]), messages).unwrap();
+        assert!(cache.get(0).unwrap().1 == messages);
+        cache.insert(0, &Blob(vec![0; 4]), "").unwrap();
+        assert!(cache.get(0).unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn cache_key_args() {
+        let src = "float4 main(): SV_Target { return 0; }";
+        let ps_6_0 = cache_key("test", src, &["-T".to_string(), "ps_6_0".to_string()]);
+        let ps_6_6 = cache_key("test", src, &["-T".to_string(), "ps_6_6".to_string()]);
+        assert!(ps_6_0 != ps_6_6);
+        assert!(ps_6_0 != cache_key("other", src, &["-T".to_string(), "ps_6_0".to_string()]));
+        assert!(cache_key("test", "ab", &[]) != cache_key("test", "a", &["b".to_string()]));
+    }
+
+    #[test]
+    fn cache_key_stable() {
+        let mut hasher = Fnv1a::new();
+        hasher.write(b"a");
+        assert!(hasher.0 == 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn cache_evict() {
+        let cache = Cache::new(cache_dir("evict"), 8);
+        cache.insert(0, &Blob(vec![0; 4]), "").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.insert(1, &Blob(vec![1; 4]), "").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.insert(2, &Blob(vec![2; 4]), "").unwrap();
+        assert!(cache.get(0).is_none());
+        assert!(cache.get(1).is_some());
+        assert!(cache.get(2).is_some());
+    }
+
+    #[test]
+    fn cache_evict_least_recently_used() {
+        let cache = Cache::new(cache_dir("evict_least_recently_used"), 8);
+        cache.insert(0, &Blob(vec![0; 4]), "").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.insert(1, &Blob(vec![1; 4]), "").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(cache.get(0).is_some());
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.insert(2, &Blob(vec![2; 4]), "").unwrap();
+        assert!(cache.get(0).is_some());
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(2).is_some());
+    }
+
+    #[test]
+    fn cache_evict_logs() {
+        let cache = Cache::new(cache_dir("evict_logs"), 16);
+        cache.insert(0, &Blob(vec![0; 4]), "warning").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.insert(1, &Blob(vec![1; 4]), "warning").unwrap();
+        assert!(cache.get(0).is_none());
+        assert!(!cache.file_path(0).with_extension("log").exists());
+        assert!(cache.get(1).is_some());
+    }
+
+    #[test]
+    fn cache_clear() {
+        let dir = cache_dir("clear");
+        let cache = Cache::new(&dir, 1024);
+        cache.insert(0, &Blob(vec![0; 4]), "").unwrap();
+        assert!(dir.is_dir());
+        Cache::clear(&dir).unwrap();
+        assert!(!dir.exists());
+        assert!(cache.get(0).is_none());
+    }
+}
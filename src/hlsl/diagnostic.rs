@@ -0,0 +1,206 @@
+use super::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "error" | "fatal error" => Some(Self::Error),
+            "warning" => Some(Self::Warning),
+            "note" | "remark" => Some(Self::Note),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(fmt, "error"),
+            Self::Warning => write!(fmt, "warning"),
+            Self::Note => write!(fmt, "note"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Diagnostic {
+    pub file: Option<PathBuf>,
+    pub line: u32,
+    pub column: u32,
+    pub severity: Severity,
+    pub message: String,
+    pub snippet: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            file: None,
+            line: 0,
+            column: 0,
+            severity,
+            message: message.into(),
+            snippet: vec![],
+        }
+    }
+
+    pub fn parse(text: &str) -> Vec<Self> {
+        static RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"^(.+?):(\d+):(\d+): (fatal error|error|warning|note|remark): (.*)$")
+                .unwrap()
+        });
+        static BARE_RE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"^(fatal error|error|warning|note|remark): (.*)$").unwrap());
+        static SUMMARY_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"^\d+ (errors?|warnings?)( and \d+ (errors?|warnings?))? generated\.$")
+                .unwrap()
+        });
+        let mut diagnostics: Vec<Self> = vec![];
+        let mut in_diagnostic = false;
+        for line in text.lines() {
+            let header = RE.captures(line).and_then(|cap| {
+                Some(Self {
+                    file: Some(PathBuf::from(&cap[1])),
+                    line: cap[2].parse().ok()?,
+                    column: cap[3].parse().ok()?,
+                    severity: Severity::parse(&cap[4])?,
+                    message: cap[5].to_string(),
+                    snippet: vec![],
+                })
+            });
+            let header = header.or_else(|| {
+                BARE_RE
+                    .captures(line)
+                    .and_then(|cap| Some(Self::new(Severity::parse(&cap[1])?, &cap[2])))
+            });
+            match header {
+                Some(d) => {
+                    diagnostics.push(d);
+                    in_diagnostic = true;
+                }
+                None if line.trim().is_empty() => {}
+                None if SUMMARY_RE.is_match(line.trim()) => in_diagnostic = false,
+                None if line.starts_with("In file included from ") => {
+                    diagnostics.push(Self::new(Severity::Note, line));
+                    in_diagnostic = false;
+                }
+                None if in_diagnostic => {
+                    diagnostics
+                        .last_mut()
+                        .unwrap()
+                        .snippet
+                        .push(line.to_string());
+                }
+                None => diagnostics.push(Self::new(Severity::Note, line)),
+            }
+        }
+        diagnostics
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.file {
+            Some(file) => write!(
+                fmt,
+                "{}:{}:{}: {}: {}",
+                file.display(),
+                self.line,
+                self.column,
+                self.severity,
+                self.message
+            )?,
+            None => write!(fmt, "{}", self.message)?,
+        }
+        for line in self.snippet.iter() {
+            write!(fmt, "\n{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "\
+In file included from examples/test.hlsl:1:
+include/test.hlsli:3:12: warning: implicit truncation of vector type
+    return float2(a);
+           ^
+examples/test.hlsl:5:9: error: use of undeclared identifier 'x'
+    return x;
+           ^~
+";
+
+    #[test]
+    fn parse_diagnostics() {
+        let diagnostics = Diagnostic::parse(OUTPUT);
+        assert!(diagnostics.len() == 3);
+        assert!(diagnostics[0].file.is_none());
+        assert!(diagnostics[0].message == "In file included from examples/test.hlsl:1:");
+        assert!(diagnostics[1].file.as_deref() == Some(Path::new("include/test.hlsli")));
+        assert!(diagnostics[1].line == 3);
+        assert!(diagnostics[1].column == 12);
+        assert!(diagnostics[1].severity == Severity::Warning);
+        assert!(diagnostics[1].snippet == ["    return float2(a);", "           ^"]);
+        assert!(diagnostics[2].severity == Severity::Error);
+        assert!(diagnostics[2].message == "use of undeclared identifier 'x'");
+    }
+
+    #[test]
+    fn parse_trailing_lines() {
+        let text = "\
+examples/test.hlsl:5:9: error: use of undeclared identifier 'x'
+    return x;
+           ^
+In file included from examples/test.hlsl:1:
+include/test.hlsli:3:12: warning: implicit truncation of vector type
+    return float2(a);
+           ^
+1 warning and 1 error generated.
+";
+        let diagnostics = Diagnostic::parse(text);
+        assert!(diagnostics.len() == 3);
+        assert!(diagnostics[0].snippet == ["    return x;", "           ^"]);
+        assert!(diagnostics[1].severity == Severity::Note);
+        assert!(diagnostics[1].message == "In file included from examples/test.hlsl:1:");
+        assert!(diagnostics[2].snippet == ["    return float2(a);", "           ^"]);
+    }
+
+    #[test]
+    fn parse_bare_error() {
+        let diagnostics = Diagnostic::parse("error: no such file or directory: 'a.hlsl'\n");
+        assert!(diagnostics.len() == 1);
+        assert!(diagnostics[0].file.is_none());
+        assert!(diagnostics[0].severity == Severity::Error);
+        assert!(diagnostics[0].message == "no such file or directory: 'a.hlsl'");
+    }
+
+    #[test]
+    fn parse_windows_path() {
+        let diagnostics =
+            Diagnostic::parse("C:\\hlsl\\test.hlsl:2:1: fatal error: 'a.hlsli' file not found");
+        assert!(diagnostics.len() == 1);
+        assert!(diagnostics[0].file.as_deref() == Some(Path::new("C:\\hlsl\\test.hlsl")));
+        assert!(diagnostics[0].severity == Severity::Error);
+    }
+
+    #[test]
+    fn display_diagnostics() {
+        let text = OUTPUT.trim_end();
+        let diagnostics = Diagnostic::parse(text);
+        let s = diagnostics
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert!(s == text);
+    }
+}
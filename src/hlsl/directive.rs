@@ -0,0 +1,541 @@
+use super::*;
+
+pub const CHANNEL_COUNT: usize = 4;
+pub const PASS_COUNT: usize = 4;
+
+const CHANNEL_KEYS: [&str; CHANNEL_COUNT] = ["channel0", "channel1", "channel2", "channel3"];
+const PASS_KEYS: [&str; PASS_COUNT] = ["pass_a", "pass_b", "pass_c", "pass_d"];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Wrap {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    #[default]
+    Linear,
+    Point,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    #[default]
+    Pixel,
+    Compute,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Channel {
+    pub path: Option<PathBuf>,
+    pub wrap: Wrap,
+    pub filter: Filter,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Pass {
+    pub path: Option<PathBuf>,
+    pub entry_point: Option<String>,
+    pub resolution: Option<[u32; 2]>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Directives {
+    pub entry_point: Option<String>,
+    pub vertex_entry_point: Option<String>,
+    pub shader_model: Option<ShaderModel>,
+    pub mode: Mode,
+    pub mesh: Option<PathBuf>,
+    pub defines: Vec<String>,
+    pub channels: [Channel; CHANNEL_COUNT],
+    pub passes: [Pass; PASS_COUNT],
+}
+
+fn indexed_key<'a>(key: &'a str, names: &[&str]) -> Option<(usize, Option<&'a str>)> {
+    let (name, option) = match key.split_once('.') {
+        Some((name, option)) => (name, Some(option)),
+        None => (key, None),
+    };
+    names.iter().position(|n| *n == name).map(|i| (i, option))
+}
+
+fn resolve_path(path: Option<&Path>, value: &str) -> PathBuf {
+    path.and_then(|p| p.parent())
+        .map_or_else(|| PathBuf::from(value), |dir| dir.join(value))
+}
+
+fn parse_resolution(value: &str) -> Option<[u32; 2]> {
+    let (width, height) = value.split_once('x')?;
+    let resolution = [width.trim().parse().ok()?, height.trim().parse().ok()?];
+    (resolution[0] > 0 && resolution[1] > 0).then_some(resolution)
+}
+
+fn relative_path(path: &Path, dir: &Path) -> PathBuf {
+    if let Ok(relative) = path.strip_prefix(dir) {
+        return relative.to_path_buf();
+    }
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    match (path.canonicalize(), dir.canonicalize()) {
+        (Ok(path), Ok(dir)) => path
+            .strip_prefix(&dir)
+            .map_or_else(|_| path.clone(), |p| p.to_path_buf()),
+        (Ok(path), Err(_)) => path,
+        _ => path.to_path_buf(),
+    }
+}
+
+impl Directives {
+    pub fn parse(data: &str, path: Option<&Path>) -> Result<Self, Error> {
+        let mut this = Self::default();
+        let mut diagnostics = vec![];
+        for (i, line) in data.lines().enumerate() {
+            let text = line.trim_start();
+            if text.is_empty() {
+                continue;
+            }
+            let comment = match text.strip_prefix("//") {
+                Some(comment) => comment.trim_start(),
+                None => break,
+            };
+            let body = match comment.strip_prefix("hlsl_box:") {
+                Some(body) => body,
+                None => continue,
+            };
+            let mut column = line.len() - body.len();
+            for item in body.split(',') {
+                let offset = column + item.len() - item.trim_start().len();
+                column += item.len() + 1;
+                let item = item.trim();
+                if item.is_empty() {
+                    continue;
+                }
+                let error = |message: String| Diagnostic {
+                    file: path.map(|p| p.to_path_buf()),
+                    line: i as u32 + 1,
+                    column: offset as u32 + 1,
+                    severity: Severity::Error,
+                    message,
+                    snippet: vec![line.to_string()],
+                };
+                let (key, value) = match item.split_once('=') {
+                    Some((key, value)) if !value.trim().is_empty() => (key.trim(), value.trim()),
+                    _ => {
+                        diagnostics.push(error(format!("expected 'key=value': '{}'", item)));
+                        continue;
+                    }
+                };
+                match key {
+                    "entry" => this.entry_point = Some(value.to_string()),
+                    "vertex" => this.vertex_entry_point = Some(value.to_string()),
+                    "mesh" => this.mesh = Some(resolve_path(path, value)),
+                    "model" => match ShaderModel::specify(value) {
+                        Ok(model) => this.shader_model = Some(model),
+                        Err(_) => {
+                            diagnostics.push(error(format!("unsupported shader model '{}'", value)))
+                        }
+                    },
+                    "define" => this.defines.push(value.to_string()),
+                    "mode" => match value {
+                        "pixel" => this.mode = Mode::Pixel,
+                        "compute" => this.mode = Mode::Compute,
+                        _ => diagnostics.push(error(format!("unknown mode '{}'", value))),
+                    },
+                    _ => match (
+                        indexed_key(key, &CHANNEL_KEYS),
+                        indexed_key(key, &PASS_KEYS),
+                    ) {
+                        (Some((index, None)), _) => {
+                            this.channels[index].path = Some(resolve_path(path, value))
+                        }
+                        (Some((index, Some("wrap"))), _) => match value {
+                            "repeat" => this.channels[index].wrap = Wrap::Repeat,
+                            "clamp" => this.channels[index].wrap = Wrap::Clamp,
+                            "mirror" => this.channels[index].wrap = Wrap::Mirror,
+                            _ => diagnostics.push(error(format!("unknown wrap mode '{}'", value))),
+                        },
+                        (Some((index, Some("filter"))), _) => match value {
+                            "linear" => this.channels[index].filter = Filter::Linear,
+                            "point" => this.channels[index].filter = Filter::Point,
+                            _ => diagnostics.push(error(format!("unknown filter '{}'", value))),
+                        },
+                        (_, Some((index, None))) => {
+                            this.passes[index].path = Some(resolve_path(path, value))
+                        }
+                        (_, Some((index, Some("entry")))) => {
+                            this.passes[index].entry_point = Some(value.to_string())
+                        }
+                        (_, Some((index, Some("resolution")))) => match parse_resolution(value) {
+                            Some(resolution) => this.passes[index].resolution = Some(resolution),
+                            None => {
+                                diagnostics.push(error(format!("invalid resolution '{}'", value)))
+                            }
+                        },
+                        _ => diagnostics.push(error(format!("unknown directive '{}'", key))),
+                    },
+                }
+            }
+        }
+        if !diagnostics.is_empty() {
+            return Err(Error::Compile(diagnostics));
+        }
+        Ok(this)
+    }
+
+    /// Writes the directives back as header lines of a file in `dir`.
+    pub fn to_header(&self, dir: &Path) -> String {
+        let path = |path: &PathBuf| relative_path(path, dir).display().to_string();
+        let mut items = vec![];
+        if let Some(entry_point) = &self.entry_point {
+            items.push(format!("entry={}", entry_point));
+        }
+        if let Some(shader_model) = self.shader_model {
+            items.push(format!("model={}", shader_model));
+        }
+        if self.mode == Mode::Compute {
+            items.push("mode=compute".to_string());
+        }
+        if let Some(vertex_entry_point) = &self.vertex_entry_point {
+            items.push(format!("vertex={}", vertex_entry_point));
+        }
+        if let Some(mesh) = &self.mesh {
+            items.push(format!("mesh={}", path(mesh)));
+        }
+        items.extend(self.defines.iter().map(|d| format!("define={}", d)));
+        let mut lines = vec![items];
+        for (key, channel) in CHANNEL_KEYS.iter().zip(&self.channels) {
+            let mut items = vec![];
+            if let Some(p) = &channel.path {
+                items.push(format!("{}={}", key, path(p)));
+            }
+            match channel.wrap {
+                Wrap::Repeat => {}
+                Wrap::Clamp => items.push(format!("{}.wrap=clamp", key)),
+                Wrap::Mirror => items.push(format!("{}.wrap=mirror", key)),
+            }
+            if channel.filter == Filter::Point {
+                items.push(format!("{}.filter=point", key));
+            }
+            lines.push(items);
+        }
+        for (key, pass) in PASS_KEYS.iter().zip(&self.passes) {
+            let mut items = vec![];
+            if let Some(p) = &pass.path {
+                items.push(format!("{}={}", key, path(p)));
+            }
+            if let Some(entry_point) = &pass.entry_point {
+                items.push(format!("{}.entry={}", key, entry_point));
+            }
+            if let Some([width, height]) = pass.resolution {
+                items.push(format!("{}.resolution={}x{}", key, width, height));
+            }
+            lines.push(items);
+        }
+        lines
+            .into_iter()
+            .map(|items| {
+                items
+                    .into_iter()
+                    .filter(|item| {
+                        let valid = !item.contains(&[',', '\n'][..]);
+                        if !valid {
+                            warn!("cannot write '{}' to the header", item);
+                        }
+                        valid
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .filter(|items| !items.is_empty())
+            .map(|items| format!("// hlsl_box: {}\n", items.join(", ")))
+            .collect()
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let data = read_source(path)?;
+        Self::parse(&data, Some(path))
+    }
+
+    /// Merges the header of the shader file into the directives of a project.
+    ///
+    /// `entry`, `vertex`, `model` and `mode=compute` in the header take precedence and its
+    /// defines follow the project's. Channels, passes and the mesh come from the project.
+    pub fn merge(&self, header: &Self) -> Self {
+        Self {
+            entry_point: header
+                .entry_point
+                .clone()
+                .or_else(|| self.entry_point.clone()),
+            vertex_entry_point: header
+                .vertex_entry_point
+                .clone()
+                .or_else(|| self.vertex_entry_point.clone()),
+            shader_model: header.shader_model.or(self.shader_model),
+            mode: match header.mode {
+                Mode::Compute => Mode::Compute,
+                Mode::Pixel => self.mode,
+            },
+            defines: self
+                .defines
+                .iter()
+                .chain(header.defines.iter())
+                .cloned()
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    pub fn apply(
+        &self,
+        entry_point: &str,
+        target: Target,
+        args: &[String],
+    ) -> (String, Target, Vec<String>) {
+        let entry = match target {
+            Target::VS(_) => &self.vertex_entry_point,
+            _ => &self.entry_point,
+        };
+        let entry_point = entry.clone().unwrap_or_else(|| entry_point.to_string());
+        let target = match self.shader_model {
+            Some(model) if model.0 > target.shader_model().0 => {
+                warn!(
+                    "shader model {} is not supported, use {}",
+                    model,
+                    target.shader_model()
+                );
+                target
+            }
+            Some(model) => target.with_shader_model(model),
+            None => target,
+        };
+        let target = match (self.mode, target) {
+            (Mode::Compute, Target::PS(model)) => Target::CS(model),
+            _ => target,
+        };
+        let args = args
+            .iter()
+            .cloned()
+            .chain(
+                self.defines
+                    .iter()
+                    .flat_map(|d| ["-D".to_string(), d.clone()]),
+            )
+            .collect();
+        (entry_point, target, args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_directives() {
+        let data = "\
+// hlsl_box: entry=ps_main, model=6_6
+// other comment
+
+//hlsl_box: define=FOO=1, define=BAR
+float4 ps_main(): SV_Target { return 0; }
+// hlsl_box: entry=ignored
+";
+        let directives = Directives::parse(data, None).unwrap();
+        assert!(directives.entry_point.as_deref() == Some("ps_main"));
+        assert!(directives.shader_model == Some(ShaderModel::specify("6_6").unwrap()));
+        assert!(directives.defines == ["FOO=1", "BAR"]);
+        let (entry_point, target, args) = directives.apply(
+            "main",
+            Target::PS(ShaderModel::specify("6_7").unwrap()),
+            &["-O3".to_string()],
+        );
+        assert!(entry_point == "ps_main");
+        assert!(target.to_string() == "ps_6_6");
+        assert!(args == ["-O3", "-D", "FOO=1", "-D", "BAR"]);
+        let (_, target, _) = directives.apply(
+            "main",
+            Target::PS(ShaderModel::specify("6_0").unwrap()),
+            &[],
+        );
+        assert!(target.to_string() == "ps_6_0");
+    }
+
+    #[test]
+    fn directives_to_header() {
+        let data = "\
+// hlsl_box: entry=ps_main, model=6_6, vertex=vs_main, mesh=models/bunny.obj, define=FOO=1
+// hlsl_box: channel0=images/noise.png, channel0.filter=point
+// hlsl_box: channel2=../sky.hdr, channel2.wrap=clamp
+// hlsl_box: pass_a=buffer_a.hlsl, pass_a.entry=simulate, pass_a.resolution=256x256
+";
+        let path = Path::new("shaders/test.hlsl");
+        let directives = Directives::parse(data, Some(path)).unwrap();
+        assert!(directives.to_header(Path::new("shaders")) == data);
+        let header = directives.to_header(Path::new("shaders"));
+        assert!(Directives::parse(&header, Some(path)).unwrap() == directives);
+        let directives = Directives::parse("// hlsl_box: mode=compute\n", None).unwrap();
+        assert!(directives.to_header(Path::new("")) == "// hlsl_box: mode=compute\n");
+        let directives = Directives {
+            defines: vec!["A".to_string(), "B=1,2".to_string()],
+            ..Default::default()
+        };
+        assert!(directives.to_header(Path::new("")) == "// hlsl_box: define=A\n");
+        assert!(Directives::default().to_header(Path::new("")).is_empty());
+    }
+
+    #[test]
+    fn merge_directives() {
+        let project = Directives {
+            entry_point: Some("project_main".to_string()),
+            vertex_entry_point: Some("project_vs".to_string()),
+            shader_model: Some(ShaderModel::specify("6_0").unwrap()),
+            mesh: Some(PathBuf::from("bunny.obj")),
+            defines: vec!["FOO=1".to_string()],
+            ..Default::default()
+        };
+        let data = "// hlsl_box: entry=ps_main, mode=compute, define=FOO=2, mesh=plane.obj\n";
+        let header = Directives::parse(data, None).unwrap();
+        let directives = project.merge(&header);
+        assert!(directives.entry_point.as_deref() == Some("ps_main"));
+        assert!(directives.vertex_entry_point.as_deref() == Some("project_vs"));
+        assert!(directives.shader_model == project.shader_model);
+        assert!(directives.mode == Mode::Compute);
+        assert!(directives.mesh == project.mesh);
+        assert!(directives.defines == ["FOO=1", "FOO=2"]);
+        let (entry_point, target, args) = directives.apply(
+            "main",
+            Target::PS(ShaderModel::specify("6_6").unwrap()),
+            &[],
+        );
+        assert!(entry_point == "ps_main");
+        assert!(target.to_string() == "cs_6_0");
+        assert!(args == ["-D", "FOO=1", "-D", "FOO=2"]);
+        assert!(project.merge(&Directives::default()) == project);
+    }
+
+    #[test]
+    fn parse_directives_mode() {
+        let data = "// hlsl_box: mode=compute, model=6_6\n";
+        let directives = Directives::parse(data, None).unwrap();
+        assert!(directives.mode == Mode::Compute);
+        let (_, target, _) = directives.apply(
+            "main",
+            Target::PS(ShaderModel::specify("6_7").unwrap()),
+            &[],
+        );
+        assert!(target.to_string() == "cs_6_6");
+        let directives = Directives::parse("// hlsl_box: mode=pixel\n", None).unwrap();
+        assert!(directives.mode == Mode::Pixel);
+        match Directives::parse("// hlsl_box: mode=vertex\n", None) {
+            Err(Error::Compile(diagnostics)) => {
+                assert!(diagnostics.len() == 1);
+                assert!(diagnostics[0].message == "unknown mode 'vertex'");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_directives_mesh() {
+        let data = "// hlsl_box: vertex=vs_main, mesh=models/bunny.obj, entry=ps_main\n";
+        let path = Path::new("shaders/test.hlsl");
+        let directives = Directives::parse(data, Some(path)).unwrap();
+        assert!(directives.vertex_entry_point.as_deref() == Some("vs_main"));
+        assert!(directives.mesh.as_deref() == Some(Path::new("shaders/models/bunny.obj")));
+        let model = ShaderModel::specify("6_0").unwrap();
+        let (entry_point, target, _) = directives.apply("main", Target::VS(model), &[]);
+        assert!(entry_point == "vs_main");
+        assert!(target.to_string() == "vs_6_0");
+        let (entry_point, target, _) = directives.apply("main", Target::PS(model), &[]);
+        assert!(entry_point == "ps_main");
+        assert!(target.to_string() == "ps_6_0");
+    }
+
+    #[test]
+    fn parse_directives_channels() {
+        let data = "\
+// hlsl_box: channel0=images/noise.png, channel0.filter=point
+// hlsl_box: channel2=../sky.hdr, channel2.wrap=clamp
+";
+        let path = Path::new("shaders/test.hlsl");
+        let directives = Directives::parse(data, Some(path)).unwrap();
+        let channels = &directives.channels;
+        assert!(channels[0].path.as_deref() == Some(Path::new("shaders/images/noise.png")));
+        assert!(channels[0].wrap == Wrap::Repeat);
+        assert!(channels[0].filter == Filter::Point);
+        assert!(channels[1] == Channel::default());
+        assert!(channels[2].path.as_deref() == Some(Path::new("shaders/../sky.hdr")));
+        assert!(channels[2].wrap == Wrap::Clamp);
+        assert!(channels[2].filter == Filter::Linear);
+        let data = "// hlsl_box: channel4=a.png, channel1.wrap=border, channel1.filter=cubic\n";
+        match Directives::parse(data, None) {
+            Err(Error::Compile(diagnostics)) => {
+                assert!(diagnostics.len() == 3);
+                assert!(diagnostics[0].message == "unknown directive 'channel4'");
+                assert!(diagnostics[1].message == "unknown wrap mode 'border'");
+                assert!(diagnostics[2].message == "unknown filter 'cubic'");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_directives_passes() {
+        let data = "\
+// hlsl_box: pass_a=buffer_a.hlsl, pass_a.resolution=512x256
+// hlsl_box: pass_c=passes/blur.hlsl, pass_c.entry=blur
+";
+        let path = Path::new("shaders/image.hlsl");
+        let directives = Directives::parse(data, Some(path)).unwrap();
+        let passes = &directives.passes;
+        assert!(passes[0].path.as_deref() == Some(Path::new("shaders/buffer_a.hlsl")));
+        assert!(passes[0].entry_point.is_none());
+        assert!(passes[0].resolution == Some([512, 256]));
+        assert!(passes[1] == Pass::default());
+        assert!(passes[2].path.as_deref() == Some(Path::new("shaders/passes/blur.hlsl")));
+        assert!(passes[2].entry_point.as_deref() == Some("blur"));
+        assert!(passes[2].resolution.is_none());
+        let data = "// hlsl_box: pass_a.resolution=0x256, pass_b.resolution=512, pass_e=e.hlsl\n";
+        match Directives::parse(data, None) {
+            Err(Error::Compile(diagnostics)) => {
+                assert!(diagnostics.len() == 3);
+                assert!(diagnostics[0].message == "invalid resolution '0x256'");
+                assert!(diagnostics[1].message == "invalid resolution '512'");
+                assert!(diagnostics[2].message == "unknown directive 'pass_e'");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_directives_none() {
+        let data = "#include \"hlsl_box.hlsli\"\n// hlsl_box: entry=ignored\n";
+        let directives = Directives::parse(data, None).unwrap();
+        assert!(directives == Directives::default());
+    }
+
+    #[test]
+    fn parse_directives_error() {
+        let path = Path::new("test.hlsl");
+        let data = "// hlsl_box: entry=main, model=4_0, foo=1\n";
+        match Directives::parse(data, Some(path)) {
+            Err(Error::Compile(diagnostics)) => {
+                assert!(diagnostics.len() == 2);
+                assert!(diagnostics[0].file.as_deref() == Some(path));
+                assert!(diagnostics[0].line == 1);
+                assert!(diagnostics[0].column == 26);
+                assert!(diagnostics[0].severity == Severity::Error);
+                assert!(diagnostics[1].message == "unknown directive 'foo'");
+            }
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use super::*;
+use windows::core::{implement, Interface, GUID, PCWSTR, PWSTR};
+use windows::Win32::{Graphics::Direct3D::Dxc::*, System::Com::CoTaskMemFree};
+
+fn create_instance<T: Interface>(clsid: &GUID) -> Result<T, Error> {
+    unsafe { DxcCreateInstance(clsid).map_err(|e| e.into()) }
+}
+
+#[implement(IDxcIncludeHandler)]
+struct IncludeHandler {
+    utils: IDxcUtils,
+    default_handler: IDxcIncludeHandler,
+    dependencies: Rc<RefCell<Vec<PathBuf>>>,
+}
+
+#[allow(non_snake_case)]
+impl IDxcIncludeHandler_Impl for IncludeHandler {
+    fn LoadSource(&self, filename: &PCWSTR) -> windows::core::Result<IDxcBlob> {
+        unsafe {
+            let len = (0..).take_while(|&i| *filename.0.add(i) != 0).count();
+            let path = PathBuf::from(String::from_utf16_lossy(std::slice::from_raw_parts(
+                filename.0, len,
+            )));
+            if let Some(data) = include::embedded(&path) {
+                let blob =
+                    self.utils
+                        .CreateBlob(data.as_ptr() as _, data.len() as _, DXC_CP_UTF8)?;
+                return Ok(blob.into());
+            }
+            let data = std::fs::read(&path)
+                .ok()
+                .and_then(|data| Encoding::detect(&data).decode(&data));
+            let blob = match data {
+                Some(data) => self
+                    .utils
+                    .CreateBlob(data.as_ptr() as _, data.len() as _, DXC_CP_UTF8)?
+                    .into(),
+                None => self.default_handler.LoadSource(*filename)?,
+            };
+            let path = path.canonicalize().unwrap_or(path);
+            let mut dependencies = self.dependencies.borrow_mut();
+            if !dependencies.contains(&path) {
+                dependencies.push(path);
+            }
+            Ok(blob)
+        }
+    }
+}
+
+fn compiler_version(compiler: &IDxcCompiler3) -> String {
+    let mut version = "dxcompiler".to_string();
+    unsafe {
+        if let Ok(info) = compiler.cast::<IDxcVersionInfo>() {
+            let (mut major, mut minor) = (0, 0);
+            if info.GetVersion(&mut major, &mut minor).is_ok() {
+                version += &format!(" {}.{}", major, minor);
+            }
+        }
+        if let Ok(info) = compiler.cast::<IDxcVersionInfo2>() {
+            let mut count = 0;
+            let mut hash = std::ptr::null_mut();
+            if info.GetCommitInfo(&mut count, &mut hash).is_ok() && !hash.is_null() {
+                let hash_str = std::ffi::CStr::from_ptr(hash).to_string_lossy();
+                version += &format!(" ({} {})", count, hash_str);
+                CoTaskMemFree(hash as _);
+            }
+        }
+    }
+    version
+}
+
+pub(super) struct DxcApi {
+    utils: IDxcUtils,
+    compiler: IDxcCompiler3,
+    version: String,
+    include_handler: IDxcIncludeHandler,
+    dependencies: Rc<RefCell<Vec<PathBuf>>>,
+}
+
+impl DxcApi {
+    pub fn new() -> Result<Self, Error> {
+        unsafe {
+            let utils: IDxcUtils = create_instance(&CLSID_DxcLibrary)?;
+            let compiler: IDxcCompiler3 = create_instance(&CLSID_DxcCompiler)?;
+            let dependencies = Rc::new(RefCell::new(Vec::new()));
+            let include_handler = IncludeHandler {
+                utils: utils.clone(),
+                default_handler: utils.CreateDefaultIncludeHandler()?,
+                dependencies: dependencies.clone(),
+            }
+            .into();
+            Ok(Self {
+                version: compiler_version(&compiler),
+                utils,
+                compiler,
+                include_handler,
+                dependencies,
+            })
+        }
+    }
+
+    fn run(&self, data: &str, path: Option<&Path>, args: &[&str]) -> Result<IDxcResult, Error> {
+        {
+            let mut dependencies = self.dependencies.borrow_mut();
+            dependencies.clear();
+            if let Some(path) = path {
+                dependencies.push(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+            }
+        }
+        let mut tmp = args
+            .iter()
+            .copied()
+            .chain(path.and_then(|path| path.to_str()))
+            .map(|a| a.encode_utf16().chain(Some(0)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let args = tmp
+            .iter_mut()
+            .map(|t| PWSTR(t.as_mut_ptr()))
+            .collect::<Vec<_>>();
+        unsafe {
+            let src =
+                self.utils
+                    .CreateBlob(data.as_ptr() as _, data.bytes().len() as _, DXC_CP_UTF8)?;
+            let buffer = DxcBuffer {
+                Ptr: src.GetBufferPointer(),
+                Size: src.GetBufferSize(),
+                Encoding: DXC_CP_UTF8.0,
+            };
+            let result = {
+                let mut result: Option<IDxcResult> = None;
+                self.compiler
+                    .Compile(
+                        &buffer,
+                        &args,
+                        &self.include_handler,
+                        &IDxcResult::IID,
+                        &mut result as *mut _ as _,
+                    )
+                    .map(|_| result.unwrap())?
+            };
+            if let Err(e) = result.GetStatus()?.ok() {
+                let mut blob: Option<IDxcBlobUtf8> = None;
+                result.GetOutput(
+                    DXC_OUT_ERRORS,
+                    &IDxcBlobUtf8::IID,
+                    &mut blob as *mut _ as _,
+                    std::ptr::null_mut(),
+                )?;
+                return Err(match blob {
+                    Some(blob) => Error::from_dxc_errors(blob, e),
+                    None => e.into(),
+                });
+            }
+            Ok(result)
+        }
+    }
+}
+
+fn output_text(result: &IDxcResult, kind: DXC_OUT_KIND) -> Result<String, Error> {
+    unsafe {
+        let mut blob: Option<IDxcBlobUtf8> = None;
+        result.GetOutput(
+            kind,
+            &IDxcBlobUtf8::IID,
+            &mut blob as *mut _ as _,
+            std::ptr::null_mut(),
+        )?;
+        Ok(blob.map_or_else(String::new, |blob| {
+            String::from_utf8_lossy(std::slice::from_raw_parts(
+                blob.GetStringPointer().0,
+                blob.GetStringLength(),
+            ))
+            .to_string()
+        }))
+    }
+}
+
+impl Backend for DxcApi {
+    fn version(&self) -> String {
+        self.version.clone()
+    }
+
+    fn compile(
+        &self,
+        data: &str,
+        path: Option<&Path>,
+        args: &[String],
+    ) -> Result<(Blob, String), Error> {
+        let args = args.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+        let result = self.run(data, path, &args)?;
+        let blob = unsafe {
+            let mut blob: Option<IDxcBlob> = None;
+            result.GetOutput(
+                DXC_OUT_OBJECT,
+                &IDxcBlob::IID,
+                &mut blob as *mut _ as _,
+                std::ptr::null_mut(),
+            )?;
+            let blob = blob.unwrap();
+            Blob(
+                std::slice::from_raw_parts(
+                    blob.GetBufferPointer() as *const u8,
+                    blob.GetBufferSize(),
+                )
+                .to_vec(),
+            )
+        };
+        Ok((blob, output_text(&result, DXC_OUT_ERRORS)?))
+    }
+
+    fn preprocess(
+        &self,
+        data: &str,
+        path: Option<&Path>,
+        args: &[String],
+    ) -> Result<String, Error> {
+        let args = args
+            .iter()
+            .map(|a| a.as_str())
+            .chain(Some("-P"))
+            .collect::<Vec<_>>();
+        let result = self.run(data, path, &args)?;
+        output_text(&result, DXC_OUT_HLSL)
+    }
+
+    fn reflect(&self, blob: &Blob) -> Result<Reflection, Error> {
+        Reflection::new(&self.utils, blob)
+    }
+
+    fn dependencies(&self) -> Vec<PathBuf> {
+        self.dependencies.borrow().clone()
+    }
+}
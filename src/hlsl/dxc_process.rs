@@ -0,0 +1,589 @@
+use super::include::*;
+use super::*;
+#[cfg(windows)]
+use once_cell::unsync::OnceCell;
+use std::path::Component;
+use std::process::Command;
+use std::sync::atomic::{self, AtomicUsize};
+#[cfg(windows)]
+use windows::Win32::Graphics::Direct3D::Dxc::*;
+
+static TEMP_ID: AtomicUsize = AtomicUsize::new(0);
+
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(ext: &str) -> Self {
+        let id = TEMP_ID.fetch_add(1, atomic::Ordering::SeqCst);
+        Self(std::env::temp_dir().join(format!("hlsl_box-{}-{}.{}", std::process::id(), id, ext)))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| {
+        std::env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    })
+}
+
+// dxc reads included files by itself, so the sources are decoded into a copy of their
+// directories and dxc reads the copies instead.
+struct SourceDir {
+    root: PathBuf,
+    names: Vec<(String, String)>,
+}
+
+impl SourceDir {
+    fn new() -> Result<Self, Error> {
+        let id = TEMP_ID.fetch_add(1, atomic::Ordering::SeqCst);
+        let root = std::env::temp_dir().join(format!("hlsl_box-{}-{}", std::process::id(), id));
+        std::fs::create_dir_all(&root).map_err(|_| Error::CreateFile(root.clone()))?;
+        Ok(Self {
+            root,
+            names: Vec::new(),
+        })
+    }
+
+    fn copy_path(&self, path: &Path) -> PathBuf {
+        let mut copy = self.root.clone();
+        for component in absolute(path).components() {
+            match component {
+                Component::Prefix(prefix) => copy.push(
+                    prefix
+                        .as_os_str()
+                        .to_string_lossy()
+                        .replace(|c: char| !c.is_ascii_alphanumeric(), ""),
+                ),
+                Component::Normal(name) => copy.push(name),
+                _ => {}
+            }
+        }
+        copy
+    }
+
+    fn map(&mut self, copy: &Path, name: String) {
+        let copy = copy.to_string_lossy().to_string();
+        if !self.names.iter().any(|(c, _)| *c == copy) {
+            self.names.push((copy, name));
+        }
+    }
+
+    fn map_dir(&mut self, dir: &Path) -> PathBuf {
+        let copy = self.copy_path(dir);
+        self.map(&copy, absolute(dir).to_string_lossy().to_string());
+        copy
+    }
+
+    fn write(&mut self, path: &Path, data: &[u8]) -> Result<PathBuf, Error> {
+        let copy = self.copy_path(path);
+        if let Some(dir) = path.parent() {
+            let dir = self.map_dir(dir);
+            std::fs::create_dir_all(&dir).map_err(|_| Error::CreateFile(dir.clone()))?;
+        }
+        std::fs::write(&copy, data).map_err(|_| Error::CreateFile(copy.clone()))?;
+        Ok(copy)
+    }
+
+    fn write_source(&mut self, data: &str, path: Option<&Path>) -> Result<PathBuf, Error> {
+        let copy = match path {
+            Some(path) => self.write(path, data.as_bytes())?,
+            None => {
+                let copy = self.root.join("hlsl.hlsl");
+                std::fs::write(&copy, data).map_err(|_| Error::CreateFile(copy.clone()))?;
+                copy
+            }
+        };
+        self.map(&copy, source_name(path));
+        Ok(copy)
+    }
+
+    fn write_include(&mut self, path: &Path) -> Result<(), Error> {
+        let data = std::fs::read(path).map_err(|_| Error::ReadFile(path.to_path_buf()))?;
+        let data = match Encoding::detect(&data).decode(&data) {
+            Some(text) => text.into_bytes(),
+            None => data,
+        };
+        self.write(path, &data)?;
+        Ok(())
+    }
+
+    fn write_embedded(&mut self) -> Result<PathBuf, Error> {
+        for (name, data) in embedded_files() {
+            let path = EMBEDDED_DIR.join(name);
+            if !path.is_file() {
+                self.write(&path, data.as_bytes())?;
+            }
+        }
+        Ok(self.map_dir(&EMBEDDED_DIR))
+    }
+
+    fn map_args(&mut self, args: &[String]) -> Vec<String> {
+        let mut ret = Vec::with_capacity(args.len());
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "-I" {
+                ret.push(arg.clone());
+                if let Some(dir) = iter.next() {
+                    ret.push(self.map_dir(Path::new(dir)).to_string_lossy().to_string());
+                }
+            } else if let Some(dir) = arg.strip_prefix("-I") {
+                let dir = self.map_dir(Path::new(dir));
+                ret.push(format!("-I{}", dir.display()));
+            } else {
+                ret.push(arg.clone());
+            }
+        }
+        ret
+    }
+
+    fn restore(&self, text: &str) -> String {
+        let mut names = self.names.iter().collect::<Vec<_>>();
+        names.sort_by_key(|(copy, _)| std::cmp::Reverse(copy.len()));
+        names.iter().fold(text.to_string(), |text, (copy, name)| {
+            text.replace(copy, name)
+        })
+    }
+}
+
+impl Drop for SourceDir {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.root).ok();
+    }
+}
+
+pub(super) struct DxcProcess {
+    path: PathBuf,
+    #[cfg(windows)]
+    utils: OnceCell<IDxcUtils>,
+    dependencies: RefCell<Vec<PathBuf>>,
+}
+
+impl DxcProcess {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            #[cfg(windows)]
+            utils: OnceCell::new(),
+            dependencies: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn run(
+        &self,
+        data: &str,
+        path: Option<&Path>,
+        args: &[String],
+        preprocess: bool,
+    ) -> Result<(Vec<u8>, String), Error> {
+        let dir = path.and_then(|path| path.parent()).map(|dir| {
+            if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            }
+        });
+        {
+            let mut dependencies = self.dependencies.borrow_mut();
+            dependencies.clear();
+            if let Some(path) = path {
+                dependencies.push(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+            }
+            scan_includes(data, dir, &include_dirs(args), &mut dependencies);
+        }
+        let mut sources = SourceDir::new()?;
+        let src = sources.write_source(data, path)?;
+        for dependency in self.dependencies.borrow().iter().skip(path.iter().count()) {
+            sources.write_include(dependency)?;
+        }
+        let embedded_dir = sources.write_embedded()?;
+        let out = TempFile::new("out");
+        let mut command = Command::new(&self.path);
+        if let Some(dir) = dir {
+            command.arg("-I").arg(sources.map_dir(dir));
+        }
+        command.args(sources.map_args(args));
+        command.arg("-I").arg(embedded_dir);
+        if preprocess {
+            command.arg("-P").arg("-Fi");
+        } else {
+            command.arg("-Fo");
+        }
+        command.arg(&out.0).arg(&src);
+        debug!("DxcProcess::run: {:?}", command);
+        let output = command
+            .output()
+            .map_err(|_| Error::ExecuteCompiler(self.path.clone()))?;
+        let restore_paths = |text: &[u8]| sources.restore(&String::from_utf8_lossy(text));
+        let messages = [&output.stderr, &output.stdout]
+            .iter()
+            .map(|text| restore_paths(text))
+            .filter(|text| !text.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if !output.status.success() {
+            let diagnostics = Diagnostic::parse(&messages);
+            if diagnostics.is_empty() {
+                return Err(Error::Compile(vec![Diagnostic::new(
+                    Severity::Error,
+                    format!("{}: {}", self.path.display(), output.status),
+                )]));
+            }
+            return Err(Error::Compile(diagnostics));
+        }
+        let data = std::fs::read(&out.0).map_err(|_| Error::ReadFile(out.0.clone()))?;
+        if !preprocess {
+            return Ok((data, messages));
+        }
+        Ok((restore_paths(&data).into_bytes(), messages))
+    }
+}
+
+fn source_name(path: Option<&Path>) -> String {
+    path.map_or_else(|| "hlsl.hlsl".to_string(), |p| p.display().to_string())
+}
+
+impl Backend for DxcProcess {
+    fn version(&self) -> String {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |modified| modified.as_nanos());
+        format!("{} {}", self.path.display(), modified)
+    }
+
+    fn compile(
+        &self,
+        data: &str,
+        path: Option<&Path>,
+        args: &[String],
+    ) -> Result<(Blob, String), Error> {
+        let (data, messages) = self.run(data, path, args, false)?;
+        Ok((Blob(data), messages))
+    }
+
+    fn preprocess(
+        &self,
+        data: &str,
+        path: Option<&Path>,
+        args: &[String],
+    ) -> Result<String, Error> {
+        let (data, _) = self.run(data, path, args, true)?;
+        Ok(String::from_utf8_lossy(&data).to_string())
+    }
+
+    #[cfg(windows)]
+    fn reflect(&self, blob: &Blob) -> Result<Reflection, Error> {
+        // dxc cannot output the reflection, so it is read with dxcompiler.dll.
+        let utils = self
+            .utils
+            .get_or_try_init(|| unsafe { DxcCreateInstance(&CLSID_DxcLibrary) })?;
+        Reflection::new(utils, blob)
+    }
+
+    fn dependencies(&self) -> Vec<PathBuf> {
+        self.dependencies.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(windows)]
+    const SUCCESS_SCRIPT: (&str, &str) = (
+        "dxc_success.cmd",
+        "@echo off\r\n\
+         set out=\r\n\
+         set pre=\r\n\
+         :loop\r\n\
+         if \"%~1\"==\"\" goto end\r\n\
+         if \"%~1\"==\"-Fo\" set \"out=%~2\"\r\n\
+         if \"%~1\"==\"-Fi\" set \"pre=%~2\"\r\n\
+         set \"last=%~1\"\r\n\
+         shift\r\n\
+         goto loop\r\n\
+         :end\r\n\
+         if defined out (<nul set /p=\"DXIL\">\"%out%\")\r\n\
+         if defined out (echo %last%:1:1: warning: stand-in warning 1>&2)\r\n\
+         if defined pre (echo #line 1 \"%last%\">\"%pre%\")\r\n",
+    );
+    #[cfg(unix)]
+    const SUCCESS_SCRIPT: (&str, &str) = (
+        "dxc_success.sh",
+        "#!/bin/sh\n\
+         out=\n\
+         pre=\n\
+         while [ $# -gt 0 ]; do\n\
+         case \"$1\" in\n\
+         -Fo) out=\"$2\";;\n\
+         -Fi) pre=\"$2\";;\n\
+         esac\n\
+         last=\"$1\"\n\
+         shift\n\
+         done\n\
+         if [ -n \"$out\" ]; then printf DXIL > \"$out\"; fi\n\
+         if [ -n \"$out\" ]; then echo \"$last:1:1: warning: stand-in warning\" >&2; fi\n\
+         if [ -n \"$pre\" ]; then printf '#line 1 \"%s\"\\n' \"$last\" > \"$pre\"; fi\n",
+    );
+    #[cfg(windows)]
+    const COUNT_SCRIPT: (&str, &str) = (
+        "dxc_count.cmd",
+        "@echo off\r\n\
+         set out=\r\n\
+         set pre=\r\n\
+         :loop\r\n\
+         if \"%~1\"==\"\" goto end\r\n\
+         if \"%~1\"==\"-Fo\" set \"out=%~2\"\r\n\
+         if \"%~1\"==\"-Fi\" set \"pre=%~2\"\r\n\
+         set \"last=%~1\"\r\n\
+         shift\r\n\
+         goto loop\r\n\
+         :end\r\n\
+         if defined out (<nul set /p=\"DXIL\">\"%out%\")\r\n\
+         if defined out (echo compile>>\"%~dp0dxc_count.log\")\r\n\
+         if defined pre (echo #line 1 \"%last%\">\"%pre%\")\r\n",
+    );
+    #[cfg(unix)]
+    const COUNT_SCRIPT: (&str, &str) = (
+        "dxc_count.sh",
+        "#!/bin/sh\n\
+         out=\n\
+         pre=\n\
+         while [ $# -gt 0 ]; do\n\
+         case \"$1\" in\n\
+         -Fo) out=\"$2\";;\n\
+         -Fi) pre=\"$2\";;\n\
+         esac\n\
+         last=\"$1\"\n\
+         shift\n\
+         done\n\
+         if [ -n \"$out\" ]; then printf DXIL > \"$out\"; fi\n\
+         if [ -n \"$out\" ]; then echo compile >> \"$(dirname \"$0\")/dxc_count.log\"; fi\n\
+         if [ -n \"$pre\" ]; then printf '#line 1 \"%s\"\\n' \"$last\" > \"$pre\"; fi\n",
+    );
+
+    #[cfg(windows)]
+    const FAILURE_SCRIPT: (&str, &str) = (
+        "dxc_failure.cmd",
+        "@echo off\r\necho error: stand-in compiler 1>&2\r\nexit /b 1\r\n",
+    );
+    #[cfg(unix)]
+    const FAILURE_SCRIPT: (&str, &str) = (
+        "dxc_failure.sh",
+        "#!/bin/sh\necho 'error: stand-in compiler' >&2\nexit 1\n",
+    );
+
+    #[cfg(windows)]
+    const STDOUT_SCRIPT: (&str, &str) = (
+        "dxc_stdout.cmd",
+        "@echo off\r\necho error: stand-in stdout\r\nexit /b 1\r\n",
+    );
+    #[cfg(unix)]
+    const STDOUT_SCRIPT: (&str, &str) = (
+        "dxc_stdout.sh",
+        "#!/bin/sh\necho 'error: stand-in stdout'\nexit 1\n",
+    );
+
+    #[cfg(windows)]
+    const INCLUDE_SCRIPT: (&str, &str) = (
+        "dxc_include.cmd",
+        "@echo off\r\n\
+         set pre=\r\n\
+         :loop\r\n\
+         if \"%~1\"==\"\" goto end\r\n\
+         if \"%~1\"==\"-Fi\" set \"pre=%~2\"\r\n\
+         set \"last=%~1\"\r\n\
+         shift\r\n\
+         goto loop\r\n\
+         :end\r\n\
+         for %%f in (\"%last%\") do type \"%%~dpfinclude.hlsli\">\"%pre%\"\r\n",
+    );
+    #[cfg(unix)]
+    const INCLUDE_SCRIPT: (&str, &str) = (
+        "dxc_include.sh",
+        "#!/bin/sh\n\
+         pre=\n\
+         while [ $# -gt 0 ]; do\n\
+         case \"$1\" in\n\
+         -Fi) pre=\"$2\";;\n\
+         esac\n\
+         last=\"$1\"\n\
+         shift\n\
+         done\n\
+         cat \"$(dirname \"$last\")/include.hlsli\" > \"$pre\"\n",
+    );
+
+    fn create_script((name, script): (&str, &str)) -> PathBuf {
+        let dir = Path::new("./target/dxc_process_test");
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, script).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn dxc_process_success() {
+        let backend = DxcProcess::new(create_script(SUCCESS_SCRIPT));
+        let path = Path::new("examples/fill.hlsl");
+        let data = std::fs::read_to_string(path).unwrap();
+        let args = ["-I".to_string(), "./include".to_string()];
+        let (blob, messages) = backend.compile(&data, Some(path), &args).unwrap();
+        assert!(blob.0 == b"DXIL");
+        let warnings = Diagnostic::parse(&messages);
+        assert!(warnings.len() == 1);
+        assert!(warnings[0].file.as_deref() == Some(path));
+        assert!(warnings[0].severity == Severity::Warning);
+        let dependencies = backend.dependencies();
+        assert!(dependencies.len() == 2);
+        assert!(dependencies[0] == path.canonicalize().unwrap());
+        assert!(dependencies[1] == Path::new("include/hlsl_box.hlsli").canonicalize().unwrap());
+    }
+
+    #[test]
+    fn dxc_process_preprocess() {
+        let backend = DxcProcess::new(create_script(SUCCESS_SCRIPT));
+        let path = Path::new("examples/fill.hlsl");
+        let data = std::fs::read_to_string(path).unwrap();
+        let src = backend.preprocess(&data, Some(path), &[]).unwrap();
+        assert!(src.trim_end() == format!("#line 1 \"{}\"", path.display()));
+    }
+
+    fn preprocess_include(name: &str, include: &[u8]) -> String {
+        let dir = Path::new("./target/dxc_process_test").join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.hlsl");
+        let data = "#include \"include.hlsli\"\n";
+        std::fs::write(&path, data).unwrap();
+        std::fs::write(dir.join("include.hlsli"), include).unwrap();
+        let backend = DxcProcess::new(create_script(INCLUDE_SCRIPT));
+        backend.preprocess(data, Some(&path), &[]).unwrap()
+    }
+
+    #[test]
+    fn dxc_process_utf16_include() {
+        let include = [0xff, 0xfe]
+            .into_iter()
+            .chain("// 日本語\n".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect::<Vec<u8>>();
+        assert!(preprocess_include("utf16", &include) == "// 日本語\n");
+    }
+
+    #[test]
+    fn dxc_process_shift_jis_include() {
+        let include = b"// \x93\xfa\x96\x7b\x8c\xea\n";
+        assert!(preprocess_include("shift_jis", include) == "// 日本語\n");
+    }
+
+    #[test]
+    fn dxc_process_cache_hit() {
+        let dir = Path::new("./target/dxc_process_test/cache");
+        Cache::clear(dir).unwrap();
+        let log = Path::new("./target/dxc_process_test/dxc_count.log");
+        std::fs::remove_file(log).ok();
+        let mut compiler = Compiler::with_dxc(create_script(COUNT_SCRIPT));
+        compiler.set_cache(Some(Cache::new(dir, 1024 * 1024)));
+        let compiles = || {
+            std::fs::read_to_string(log)
+                .unwrap_or_default()
+                .lines()
+                .count()
+        };
+        let version = ShaderModel::specify("6_0").unwrap();
+        let path = Path::new("examples/fill.hlsl");
+        let a = compiler
+            .compile_from_file(path, "main", Target::PS(version), &[])
+            .unwrap();
+        assert!(compiles() == 1);
+        let b = compiler
+            .compile_from_file(path, "main", Target::PS(version), &[])
+            .unwrap();
+        assert!(a == b);
+        assert!(compiles() == 1);
+        compiler
+            .compile_from_file(path, "main", Target::PS(version), &["-O0".to_string()])
+            .unwrap();
+        assert!(compiles() == 2);
+    }
+
+    #[test]
+    fn dxc_process_compiler() {
+        let mut compiler = Compiler::with_dxc(create_script(SUCCESS_SCRIPT));
+        compiler.set_include_paths(vec![PathBuf::from("include")]);
+        let target = Target::PS(ShaderModel::latest());
+        compiler
+            .compile_from_str(
+                "float4 main(): SV_Target { return 0; }",
+                "main",
+                target,
+                &[],
+            )
+            .unwrap();
+        assert!(compiler.warnings().len() == 1);
+        let path = Path::new("examples/fill.hlsl");
+        compiler
+            .compile_from_file(path, "main", target, &[])
+            .unwrap();
+        let dependencies = compiler.dependencies();
+        assert!(dependencies.len() == 2);
+        assert!(dependencies[1] == Path::new("include/hlsl_box.hlsli").canonicalize().unwrap());
+        let directives = Directives::from_file(path).unwrap();
+        let src = compiler
+            .flatten_file(
+                path,
+                path.with_extension("flat.hlsl"),
+                &directives,
+                target,
+                &[],
+            )
+            .unwrap();
+        assert!(src.starts_with("// hlsl_box: entry=main\n// flattened from fill.hlsl\n#line 1"));
+    }
+
+    #[test]
+    fn dxc_process_failure() {
+        let backend = DxcProcess::new(create_script(FAILURE_SCRIPT));
+        match backend.compile("", None, &[]) {
+            Err(Error::Compile(diagnostics)) => {
+                assert!(diagnostics.len() == 1);
+                assert!(diagnostics[0].severity == Severity::Error);
+                assert!(diagnostics[0].message == "stand-in compiler");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn dxc_process_stdout() {
+        let backend = DxcProcess::new(create_script(STDOUT_SCRIPT));
+        match backend.compile("", None, &[]) {
+            Err(Error::Compile(diagnostics)) => {
+                assert!(diagnostics.len() == 1);
+                assert!(diagnostics[0].severity == Severity::Error);
+                assert!(diagnostics[0].message == "stand-in stdout");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn dxc_process_not_found() {
+        let backend = DxcProcess::new("./target/dxc_process_test/not_found");
+        assert!(matches!(
+            backend.compile("", None, &[]),
+            Err(Error::ExecuteCompiler(_))
+        ));
+    }
+}
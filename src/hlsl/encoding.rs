@@ -0,0 +1,158 @@
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Encoding {
+    Utf8,
+    Utf8Bom,
+    Utf16LE,
+    Utf16BE,
+    ShiftJis,
+}
+
+impl Encoding {
+    pub fn detect(data: &[u8]) -> Self {
+        if data.starts_with(&[0xef, 0xbb, 0xbf]) {
+            return Self::Utf8Bom;
+        }
+        if data.starts_with(&[0xff, 0xfe]) {
+            return Self::Utf16LE;
+        }
+        if data.starts_with(&[0xfe, 0xff]) {
+            return Self::Utf16BE;
+        }
+        if data.len() % 2 == 0 {
+            let zeros = |offset: usize| {
+                data.iter()
+                    .skip(offset)
+                    .step_by(2)
+                    .filter(|b| **b == 0)
+                    .count()
+            };
+            let threshold = data.len() / 4;
+            if zeros(1) > threshold {
+                return Self::Utf16LE;
+            }
+            if zeros(0) > threshold {
+                return Self::Utf16BE;
+            }
+        }
+        if std::str::from_utf8(data).is_ok() {
+            return Self::Utf8;
+        }
+        if is_shift_jis(data) {
+            return Self::ShiftJis;
+        }
+        Self::Utf8
+    }
+
+    pub fn decode(&self, data: &[u8]) -> Option<String> {
+        match self {
+            Self::Utf8 => String::from_utf8(data.to_vec()).ok(),
+            Self::Utf8Bom => String::from_utf8(data[3..].to_vec()).ok(),
+            Self::Utf16LE => decode_utf16(data, u16::from_le_bytes),
+            Self::Utf16BE => decode_utf16(data, u16::from_be_bytes),
+            Self::ShiftJis => decode_shift_jis(data),
+        }
+    }
+}
+
+impl std::fmt::Display for Encoding {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Utf8 => write!(fmt, "UTF-8"),
+            Self::Utf8Bom => write!(fmt, "UTF-8 (BOM)"),
+            Self::Utf16LE => write!(fmt, "UTF-16LE"),
+            Self::Utf16BE => write!(fmt, "UTF-16BE"),
+            Self::ShiftJis => write!(fmt, "Shift_JIS"),
+        }
+    }
+}
+
+fn is_shift_jis(data: &[u8]) -> bool {
+    let mut iter = data.iter();
+    while let Some(&b) = iter.next() {
+        match b {
+            0x00..=0x7f | 0xa1..=0xdf => {}
+            0x81..=0x9f | 0xe0..=0xfc => match iter.next() {
+                Some(0x40..=0x7e | 0x80..=0xfc) => {}
+                _ => return false,
+            },
+            _ => return false,
+        }
+    }
+    true
+}
+
+fn decode_utf16(data: &[u8], f: fn([u8; 2]) -> u16) -> Option<String> {
+    if data.len() % 2 != 0 {
+        return None;
+    }
+    let data = data
+        .chunks_exact(2)
+        .map(|c| f([c[0], c[1]]))
+        .collect::<Vec<_>>();
+    let data = data.strip_prefix(&[0xfeff]).unwrap_or(&data);
+    String::from_utf16(data).ok()
+}
+
+// encoding_rs follows the WHATWG Shift_JIS, which covers the CP932 extensions.
+fn decode_shift_jis(data: &[u8]) -> Option<String> {
+    encoding_rs::SHIFT_JIS
+        .decode_without_bom_handling_and_without_replacement(data)
+        .map(|s| s.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "// 日本語\nfloat4 main(): SV_Target { return 0; }\n";
+    const SHIFT_JIS: &[u8] =
+        b"// \x93\xfa\x96\x7b\x8c\xea\nfloat4 main(): SV_Target { return 0; }\n";
+
+    #[test]
+    fn detect_utf8() {
+        assert!(Encoding::detect(TEXT.as_bytes()) == Encoding::Utf8);
+        let data = [&[0xef, 0xbb, 0xbf], TEXT.as_bytes()].concat();
+        let encoding = Encoding::detect(&data);
+        assert!(encoding == Encoding::Utf8Bom);
+        assert!(encoding.decode(&data).as_deref() == Some(TEXT));
+    }
+
+    #[test]
+    fn detect_utf16() {
+        let le = TEXT
+            .encode_utf16()
+            .flat_map(|c| c.to_le_bytes())
+            .collect::<Vec<_>>();
+        let be = TEXT
+            .encode_utf16()
+            .flat_map(|c| c.to_be_bytes())
+            .collect::<Vec<_>>();
+        assert!(Encoding::detect(&le) == Encoding::Utf16LE);
+        assert!(Encoding::detect(&be) == Encoding::Utf16BE);
+        let ascii = b"f\0l\0o\0a\0t\0";
+        assert!(Encoding::detect(ascii) == Encoding::Utf16LE);
+        let le_bom = [&[0xff, 0xfe], le.as_slice()].concat();
+        let be_bom = [&[0xfe, 0xff], be.as_slice()].concat();
+        assert!(Encoding::detect(&le_bom) == Encoding::Utf16LE);
+        assert!(Encoding::detect(&be_bom) == Encoding::Utf16BE);
+        assert!(Encoding::Utf16LE.decode(&le).as_deref() == Some(TEXT));
+        assert!(Encoding::Utf16LE.decode(&le_bom).as_deref() == Some(TEXT));
+        assert!(Encoding::Utf16BE.decode(&be_bom).as_deref() == Some(TEXT));
+        assert!(Encoding::Utf16LE.decode(&le[1..]).is_none());
+    }
+
+    #[test]
+    fn detect_shift_jis() {
+        assert!(Encoding::detect(SHIFT_JIS) == Encoding::ShiftJis);
+        assert!(Encoding::detect(b"\x93\xfa\xff") == Encoding::Utf8);
+        assert!(Encoding::Utf8.decode(b"\x93\xfa\xff").is_none());
+    }
+
+    #[test]
+    fn convert_shift_jis() {
+        assert!(Encoding::ShiftJis.decode(SHIFT_JIS).as_deref() == Some(TEXT));
+        // NEC special characters of CP932
+        assert!(Encoding::ShiftJis.decode(b"\x87\x40").as_deref() == Some("①"));
+        assert!(Encoding::ShiftJis.decode(b"\x93").is_none());
+    }
+}
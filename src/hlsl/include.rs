@@ -0,0 +1,319 @@
+use super::*;
+
+const EMBEDDED: &[(&str, &str)] = &[
+    (
+        "hlsl_box.hlsli",
+        include_str!("../../include/hlsl_box.hlsli"),
+    ),
+    (
+        "constants.hlsli",
+        include_str!("../../include/constants.hlsli"),
+    ),
+];
+
+pub(super) static EMBEDDED_DIR: Lazy<PathBuf> = Lazy::new(|| EXE_DIR_PATH.join("include"));
+
+pub(super) fn embedded_files() -> impl Iterator<Item = (&'static str, &'static str)> {
+    EMBEDDED.iter().copied()
+}
+
+pub(super) fn embedded(path: &Path) -> Option<&'static str> {
+    if path.parent() != Some(EMBEDDED_DIR.as_path()) || path.is_file() {
+        return None;
+    }
+    let name = path.file_name()?;
+    EMBEDDED
+        .iter()
+        .find(|(n, _)| name == *n)
+        .map(|(_, data)| *data)
+}
+
+pub(super) fn include_dirs(args: &[String]) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "-I" {
+            if let Some(dir) = iter.next() {
+                dirs.push(PathBuf::from(dir));
+            }
+        } else if let Some(dir) = arg.strip_prefix("-I") {
+            dirs.push(PathBuf::from(dir));
+        }
+    }
+    dirs
+}
+
+fn includes(data: &str) -> impl Iterator<Item = String> + '_ {
+    static RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r#"(?m)^\s*#\s*include\s*[<"]([^>"]+)[>"]"#).unwrap());
+    RE.captures_iter(data).map(|cap| cap[1].to_string())
+}
+
+fn read_include(path: &Path) -> Option<String> {
+    let data = std::fs::read(path).ok()?;
+    Encoding::detect(&data).decode(&data)
+}
+
+fn resolve(name: &str, dir: Option<&Path>, include_dirs: &[PathBuf]) -> Option<PathBuf> {
+    let path = dir
+        .into_iter()
+        .chain(include_dirs.iter().map(|d| d.as_path()))
+        .map(|d| d.join(name))
+        .find(|path| path.is_file())?;
+    Some(path.canonicalize().unwrap_or(path))
+}
+
+pub(super) fn scan_includes(
+    data: &str,
+    dir: Option<&Path>,
+    include_dirs: &[PathBuf],
+    dependencies: &mut Vec<PathBuf>,
+) {
+    for name in includes(data) {
+        let path = match resolve(&name, dir, include_dirs) {
+            Some(path) => path,
+            None => continue,
+        };
+        if dependencies.contains(&path) {
+            continue;
+        }
+        dependencies.push(path.clone());
+        if let Some(data) = read_include(&path) {
+            scan_includes(&data, path.parent(), include_dirs, dependencies);
+        }
+    }
+}
+
+fn find_chain(
+    data: &str,
+    dir: Option<&Path>,
+    include_dirs: &[PathBuf],
+    target: &Path,
+    chain: &mut Vec<PathBuf>,
+) -> bool {
+    for name in includes(data) {
+        let path = match resolve(&name, dir, include_dirs) {
+            Some(path) => path,
+            None => continue,
+        };
+        if chain.contains(&path) {
+            continue;
+        }
+        chain.push(path.clone());
+        if path == target {
+            return true;
+        }
+        if let Some(data) = read_include(&path) {
+            if find_chain(&data, path.parent(), include_dirs, target, chain) {
+                return true;
+            }
+        }
+        chain.pop();
+    }
+    false
+}
+
+pub(super) fn include_chain(
+    data: &str,
+    path: Option<&Path>,
+    include_dirs: &[PathBuf],
+    target: &Path,
+) -> Vec<PathBuf> {
+    let target = target
+        .canonicalize()
+        .unwrap_or_else(|_| target.to_path_buf());
+    let path = path.map(|path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+    let mut chain = path.iter().cloned().collect::<Vec<_>>();
+    if path.as_ref() == Some(&target) {
+        return chain;
+    }
+    let dir = path.as_ref().and_then(|path| path.parent());
+    if find_chain(data, dir, include_dirs, &target, &mut chain) {
+        chain
+    } else {
+        vec![]
+    }
+}
+
+pub(super) fn annotate_missing_includes(
+    diagnostics: Vec<Diagnostic>,
+    data: &str,
+    path: Option<&Path>,
+    include_dirs: &[PathBuf],
+) -> Vec<Diagnostic> {
+    static RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^'(.+)' file not found$").unwrap());
+    let mut ret = Vec::with_capacity(diagnostics.len());
+    for d in diagnostics {
+        let missing = match (&d.file, RE.captures(&d.message)) {
+            (Some(file), Some(cap)) if d.severity == Severity::Error => {
+                Some((file.clone(), cap[1].to_string()))
+            }
+            _ => None,
+        };
+        ret.push(d);
+        let (file, name) = match missing {
+            Some(missing) => missing,
+            None => continue,
+        };
+        let chain = include_chain(data, path, include_dirs, &file);
+        if !chain.is_empty() {
+            let chain = chain
+                .iter()
+                .map(|p| p.display().to_string())
+                .chain(Some(format!("'{}'", name)))
+                .collect::<Vec<_>>();
+            ret.push(Diagnostic::new(
+                Severity::Note,
+                format!("include chain: {}", chain.join(" -> ")),
+            ));
+        }
+        let dirs = file
+            .parent()
+            .into_iter()
+            .chain(include_dirs.iter().map(|d| d.as_path()))
+            .map(|d| d.display().to_string())
+            .collect::<Vec<_>>();
+        ret.push(Diagnostic::new(
+            Severity::Note,
+            format!("searched: {}", dirs.join(", ")),
+        ));
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = Path::new("./target/include_test").join(name);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        for (name, data) in files {
+            std::fs::create_dir_all(dir.join(name).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(name), data).unwrap();
+        }
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn embedded_headers() {
+        assert!(embedded(&EMBEDDED_DIR.join("hlsl_box.hlsli")).is_some());
+        assert!(embedded(&EMBEDDED_DIR.join("constants.hlsli")).is_some());
+        assert!(embedded(&EMBEDDED_DIR.join("unknown.hlsli")).is_none());
+        assert!(embedded(Path::new("hlsl_box.hlsli")).is_none());
+    }
+
+    #[test]
+    fn include_search_order() {
+        let dir = create_files(
+            "search_order",
+            &[
+                ("a.hlsli", ""),
+                ("first/a.hlsli", ""),
+                ("first/b.hlsli", ""),
+                ("second/b.hlsli", ""),
+                ("second/c.hlsli", ""),
+            ],
+        );
+        let args = create_args(
+            "main",
+            Target::PS(ShaderModel::specify("6_0").unwrap()),
+            &[dir.join("first"), dir.join("second")],
+            &["-Ithird".to_string()],
+        );
+        let include_dirs = include_dirs(&args);
+        assert!(
+            include_dirs
+                == [
+                    dir.join("first"),
+                    dir.join("second"),
+                    EMBEDDED_DIR.clone(),
+                    PathBuf::from("third")
+                ]
+        );
+        let resolve = |name| resolve(name, Some(&dir), &include_dirs);
+        assert!(resolve("a.hlsli") == Some(dir.join("a.hlsli")));
+        assert!(resolve("b.hlsli") == Some(dir.join("first/b.hlsli")));
+        assert!(resolve("c.hlsli") == Some(dir.join("second/c.hlsli")));
+        assert!(resolve("d.hlsli").is_none());
+    }
+
+    #[test]
+    fn scan_relative_includes() {
+        let dir = create_files(
+            "scan",
+            &[
+                ("main.hlsl", "#include \"sub/a.hlsli\"\n"),
+                ("sub/a.hlsli", "#include \"b.hlsli\"\n"),
+                ("sub/b.hlsli", ""),
+            ],
+        );
+        let data = std::fs::read_to_string(dir.join("main.hlsl")).unwrap();
+        let mut dependencies = vec![];
+        scan_includes(&data, Some(&dir), &[], &mut dependencies);
+        assert!(dependencies == [dir.join("sub/a.hlsli"), dir.join("sub/b.hlsli")]);
+    }
+
+    #[test]
+    fn scan_utf16_includes() {
+        let dir = create_files("utf16", &[("main.hlsl", "#include \"a.hlsli\"\n")]);
+        let a = [0xff, 0xfe]
+            .into_iter()
+            .chain(
+                "#include \"b.hlsli\"\n"
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes),
+            )
+            .collect::<Vec<u8>>();
+        std::fs::write(dir.join("a.hlsli"), a).unwrap();
+        std::fs::write(dir.join("b.hlsli"), "").unwrap();
+        let mut dependencies = vec![];
+        scan_includes("#include \"a.hlsli\"\n", Some(&dir), &[], &mut dependencies);
+        assert!(dependencies == [dir.join("a.hlsli"), dir.join("b.hlsli")]);
+    }
+
+    #[test]
+    fn missing_include_chain() {
+        let dir = create_files(
+            "chain",
+            &[
+                ("main.hlsl", "#include \"sub/a.hlsli\"\n"),
+                ("sub/a.hlsli", "#include \"missing.hlsli\"\n"),
+            ],
+        );
+        let path = dir.join("main.hlsl");
+        let data = std::fs::read_to_string(&path).unwrap();
+        let diagnostics = vec![Diagnostic {
+            file: Some(dir.join("sub/a.hlsli")),
+            line: 1,
+            column: 10,
+            severity: Severity::Error,
+            message: "'missing.hlsli' file not found".to_string(),
+            snippet: vec![],
+        }];
+        let include_dir = dir.join("include");
+        let diagnostics = annotate_missing_includes(
+            diagnostics,
+            &data,
+            Some(&path),
+            std::slice::from_ref(&include_dir),
+        );
+        assert!(diagnostics.len() == 3);
+        assert!(
+            diagnostics[1].message
+                == format!(
+                    "include chain: {} -> {} -> 'missing.hlsli'",
+                    path.display(),
+                    dir.join("sub/a.hlsli").display()
+                )
+        );
+        assert!(
+            diagnostics[2].message
+                == format!(
+                    "searched: {}, {}",
+                    dir.join("sub").display(),
+                    include_dir.display()
+                )
+        );
+    }
+}
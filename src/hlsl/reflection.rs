@@ -0,0 +1,393 @@
+use super::*;
+use windows::core::{Interface, PCSTR};
+use windows::Win32::Graphics::{Direct3D::Dxc::*, Direct3D::*};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BindingKind {
+    ConstantBuffer,
+    Texture,
+    Buffer,
+    Sampler,
+    UnorderedAccess,
+}
+
+impl BindingKind {
+    fn new(ty: D3D_SHADER_INPUT_TYPE) -> Self {
+        match ty {
+            D3D_SIT_CBUFFER => Self::ConstantBuffer,
+            D3D_SIT_TEXTURE => Self::Texture,
+            D3D_SIT_SAMPLER => Self::Sampler,
+            D3D_SIT_UAV_RWTYPED
+            | D3D_SIT_UAV_RWSTRUCTURED
+            | D3D_SIT_UAV_RWBYTEADDRESS
+            | D3D_SIT_UAV_APPEND_STRUCTURED
+            | D3D_SIT_UAV_CONSUME_STRUCTURED
+            | D3D_SIT_UAV_RWSTRUCTURED_WITH_COUNTER
+            | D3D_SIT_UAV_FEEDBACKTEXTURE => Self::UnorderedAccess,
+            _ => Self::Buffer,
+        }
+    }
+
+    fn register_type(&self) -> char {
+        match self {
+            Self::ConstantBuffer => 'b',
+            Self::Texture | Self::Buffer => 't',
+            Self::Sampler => 's',
+            Self::UnorderedAccess => 'u',
+        }
+    }
+}
+
+impl std::fmt::Display for BindingKind {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::ConstantBuffer => write!(fmt, "constant buffer"),
+            Self::Texture => write!(fmt, "texture"),
+            Self::Buffer => write!(fmt, "buffer"),
+            Self::Sampler => write!(fmt, "sampler"),
+            Self::UnorderedAccess => write!(fmt, "UAV"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Binding {
+    pub name: String,
+    pub kind: BindingKind,
+    pub register: u32,
+    pub count: u32,
+    pub space: u32,
+}
+
+impl std::fmt::Display for Binding {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            fmt,
+            "{} '{}' ({}{}, space{})",
+            self.kind,
+            self.name,
+            self.kind.register_type(),
+            self.register,
+            self.space
+        )
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Variable {
+    pub name: String,
+    pub type_name: String,
+    pub offset: u32,
+    pub size: u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ConstantBuffer {
+    pub name: String,
+    pub size: u32,
+    pub variables: Vec<Variable>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Reflection {
+    pub bindings: Vec<Binding>,
+    pub constant_buffers: Vec<ConstantBuffer>,
+    pub thread_group_size: Option<[u32; 3]>,
+}
+
+impl Reflection {
+    pub(super) fn new(utils: &IDxcUtils, blob: &Blob) -> Result<Self, Error> {
+        unsafe {
+            let buffer = DxcBuffer {
+                Ptr: blob.0.as_ptr() as _,
+                Size: blob.0.len(),
+                Encoding: 0,
+            };
+            let reflection = {
+                let mut reflection: Option<ID3D12ShaderReflection> = None;
+                utils
+                    .CreateReflection(
+                        &buffer,
+                        &ID3D12ShaderReflection::IID,
+                        &mut reflection as *mut _ as _,
+                    )
+                    .map(|_| reflection.unwrap())?
+            };
+            let desc = reflection.GetDesc()?;
+            let mut bindings = Vec::with_capacity(desc.BoundResources as _);
+            for i in 0..desc.BoundResources {
+                let desc = reflection.GetResourceBindingDesc(i)?;
+                bindings.push(Binding {
+                    name: to_string(desc.Name),
+                    kind: BindingKind::new(desc.Type),
+                    register: desc.BindPoint,
+                    count: desc.BindCount,
+                    space: desc.Space,
+                });
+            }
+            let mut constant_buffers = Vec::with_capacity(desc.ConstantBuffers as _);
+            for i in 0..desc.ConstantBuffers {
+                let cb = match reflection.GetConstantBufferByIndex(i) {
+                    Some(cb) => cb,
+                    None => continue,
+                };
+                let mut desc = D3D12_SHADER_BUFFER_DESC::default();
+                cb.GetDesc(&mut desc)?;
+                let mut variables = vec![];
+                for j in 0..desc.Variables {
+                    let var = match cb.GetVariableByIndex(j) {
+                        Some(var) => var,
+                        None => continue,
+                    };
+                    let var_desc = var.GetDesc()?;
+                    let name = to_string(var_desc.Name);
+                    match var.GetType() {
+                        Some(ty) => {
+                            push_variables(&ty, name, var_desc.StartOffset, &mut variables)?;
+                        }
+                        None => variables.push(Variable {
+                            name,
+                            type_name: String::new(),
+                            offset: var_desc.StartOffset,
+                            size: var_desc.Size,
+                        }),
+                    }
+                }
+                constant_buffers.push(ConstantBuffer {
+                    name: to_string(desc.Name),
+                    size: desc.Size,
+                    variables,
+                });
+            }
+            let mut thread_group_size = [0; 3];
+            let total = reflection.GetThreadGroupSize(
+                &mut thread_group_size[0],
+                &mut thread_group_size[1],
+                &mut thread_group_size[2],
+            );
+            Ok(Self {
+                bindings,
+                constant_buffers,
+                thread_group_size: (total > 0).then_some(thread_group_size),
+            })
+        }
+    }
+
+    pub fn constant_buffer(&self, name: &str) -> Option<&ConstantBuffer> {
+        self.constant_buffers.iter().find(|cb| cb.name == name)
+    }
+}
+
+unsafe fn to_string(s: PCSTR) -> String {
+    if s.is_null() {
+        return String::new();
+    }
+    std::ffi::CStr::from_ptr(s.0 as _)
+        .to_string_lossy()
+        .to_string()
+}
+
+fn align16(n: u32) -> u32 {
+    (n + 15) & !15
+}
+
+unsafe fn type_size(ty: &ID3D12ShaderReflectionType) -> Result<u32, Error> {
+    let desc = ty.GetDesc()?;
+    let component = match desc.Type {
+        D3D_SVT_DOUBLE | D3D_SVT_INT64 | D3D_SVT_UINT64 => 8,
+        D3D_SVT_MIN16FLOAT | D3D_SVT_MIN16INT | D3D_SVT_MIN16UINT => 4,
+        D3D_SVT_FLOAT16 | D3D_SVT_INT16 | D3D_SVT_UINT16 => 2,
+        _ => 4,
+    };
+    let size = match desc.Class {
+        D3D_SVC_MATRIX_ROWS => (desc.Rows - 1) * 16 + desc.Columns * component,
+        D3D_SVC_MATRIX_COLUMNS => (desc.Columns - 1) * 16 + desc.Rows * component,
+        D3D_SVC_STRUCT => {
+            let mut size = 0;
+            for i in 0..desc.Members {
+                if let Some(member) = ty.GetMemberTypeByIndex(i) {
+                    let offset = member.GetDesc()?.Offset;
+                    size = size.max(offset + type_size(&member)?);
+                }
+            }
+            size
+        }
+        _ => desc.Columns * component,
+    };
+    if desc.Elements > 0 {
+        Ok(align16(size) * (desc.Elements - 1) + size)
+    } else {
+        Ok(size)
+    }
+}
+
+unsafe fn push_variables(
+    ty: &ID3D12ShaderReflectionType,
+    name: String,
+    offset: u32,
+    variables: &mut Vec<Variable>,
+) -> Result<(), Error> {
+    let desc = ty.GetDesc()?;
+    if desc.Class == D3D_SVC_STRUCT && desc.Elements == 0 {
+        for i in 0..desc.Members {
+            let member = match ty.GetMemberTypeByIndex(i) {
+                Some(member) => member,
+                None => continue,
+            };
+            let member_name = ty.GetMemberTypeName(i);
+            let member_name = to_string(PCSTR(member_name.0));
+            let member_offset = member.GetDesc()?.Offset;
+            push_variables(
+                &member,
+                format!("{}.{}", name, member_name),
+                offset + member_offset,
+                variables,
+            )?;
+        }
+        return Ok(());
+    }
+    let mut type_name = to_string(desc.Name);
+    if desc.Elements > 0 {
+        type_name.push_str(&format!("[{}]", desc.Elements));
+    }
+    variables.push(Variable {
+        name,
+        type_name,
+        offset,
+        size: type_size(ty)?,
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reflect_bindings() {
+        let compiler = Compiler::new().unwrap();
+        let version = ShaderModel::specify("6_0").unwrap();
+        let blob = compiler
+            .compile_from_str(
+                "\
+#include \"hlsl_box.hlsli\"
+Texture2D<float4> tex: register(t0);
+SamplerState samp: register(s1, space2);
+float4 main(Input input): SV_Target {
+    return tex.Sample(samp, input.coord / HLSLBox.resolution) * HLSLBox.time;
+}
+",
+                "main",
+                Target::PS(version),
+                &[],
+            )
+            .unwrap();
+        let reflection = compiler.reflect(&blob).unwrap();
+        let binding = |name: &str| {
+            reflection
+                .bindings
+                .iter()
+                .find(|b| b.name == name)
+                .unwrap()
+                .to_string()
+        };
+        assert!(binding("HLSLBox") == "constant buffer 'HLSLBox' (b0, space0)");
+        assert!(binding("tex") == "texture 'tex' (t0, space0)");
+        assert!(binding("samp") == "sampler 'samp' (s1, space2)");
+        let variables = reflection
+            .constant_buffer("HLSLBox")
+            .unwrap()
+            .variables
+            .iter()
+            .map(|v| (v.name.as_str(), v.type_name.as_str(), v.offset, v.size))
+            .collect::<Vec<_>>();
+        assert!(
+            variables[..3]
+                == [
+                    ("HLSLBox.resolution", "float2", 0, 8),
+                    ("HLSLBox.mouse", "float2", 8, 8),
+                    ("HLSLBox.time", "float", 16, 4),
+                ]
+        );
+    }
+
+    #[test]
+    fn reflect_thread_group_size() {
+        let compiler = Compiler::new().unwrap();
+        let version = ShaderModel::specify("6_0").unwrap();
+        let blob = compiler
+            .compile_from_str(
+                "\
+#include \"hlsl_box.hlsli\"
+[numthreads(8, 4, 1)]
+void main(uint3 id: SV_DispatchThreadID) {
+    HLSLBoxOutput[id.xy] = float4(id.xy / HLSLBox.resolution, 0.0, 1.0);
+}
+",
+                "main",
+                Target::CS(version),
+                &[],
+            )
+            .unwrap();
+        let reflection = compiler.reflect(&blob).unwrap();
+        assert!(reflection.thread_group_size == Some([8, 4, 1]));
+        assert!(reflection
+            .bindings
+            .iter()
+            .any(|b| b.to_string() == "UAV 'HLSLBoxOutput' (u0, space0)"));
+    }
+
+    #[test]
+    fn parameters_layout() {
+        let compiler = Compiler::new().unwrap();
+        let shader_model = ShaderModel::specify("6_0").unwrap();
+        let blob = compiler
+            .compile_from_str(
+                "\
+#include \"hlsl_box.hlsli\"
+float4 main(Input input): SV_Target {
+    return HLSLBox.time;
+}
+",
+                "main",
+                Target::PS(shader_model),
+                &[],
+            )
+            .unwrap();
+        let reflection = compiler.reflect(&blob).unwrap();
+        let variables = reflection
+            .constant_buffer("HLSLBox")
+            .unwrap()
+            .variables
+            .iter()
+            .map(|v| (v.name.as_str(), v.offset, v.size))
+            .collect::<Vec<_>>();
+        let p = crate::renderer::pixel_shader::Parameters::default();
+        let base = &p as *const _ as usize;
+        macro_rules! field {
+            ($name:ident) => {
+                (
+                    concat!("HLSLBox.", stringify!($name)),
+                    (std::ptr::addr_of!(p.$name) as usize - base) as u32,
+                    std::mem::size_of_val(&p.$name) as u32,
+                )
+            };
+        }
+        assert!(
+            variables
+                == [
+                    field!(resolution),
+                    field!(mouse),
+                    field!(time),
+                    field!(time_delta),
+                    field!(frame),
+                    field!(mouse_buttons),
+                    field!(date),
+                    field!(click),
+                    field!(model),
+                    field!(view),
+                    field!(projection),
+                ]
+        );
+    }
+}
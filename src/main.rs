@@ -1,38 +1,89 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+#[cfg(windows)]
 mod application;
+#[cfg(any(windows, test))]
 mod error;
+#[cfg(any(windows, test))]
 mod hlsl;
+#[cfg(windows)]
 mod messages;
+#[cfg(any(windows, test))]
 mod monitor;
+#[cfg(windows)]
+mod project;
+#[cfg(any(windows, test))]
+mod reload;
+#[cfg(windows)]
 mod renderer;
+#[cfg(windows)]
 mod settings;
+#[cfg(windows)]
 mod window;
 
-use once_cell::sync::Lazy;
-use std::cell::RefCell;
-use std::rc::Rc;
-use std::sync::Arc;
-use tracing::{debug, error, info, warn};
-use windows::Win32::{Globalization::*, System::Com::*};
-
-use application::{Application, Method};
-use error::Error;
-use messages::MESSAGES;
-use monitor::*;
-use renderer::*;
-use settings::Settings;
-use window::*;
+#[cfg(any(windows, test))]
+use {
+    error::Error,
+    once_cell::sync::Lazy,
+    std::cell::RefCell,
+    tracing::{debug, warn},
+};
+
+#[cfg(windows)]
+use {
+    application::{Application, Method},
+    messages::MESSAGES,
+    reload::*,
+    renderer::*,
+    settings::Settings,
+    std::{rc::Rc, sync::Arc},
+    tracing::{error, info},
+    window::*,
+    windows::Win32::{Globalization::*, System::Com::*},
+};
 
 const TITLE: &str = "HLSL Box";
 
+#[cfg(windows)]
 static LOCALE: Lazy<Option<String>> = Lazy::new(|| unsafe {
     let mut buffer = vec![0u16; 85];
     let size = GetUserDefaultLocaleName(&mut buffer) as usize;
     (size != 0).then(|| String::from_utf16_lossy(&buffer[0..size - 1]))
 });
 
+#[cfg(all(not(windows), test))]
+static LOCALE: Lazy<Option<String>> = Lazy::new(|| {
+    let lang = std::env::var("LANG").ok()?;
+    let name = lang.split('.').next()?;
+    (!name.is_empty()).then(|| name.replace('_', "-"))
+});
+
+#[cfg(windows)]
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    Flatten {
+        input_file: String,
+        #[clap(short, long)]
+        output: Option<String>,
+    },
+    Render {
+        input_file: String,
+        #[clap(short, long)]
+        output: String,
+        #[clap(long)]
+        resolution: Option<settings::Resolution>,
+        #[clap(long, default_value = "0")]
+        start: f32,
+        #[clap(long)]
+        end: Option<f32>,
+        #[clap(long)]
+        frame_rate: Option<u32>,
+    },
+}
+
+#[cfg(windows)]
 #[derive(Debug, clap::Parser)]
+#[clap(args_conflicts_with_subcommands = true)]
 struct EnvArgs {
     #[clap(long)]
     debuglayer: bool,
@@ -40,14 +91,20 @@ struct EnvArgs {
     nomodal: bool,
     #[clap(long)]
     debug_error_msg: bool,
+    #[clap(long)]
+    clear_cache: bool,
     input_file: Option<String>,
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
+#[cfg(windows)]
 static ENV_ARGS: Lazy<EnvArgs> = Lazy::new(|| {
     use clap::Parser;
     EnvArgs::parse()
 });
 
+#[cfg(any(windows, test))]
 static EXE_DIR_PATH: Lazy<std::path::PathBuf> = Lazy::new(|| {
     std::env::current_exe()
         .unwrap()
@@ -58,12 +115,19 @@ static EXE_DIR_PATH: Lazy<std::path::PathBuf> = Lazy::new(|| {
         .to_path_buf()
 });
 
+#[cfg(windows)]
 static SETTINGS_PATH: Lazy<std::path::PathBuf> = Lazy::new(|| EXE_DIR_PATH.join("settings.toml"));
+#[cfg(windows)]
 static WINDOW_SETTING_PATH: Lazy<std::path::PathBuf> =
     Lazy::new(|| EXE_DIR_PATH.join("window.toml"));
+#[cfg(windows)]
 static SCREEN_SHOT_PATH: Lazy<std::path::PathBuf> = Lazy::new(|| EXE_DIR_PATH.join("screenshot"));
+#[cfg(windows)]
 static VIDEO_PATH: Lazy<std::path::PathBuf> = Lazy::new(|| EXE_DIR_PATH.join("video"));
+#[cfg(windows)]
+static CACHE_PATH: Lazy<std::path::PathBuf> = Lazy::new(|| EXE_DIR_PATH.join("cache"));
 
+#[cfg(windows)]
 fn set_logger() {
     use std::fs::File;
     use tracing_subscriber::{filter::LevelFilter, prelude::*};
@@ -91,6 +155,7 @@ fn set_logger() {
         .init();
 }
 
+#[cfg(windows)]
 fn panic_handler(info: &std::panic::PanicInfo) {
     use windows::Win32::Foundation::HWND;
     use windows::Win32::UI::WindowsAndMessaging::*;
@@ -127,6 +192,7 @@ fn panic_handler(info: &std::panic::PanicInfo) {
     };
 }
 
+#[cfg(windows)]
 fn set_locale() {
     unsafe {
         let locale = std::ffi::CString::new(LOCALE.as_ref().map_or("", |l| l.as_str())).unwrap();
@@ -134,6 +200,26 @@ fn set_locale() {
     }
 }
 
+#[cfg(windows)]
+fn create_runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .worker_threads(3)
+        .on_thread_start(|| unsafe {
+            CoInitializeEx(
+                std::ptr::null(),
+                COINIT_MULTITHREADED | COINIT_DISABLE_OLE1DDE,
+            )
+            .unwrap();
+        })
+        .on_thread_stop(|| unsafe {
+            CoUninitialize();
+        })
+        .build()
+        .unwrap()
+}
+
+#[cfg(windows)]
 fn main() {
     set_logger();
     let default_handler = std::panic::take_hook();
@@ -144,33 +230,59 @@ fn main() {
     info!("start");
     debug!("ENV_ARGS: {:?}", &*ENV_ARGS);
     set_locale();
+    if ENV_ARGS.clear_cache {
+        match hlsl::Cache::clear(&*CACHE_PATH) {
+            Ok(_) => info!("clear cache"),
+            Err(e) => error!("clear cache: {}", e),
+        }
+    }
     let _coinit = coinit::init(coinit::APARTMENTTHREADED | coinit::DISABLE_OLE1DDE).unwrap();
+    if let Some(Command::Flatten { input_file, output }) = &ENV_ARGS.command {
+        let ret = application::flatten(
+            std::path::Path::new(input_file),
+            output.as_ref().map(std::path::Path::new),
+        );
+        if let Err(e) = ret {
+            error!("flatten: {}", e);
+            std::process::exit(1);
+        }
+        info!("end");
+        return;
+    }
+    if let Some(Command::Render {
+        input_file,
+        output,
+        resolution,
+        start,
+        end,
+        frame_rate,
+    }) = &ENV_ARGS.command
+    {
+        let options = application::RenderOptions {
+            input_file: input_file.into(),
+            output: output.into(),
+            resolution: *resolution,
+            start: *start,
+            end: *end,
+            frame_rate: *frame_rate,
+        };
+        if let Err(e) = create_runtime().block_on(application::render(&options)) {
+            error!("render: {}", e);
+            std::process::exit(1);
+        }
+        info!("end");
+        return;
+    }
     let th_handle = Rc::new(RefCell::new(None));
     let th_handle_f = th_handle.clone();
     let f = move || -> Result<WindowHandler, Error> {
         let settings = Settings::load(&*SETTINGS_PATH);
         debug!("settings: {:?}", settings);
         let window_setting = settings::Window::load(&*WINDOW_SETTING_PATH)?;
-        let mut key_map = KeyboardMap::new();
-        key_map.insert(
-            vec![wita::VirtualKey::Ctrl, wita::VirtualKey::Char('O')],
-            Method::OpenDialog,
-        );
-        key_map.insert(
-            vec![wita::VirtualKey::Ctrl, wita::VirtualKey::Char('F')],
-            Method::FrameCounter,
-        );
-        key_map.insert(vec![wita::VirtualKey::PrintScreen], Method::ScreenShot);
-        key_map.insert(vec![wita::VirtualKey::Space], Method::Play);
-        key_map.insert(vec![wita::VirtualKey::Char('R')], Method::Head);
-        key_map.insert(
-            vec![wita::VirtualKey::Ctrl, wita::VirtualKey::Char('V')],
-            Method::RecordVideo,
-        );
-        key_map.insert(
-            vec![wita::VirtualKey::Ctrl, wita::VirtualKey::Char('Q')],
-            Method::Exit,
-        );
+        let key_map = match settings.as_ref() {
+            Ok(settings) => KeyboardMap::from_shortcuts(&settings.shortcuts),
+            Err(_) => KeyboardMap::from_shortcuts(&settings::Shortcuts::default()),
+        };
         let (window, window_manager) = WindowHandler::new(&settings, &window_setting, key_map);
         let th_settings = settings;
         let th = std::thread::spawn(move || {
@@ -182,25 +294,10 @@ fn main() {
                 handler(info);
                 main_window.close();
             }));
-            tokio::runtime::Builder::new_multi_thread()
-                .enable_all()
-                .worker_threads(3)
-                .on_thread_start(|| unsafe {
-                    CoInitializeEx(
-                        std::ptr::null(),
-                        COINIT_MULTITHREADED | COINIT_DISABLE_OLE1DDE,
-                    )
-                    .unwrap();
-                })
-                .on_thread_stop(|| unsafe {
-                    CoUninitialize();
-                })
-                .build()
-                .unwrap()
-                .block_on(async {
-                    let mut app = Application::new(th_settings, window_manager).await.unwrap();
-                    app.run().await.unwrap();
-                });
+            create_runtime().block_on(async {
+                let mut app = Application::new(th_settings, window_manager).await.unwrap();
+                app.run().await.unwrap();
+            });
             info!("end rendering thread");
         });
         *th_handle_f.borrow_mut() = Some(th);
@@ -212,3 +309,9 @@ fn main() {
     th_handle.borrow_mut().take().unwrap().join().unwrap();
     info!("end");
 }
+
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("{} supports only Windows", TITLE);
+    std::process::exit(1);
+}
@@ -4,6 +4,12 @@ pub struct Messages {
     pub screen_shot: &'static str,
     pub record_video_start: &'static str,
     pub record_video_end: &'static str,
+    pub compiling: &'static str,
+    pub flatten: &'static str,
+    pub flatten_error: &'static str,
+    pub save_params: &'static str,
+    pub save_params_error: &'static str,
+    pub preset: &'static str,
 }
 
 impl Messages {
@@ -13,11 +19,23 @@ impl Messages {
                 screen_shot: "スクリーンショットを撮影",
                 record_video_start: "録画を開始",
                 record_video_end: "録画を終了",
+                compiling: "コンパイル中…",
+                flatten: "1つのファイルにまとめて保存",
+                flatten_error: "1つのファイルにまとめられません",
+                save_params: "パラメータを保存",
+                save_params_error: "パラメータを保存できません",
+                preset: "プリセット",
             },
             _ => Self {
                 screen_shot: "take the screenshot",
                 record_video_start: "start recoding",
                 record_video_end: "end recoding",
+                compiling: "compiling…",
+                flatten: "save the flattened file",
+                flatten_error: "cannot flatten the file",
+                save_params: "save the parameters",
+                save_params_error: "cannot save the parameters",
+                preset: "preset",
             },
         }
     }
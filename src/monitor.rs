@@ -1,39 +1,107 @@
-use crate::*;
+#[cfg(target_os = "linux")]
+mod inotify;
+mod polling;
+#[cfg(windows)]
+mod win32;
+
 use std::{
-    os::windows::io::AsRawHandle,
     path::{Path, PathBuf},
-    sync::{
-        atomic::{self, AtomicBool},
-        mpsc,
-    },
+    sync::mpsc,
+    time::{Duration, Instant},
 };
-use windows::Win32::{Foundation::*, Storage::FileSystem::*, System::IO::*};
+use tracing::{debug, error, warn};
+
+#[cfg(target_os = "linux")]
+use inotify::Watcher as NativeWatcher;
+#[cfg(windows)]
+use win32::Watcher as NativeWatcher;
+
+/// Stands in where there is no native watcher, so that `DirMonitor` falls back to polling.
+#[cfg(not(any(target_os = "linux", windows)))]
+struct NativeWatcher;
+
+#[cfg(not(any(target_os = "linux", windows)))]
+impl NativeWatcher {
+    fn new(
+        _path: PathBuf,
+        _interval: Duration,
+        _tx: mpsc::Sender<FileEvent>,
+    ) -> std::io::Result<Self> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "no native file watcher",
+        ))
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FileEvent {
+    Modified(PathBuf),
+    Created(PathBuf),
+    Renamed(PathBuf),
+    Removed(PathBuf),
+}
+
+impl FileEvent {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Modified(path) => path,
+            Self::Created(path) => path,
+            Self::Renamed(path) => path,
+            Self::Removed(path) => path,
+        }
+    }
+
+    pub fn is_written(&self) -> bool {
+        !matches!(self, Self::Removed(_))
+    }
+}
+
+fn rescan(dir_path: &Path, tx: &mpsc::Sender<FileEvent>) -> std::io::Result<()> {
+    warn!("lost file events, rescan: {}", dir_path.display());
+    for entry in std::fs::read_dir(dir_path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            tx.send(FileEvent::Modified(entry.path())).ok();
+        }
+    }
+    Ok(())
+}
+
+// The watchers are kept only to stop watching when they are dropped.
+enum Watcher {
+    Native { _watcher: NativeWatcher },
+    Polling { _watcher: polling::Watcher },
+}
 
 pub struct DirMonitor {
-    th: Option<std::thread::JoinHandle<()>>,
-    exit: Arc<AtomicBool>,
-    rx: mpsc::Receiver<PathBuf>,
+    _watcher: Watcher,
+    rx: mpsc::Receiver<FileEvent>,
     path: PathBuf,
 }
 
 impl DirMonitor {
-    pub fn new(path: impl AsRef<Path>) -> Result<Self, Error> {
+    pub fn new(path: impl AsRef<Path>, polling: bool, interval: Duration) -> std::io::Result<Self> {
         let (tx, rx) = mpsc::channel();
-        let exit_flag = Arc::new(AtomicBool::new(false));
-        let exit = exit_flag.clone();
         let path = path.as_ref().to_path_buf();
-        let th_path = path.clone();
-        let th = std::thread::spawn(move || unsafe {
-            let p = th_path.clone();
-            debug!("start monitoring directory: {}", p.display());
-            if let Err(e) = Self::read_directory(th_path, exit_flag, tx) {
-                error!("read_directory: {}", e);
+        let watcher = if polling {
+            Watcher::Polling {
+                _watcher: polling::Watcher::new(path.clone(), interval, tx)?,
             }
-            debug!("end monitoring directory: {}", p.display());
-        });
+        } else {
+            match NativeWatcher::new(path.clone(), interval, tx.clone()) {
+                Ok(watcher) => Watcher::Native { _watcher: watcher },
+                Err(e) => {
+                    error!("DirMonitor::new: {}: {}", path.display(), e);
+                    warn!("fall back to polling: {}", path.display());
+                    Watcher::Polling {
+                        _watcher: polling::Watcher::new(path.clone(), interval, tx)?,
+                    }
+                }
+            }
+        };
         Ok(Self {
-            th: Some(th),
-            exit,
+            _watcher: watcher,
             rx,
             path,
         })
@@ -43,76 +111,75 @@ impl DirMonitor {
         &self.path
     }
 
-    pub fn try_recv(&self) -> Option<PathBuf> {
+    pub fn try_recv(&self) -> Option<FileEvent> {
         self.rx.try_recv().ok()
     }
+}
 
-    unsafe fn read_directory(
-        dir_path: PathBuf,
-        exit: Arc<AtomicBool>,
-        tx: mpsc::Sender<PathBuf>,
-    ) -> Result<(), Error> {
-        assert!(dir_path.is_dir());
-        let path = dir_path.to_string_lossy().to_string();
-        let dir = CreateFileW(
-            path.as_str(),
-            FILE_LIST_DIRECTORY,
-            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
-            std::ptr::null(),
-            OPEN_EXISTING,
-            FILE_FLAG_BACKUP_SEMANTICS,
-            HANDLE::default(),
-        )?;
-        let mut buffer = vec![0u8; 2048];
-        loop {
-            if exit.load(atomic::Ordering::SeqCst) {
-                break;
-            }
-            let mut len = 0;
-            let ret = ReadDirectoryChangesW(
-                dir,
-                buffer.as_mut_ptr() as _,
-                buffer.len() as _,
-                false,
-                FILE_NOTIFY_CHANGE_LAST_WRITE,
-                &mut len,
-                std::ptr::null_mut(),
-                None,
-            );
-            if !ret.as_bool() {
-                break;
-            }
-            let mut p = buffer.as_ptr() as *const u8;
-            loop {
-                let data = (p as *const FILE_NOTIFY_INFORMATION).as_ref().unwrap();
-                if data.Action == FILE_ACTION_MODIFIED {
-                    let file_name = std::slice::from_raw_parts(
-                        data.FileName.as_ptr() as *const u16,
-                        data.FileNameLength as usize / std::mem::size_of::<u16>(),
-                    );
-                    let file_name = PathBuf::from(String::from_utf16_lossy(file_name));
-                    tx.send(dir_path.join(file_name)).ok();
-                }
-                if data.NextEntryOffset == 0 {
-                    break;
-                }
-                p = p.offset(data.NextEntryOffset as _);
-            }
+struct Pending {
+    event: FileEvent,
+    time: Instant,
+    retried: bool,
+}
+
+pub struct Debouncer {
+    monitor: DirMonitor,
+    delay: Duration,
+    pending: Vec<Pending>,
+}
+
+impl Debouncer {
+    pub fn new(monitor: DirMonitor, delay: Duration) -> Self {
+        Self {
+            monitor,
+            delay,
+            pending: Vec::new(),
         }
-        CloseHandle(dir);
-        Ok(())
     }
-}
 
-impl Drop for DirMonitor {
-    fn drop(&mut self) {
-        if let Some(th) = self.th.take() {
-            self.exit.store(true, atomic::Ordering::SeqCst);
-            unsafe {
-                CancelSynchronousIo(HANDLE(th.as_raw_handle() as _));
+    pub fn path(&self) -> &Path {
+        self.monitor.path()
+    }
+
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay;
+    }
+
+    pub fn try_recv(&mut self) -> Option<FileEvent> {
+        while let Some(event) = self.monitor.try_recv() {
+            let time = Instant::now();
+            match self
+                .pending
+                .iter_mut()
+                .find(|p| p.event.path() == event.path())
+            {
+                Some(p) => {
+                    p.event = event;
+                    p.time = time;
+                }
+                None => self.pending.push(Pending {
+                    event,
+                    time,
+                    retried: false,
+                }),
             }
-            th.join().ok();
         }
+        let now = Instant::now();
+        let index = self
+            .pending
+            .iter()
+            .position(|p| now - p.time >= self.delay)?;
+        let p = self.pending.remove(index);
+        if p.event.is_written() && !p.retried && std::fs::File::open(p.event.path()).is_err() {
+            debug!("retry: {}", p.event.path().display());
+            self.pending.push(Pending {
+                time: now,
+                retried: true,
+                ..p
+            });
+            return None;
+        }
+        Some(p.event)
     }
 }
 
@@ -120,6 +187,8 @@ impl Drop for DirMonitor {
 mod tests {
     use super::*;
 
+    const INTERVAL: Duration = Duration::from_millis(500);
+
     #[test]
     fn dir_monitor() {
         let dir_path = Path::new("./target/dir_monitor_test");
@@ -130,8 +199,7 @@ mod tests {
                 _ => Err(e).unwrap(),
             }
         }
-        let dm = DirMonitor::new(&dir_path).unwrap();
-        std::thread::sleep(std::time::Duration::from_millis(100));
+        let dm = DirMonitor::new(&dir_path, false, INTERVAL).unwrap();
         {
             use std::io::Write;
             let file = std::fs::File::create(&file_path).unwrap();
@@ -142,7 +210,133 @@ mod tests {
             dm.rx
                 .recv_timeout(std::time::Duration::from_secs(1))
                 .unwrap()
+                .path()
                 == file_path
         );
     }
+
+    #[test]
+    fn dir_monitor_rename() {
+        let dir_path = Path::new("./target/dir_monitor_rename_test");
+        let file_path = dir_path.join("test.txt");
+        let tmp_path = dir_path.join("test.txt.tmp");
+        if let Err(e) = std::fs::create_dir(dir_path) {
+            match e.kind() {
+                std::io::ErrorKind::AlreadyExists => {}
+                _ => Err(e).unwrap(),
+            }
+        }
+        std::fs::write(&file_path, "a").unwrap();
+        let dm = DirMonitor::new(&dir_path, false, INTERVAL).unwrap();
+        std::fs::write(&tmp_path, "b").unwrap();
+        std::fs::rename(&tmp_path, &file_path).unwrap();
+        let ev = loop {
+            let ev = dm
+                .rx
+                .recv_timeout(std::time::Duration::from_secs(1))
+                .unwrap();
+            if ev.path() == file_path {
+                break ev;
+            }
+        };
+        assert!(ev == FileEvent::Renamed(file_path));
+        assert!(ev.is_written());
+    }
+
+    #[test]
+    fn dir_monitor_remove() {
+        let dir_path = Path::new("./target/dir_monitor_remove_test");
+        let file_path = dir_path.join("test.txt");
+        if let Err(e) = std::fs::create_dir(dir_path) {
+            match e.kind() {
+                std::io::ErrorKind::AlreadyExists => {}
+                _ => Err(e).unwrap(),
+            }
+        }
+        std::fs::write(&file_path, "a").unwrap();
+        let dm = DirMonitor::new(&dir_path, false, INTERVAL).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+        let ev = dm
+            .rx
+            .recv_timeout(std::time::Duration::from_secs(1))
+            .unwrap();
+        assert!(ev == FileEvent::Removed(file_path));
+        assert!(!ev.is_written());
+    }
+
+    #[test]
+    fn debouncer() {
+        let dir_path = Path::new("./target/debouncer_test");
+        let file_path = dir_path.join("test.txt");
+        if let Err(e) = std::fs::create_dir(dir_path) {
+            match e.kind() {
+                std::io::ErrorKind::AlreadyExists => {}
+                _ => Err(e).unwrap(),
+            }
+        }
+        let mut debouncer = Debouncer::new(
+            DirMonitor::new(&dir_path, false, INTERVAL).unwrap(),
+            Duration::from_millis(200),
+        );
+        for i in 0..3 {
+            std::fs::write(&file_path, i.to_string()).unwrap();
+        }
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(debouncer.try_recv().is_none());
+        std::thread::sleep(Duration::from_millis(300));
+        let ev = debouncer.try_recv().unwrap();
+        assert!(ev.path() == file_path);
+        assert!(ev.is_written());
+        assert!(debouncer.try_recv().is_none());
+    }
+
+    #[test]
+    fn polling_dir_monitor() {
+        let dir_path = Path::new("./target/polling_dir_monitor_test");
+        let file_path = dir_path.join("test.txt");
+        if let Err(e) = std::fs::create_dir(dir_path) {
+            match e.kind() {
+                std::io::ErrorKind::AlreadyExists => {}
+                _ => Err(e).unwrap(),
+            }
+        }
+        std::fs::remove_file(&file_path).ok();
+        let dm = DirMonitor::new(&dir_path, true, Duration::from_millis(20)).unwrap();
+        std::fs::write(&file_path, "a").unwrap();
+        let ev = dm.rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(ev == FileEvent::Created(file_path.clone()));
+        let modified = std::fs::metadata(&file_path).unwrap().modified().unwrap();
+        std::fs::write(&file_path, "b").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&file_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let ev = dm.rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(ev == FileEvent::Modified(file_path.clone()));
+        std::fs::remove_file(&file_path).unwrap();
+        let ev = dm.rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(ev == FileEvent::Removed(file_path));
+    }
+
+    #[test]
+    fn dir_monitor_drop() {
+        let dir_path = Path::new("./target/dir_monitor_drop_test");
+        if let Err(e) = std::fs::create_dir(dir_path) {
+            match e.kind() {
+                std::io::ErrorKind::AlreadyExists => {}
+                _ => Err(e).unwrap(),
+            }
+        }
+        let (tx, rx) = mpsc::channel();
+        let th = std::thread::spawn(move || {
+            let dm = DirMonitor::new(&dir_path, false, INTERVAL).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            std::mem::drop(dm);
+            tx.send(()).unwrap();
+        });
+        assert!(rx.recv_timeout(std::time::Duration::from_secs(1)).is_ok());
+        th.join().unwrap();
+    }
 }
@@ -0,0 +1,161 @@
+use super::*;
+use std::{
+    ffi::{CStr, CString, OsStr},
+    fs::File,
+    io::Read,
+    os::unix::{
+        ffi::OsStrExt,
+        io::{AsRawFd, FromRawFd},
+    },
+};
+
+pub(super) struct Watcher {
+    th: Option<std::thread::JoinHandle<()>>,
+    exit: Option<File>,
+}
+
+impl Watcher {
+    pub fn new(
+        path: PathBuf,
+        interval: Duration,
+        tx: mpsc::Sender<FileEvent>,
+    ) -> std::io::Result<Self> {
+        let inotify = unsafe { Self::add_watch(&path)? };
+        let (exit_rx, exit_tx) = unsafe {
+            let mut fds = [0; 2];
+            if libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1]))
+        };
+        let th = std::thread::spawn(move || unsafe {
+            debug!("start monitoring directory: {}", path.display());
+            if let Err(e) = Self::read_directory(&path, inotify, &exit_rx, &tx) {
+                error!("read_directory: {}", e);
+                warn!("fall back to polling: {}", path.display());
+                let ret = polling::scan(&path).and_then(|entries| {
+                    polling::poll_directory(&path, entries, interval, &tx, |interval| {
+                        Self::wait_exit(&exit_rx, interval)
+                    })
+                });
+                if let Err(e) = ret {
+                    error!("poll_directory: {}", e);
+                }
+            }
+            debug!("end monitoring directory: {}", path.display());
+        });
+        Ok(Self {
+            th: Some(th),
+            exit: Some(exit_tx),
+        })
+    }
+
+    unsafe fn add_watch(dir_path: &Path) -> std::io::Result<File> {
+        let fd = libc::inotify_init1(libc::IN_CLOEXEC);
+        if fd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let inotify = File::from_raw_fd(fd);
+        let path = CString::new(dir_path.as_os_str().as_bytes()).unwrap();
+        let mask = libc::IN_ONLYDIR
+            | libc::IN_MODIFY
+            | libc::IN_CREATE
+            | libc::IN_MOVED_TO
+            | libc::IN_MOVED_FROM
+            | libc::IN_DELETE;
+        if libc::inotify_add_watch(fd, path.as_ptr(), mask) == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(inotify)
+    }
+
+    fn wait_exit(exit: &File, timeout: Duration) -> bool {
+        let mut fds = [libc::pollfd {
+            fd: exit.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+        let ret = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, timeout.as_millis() as _) };
+        ret == -1 && std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted
+            || fds[0].revents != 0
+    }
+
+    unsafe fn read_directory(
+        dir_path: &Path,
+        mut inotify: File,
+        exit: &File,
+        tx: &mpsc::Sender<FileEvent>,
+    ) -> std::io::Result<()> {
+        let fd = inotify.as_raw_fd();
+        let header_size = std::mem::size_of::<libc::inotify_event>();
+        let mut buffer = vec![0u8; 4096];
+        loop {
+            let mut fds = [
+                libc::pollfd {
+                    fd,
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+                libc::pollfd {
+                    fd: exit.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+            ];
+            if libc::poll(fds.as_mut_ptr(), fds.len() as _, -1) == -1 {
+                let e = std::io::Error::last_os_error();
+                if e.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+            if fds[1].revents != 0 {
+                break;
+            }
+            if fds[0].revents & libc::POLLIN == 0 {
+                continue;
+            }
+            let len = match inotify.read(&mut buffer) {
+                Ok(len) => len,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let mut p = 0;
+            while p + header_size <= len {
+                let data =
+                    std::ptr::read_unaligned(buffer.as_ptr().add(p) as *const libc::inotify_event);
+                if data.mask & libc::IN_Q_OVERFLOW != 0 {
+                    rescan(dir_path, tx)?;
+                } else if data.len > 0 {
+                    let file_name = CStr::from_ptr(buffer.as_ptr().add(p + header_size) as _);
+                    let path = dir_path.join(OsStr::from_bytes(file_name.to_bytes()));
+                    let event = if data.mask & libc::IN_MODIFY != 0 {
+                        Some(FileEvent::Modified(path))
+                    } else if data.mask & libc::IN_CREATE != 0 {
+                        Some(FileEvent::Created(path))
+                    } else if data.mask & libc::IN_MOVED_TO != 0 {
+                        Some(FileEvent::Renamed(path))
+                    } else if data.mask & (libc::IN_DELETE | libc::IN_MOVED_FROM) != 0 {
+                        Some(FileEvent::Removed(path))
+                    } else {
+                        None
+                    };
+                    if let Some(event) = event {
+                        tx.send(event).ok();
+                    }
+                }
+                p += header_size + data.len as usize;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        if let Some(th) = self.th.take() {
+            self.exit.take();
+            th.join().ok();
+        }
+    }
+}
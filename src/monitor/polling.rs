@@ -0,0 +1,141 @@
+use super::*;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::Hasher,
+    sync::{
+        atomic::{self, AtomicBool},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+const HASH_SIZE_LIMIT: u64 = 1024 * 1024;
+// Some file systems keep timestamps in 2 second units, so a write within that window may leave
+// both mtime and size unchanged. Only such recently modified files are hashed.
+const HASH_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+pub(super) struct Entry {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: Option<u64>,
+}
+
+impl Entry {
+    fn is_changed(&self, prev: &Self) -> bool {
+        self.modified != prev.modified
+            || self.len != prev.len
+            || matches!((self.hash, prev.hash), (Some(a), Some(b)) if a != b)
+    }
+}
+
+fn is_recent(modified: Option<SystemTime>) -> bool {
+    match modified.map(|modified| modified.elapsed()) {
+        Some(Ok(elapsed)) => elapsed < HASH_WINDOW,
+        _ => true,
+    }
+}
+
+fn hash_file(path: &Path, len: u64) -> Option<u64> {
+    if len > HASH_SIZE_LIMIT {
+        return None;
+    }
+    let data = std::fs::read(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    hasher.write(&data);
+    Some(hasher.finish())
+}
+
+pub(super) fn scan(dir_path: &Path) -> std::io::Result<HashMap<PathBuf, Entry>> {
+    let mut entries = HashMap::new();
+    for entry in std::fs::read_dir(dir_path)? {
+        let entry = entry?;
+        let metadata = match entry.metadata() {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => continue,
+        };
+        let path = entry.path();
+        let modified = metadata.modified().ok();
+        let len = metadata.len();
+        let hash = is_recent(modified).then(|| hash_file(&path, len)).flatten();
+        entries.insert(
+            path,
+            Entry {
+                modified,
+                len,
+                hash,
+            },
+        );
+    }
+    Ok(entries)
+}
+
+pub(super) fn poll_directory(
+    dir_path: &Path,
+    mut prev: HashMap<PathBuf, Entry>,
+    interval: Duration,
+    tx: &mpsc::Sender<FileEvent>,
+    mut wait: impl FnMut(Duration) -> bool,
+) -> std::io::Result<()> {
+    while !wait(interval) {
+        let current = match scan(dir_path) {
+            Ok(current) => current,
+            Err(e) => {
+                warn!("poll_directory: {}: {}", dir_path.display(), e);
+                continue;
+            }
+        };
+        for (path, entry) in current.iter() {
+            let event = match prev.get(path) {
+                Some(p) if !entry.is_changed(p) => continue,
+                Some(_) => FileEvent::Modified(path.clone()),
+                None => FileEvent::Created(path.clone()),
+            };
+            tx.send(event).ok();
+        }
+        for path in prev.keys().filter(|path| !current.contains_key(*path)) {
+            tx.send(FileEvent::Removed(path.clone())).ok();
+        }
+        prev = current;
+    }
+    Ok(())
+}
+
+pub(super) struct Watcher {
+    th: Option<std::thread::JoinHandle<()>>,
+    exit: Arc<AtomicBool>,
+}
+
+impl Watcher {
+    pub fn new(
+        path: PathBuf,
+        interval: Duration,
+        tx: mpsc::Sender<FileEvent>,
+    ) -> std::io::Result<Self> {
+        let entries = scan(&path)?;
+        let exit_flag = Arc::new(AtomicBool::new(false));
+        let exit = exit_flag.clone();
+        let th = std::thread::spawn(move || {
+            debug!("start polling directory: {}", path.display());
+            let ret = poll_directory(&path, entries, interval, &tx, |interval| {
+                std::thread::park_timeout(interval);
+                exit_flag.load(atomic::Ordering::SeqCst)
+            });
+            if let Err(e) = ret {
+                error!("poll_directory: {}", e);
+            }
+            debug!("end polling directory: {}", path.display());
+        });
+        Ok(Self { th: Some(th), exit })
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        if let Some(th) = self.th.take() {
+            self.exit.store(true, atomic::Ordering::SeqCst);
+            th.thread().unpark();
+            th.join().ok();
+        }
+    }
+}
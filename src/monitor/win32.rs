@@ -0,0 +1,183 @@
+use super::*;
+use windows::Win32::{
+    Foundation::*,
+    Storage::FileSystem::*,
+    System::{Threading::*, WindowsProgramming::INFINITE, IO::*},
+};
+
+const BUFFER_LEN: usize = 512;
+
+struct Directory {
+    handle: HANDLE,
+    overlapped: Box<OVERLAPPED>,
+    buffer: Vec<u32>,
+}
+
+unsafe impl Send for Directory {}
+
+impl Directory {
+    unsafe fn open(dir_path: &Path) -> std::io::Result<Self> {
+        let handle = CreateFileW(
+            dir_path.as_os_str(),
+            FILE_LIST_DIRECTORY,
+            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            std::ptr::null(),
+            OPEN_EXISTING,
+            FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OVERLAPPED,
+            HANDLE::default(),
+        )?;
+        let event = match CreateEventW(std::ptr::null(), false, false, None) {
+            Ok(event) => event,
+            Err(e) => {
+                CloseHandle(handle);
+                return Err(e.into());
+            }
+        };
+        let mut this = Self {
+            handle,
+            overlapped: Box::new(OVERLAPPED {
+                hEvent: event,
+                ..Default::default()
+            }),
+            buffer: vec![0; BUFFER_LEN],
+        };
+        this.read()?;
+        Ok(this)
+    }
+
+    unsafe fn read(&mut self) -> std::io::Result<()> {
+        let ret = ReadDirectoryChangesW(
+            self.handle,
+            self.buffer.as_mut_ptr() as _,
+            (self.buffer.len() * std::mem::size_of::<u32>()) as _,
+            false,
+            FILE_NOTIFY_CHANGE_LAST_WRITE | FILE_NOTIFY_CHANGE_FILE_NAME,
+            std::ptr::null_mut(),
+            &mut *self.overlapped,
+            None,
+        );
+        if !ret.as_bool() {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    unsafe fn result(&self) -> std::io::Result<usize> {
+        let mut len = 0;
+        if !GetOverlappedResult(self.handle, &*self.overlapped, &mut len, false).as_bool() {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(len as usize)
+    }
+}
+
+impl Drop for Directory {
+    fn drop(&mut self) {
+        unsafe {
+            if CancelIoEx(self.handle, &*self.overlapped).as_bool() {
+                let mut len = 0;
+                GetOverlappedResult(self.handle, &*self.overlapped, &mut len, true);
+            }
+            CloseHandle(self.handle);
+            CloseHandle(self.overlapped.hEvent);
+        }
+    }
+}
+
+pub(super) struct Watcher {
+    th: Option<std::thread::JoinHandle<()>>,
+    exit: HANDLE,
+}
+
+impl Watcher {
+    pub fn new(
+        path: PathBuf,
+        interval: Duration,
+        tx: mpsc::Sender<FileEvent>,
+    ) -> std::io::Result<Self> {
+        unsafe {
+            let mut dir = Directory::open(&path)?;
+            let exit = CreateEventW(std::ptr::null(), true, false, None)?;
+            let th = std::thread::spawn(move || {
+                debug!("start monitoring directory: {}", path.display());
+                if let Err(e) = Self::read_directory(&path, &mut dir, exit, &tx) {
+                    error!("read_directory: {}", e);
+                    warn!("fall back to polling: {}", path.display());
+                    let ret = polling::scan(&path).and_then(|entries| {
+                        polling::poll_directory(&path, entries, interval, &tx, |interval| {
+                            WaitForSingleObject(exit, interval.as_millis() as _) == WAIT_OBJECT_0
+                        })
+                    });
+                    if let Err(e) = ret {
+                        error!("poll_directory: {}", e);
+                    }
+                }
+                debug!("end monitoring directory: {}", path.display());
+            });
+            Ok(Self { th: Some(th), exit })
+        }
+    }
+
+    unsafe fn read_directory(
+        dir_path: &Path,
+        dir: &mut Directory,
+        exit: HANDLE,
+        tx: &mpsc::Sender<FileEvent>,
+    ) -> std::io::Result<()> {
+        loop {
+            let ret = WaitForMultipleObjects(&[dir.overlapped.hEvent, exit], false, INFINITE);
+            if ret == WAIT_OBJECT_0 + 1 {
+                break;
+            }
+            if ret != WAIT_OBJECT_0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let len = dir.result()?;
+            let buffer = dir.buffer.clone();
+            dir.read()?;
+            if len == 0 {
+                rescan(dir_path, tx)?;
+                continue;
+            }
+            let mut p = buffer.as_ptr() as *const u8;
+            let end = p.add(len);
+            while p < end {
+                let data = (p as *const FILE_NOTIFY_INFORMATION).as_ref().unwrap();
+                let file_name = std::slice::from_raw_parts(
+                    data.FileName.as_ptr() as *const u16,
+                    data.FileNameLength as usize / std::mem::size_of::<u16>(),
+                );
+                let path = dir_path.join(String::from_utf16_lossy(file_name));
+                let event = match data.Action {
+                    FILE_ACTION_MODIFIED => Some(FileEvent::Modified(path)),
+                    FILE_ACTION_ADDED => Some(FileEvent::Created(path)),
+                    FILE_ACTION_RENAMED_NEW_NAME => Some(FileEvent::Renamed(path)),
+                    FILE_ACTION_REMOVED | FILE_ACTION_RENAMED_OLD_NAME => {
+                        Some(FileEvent::Removed(path))
+                    }
+                    _ => None,
+                };
+                if let Some(event) = event {
+                    tx.send(event).ok();
+                }
+                if data.NextEntryOffset == 0 {
+                    break;
+                }
+                p = p.offset(data.NextEntryOffset as _);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(th) = self.th.take() {
+                SetEvent(self.exit);
+                th.join().ok();
+            }
+            CloseHandle(self.exit);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use crate::*;
+use std::path::{Path, PathBuf};
+
+const EXTENSION: &str = ".hlslbox.toml";
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Channel {
+    path: PathBuf,
+    #[serde(default)]
+    wrap: hlsl::Wrap,
+    #[serde(default)]
+    filter: hlsl::Filter,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Pass {
+    shader: PathBuf,
+    entry: Option<String>,
+    resolution: Option<settings::Resolution>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Video {
+    pub frame_rate: Option<u32>,
+    pub end_frame: Option<u64>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    shader: PathBuf,
+    entry: Option<String>,
+    vertex: Option<String>,
+    model: Option<String>,
+    #[serde(default)]
+    mode: hlsl::Mode,
+    mesh: Option<PathBuf>,
+    #[serde(default)]
+    defines: Vec<String>,
+    resolution: Option<settings::Resolution>,
+    channel0: Option<Channel>,
+    channel1: Option<Channel>,
+    channel2: Option<Channel>,
+    channel3: Option<Channel>,
+    pass_a: Option<Pass>,
+    pass_b: Option<Pass>,
+    pass_c: Option<Pass>,
+    pass_d: Option<Pass>,
+    #[serde(default)]
+    uniforms: settings::Preset,
+    #[serde(default)]
+    video: Video,
+}
+
+#[derive(Clone, Debug)]
+pub struct Project {
+    pub shader: PathBuf,
+    pub directives: hlsl::Directives,
+    pub resolution: Option<settings::Resolution>,
+    pub uniforms: settings::Preset,
+    pub video: Video,
+}
+
+pub fn is_project(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.to_ascii_lowercase().ends_with(EXTENSION))
+        .unwrap_or(false)
+}
+
+fn check_resolution(resolution: settings::Resolution) -> Result<settings::Resolution, String> {
+    if resolution.width == 0 || resolution.height == 0 {
+        return Err(format!(
+            "invalid resolution '{}x{}'",
+            resolution.width, resolution.height
+        ));
+    }
+    Ok(resolution)
+}
+
+impl Project {
+    pub fn parse(data: &str, path: &Path) -> Result<Self, Error> {
+        let error = |message: String| Error::InvalidProject(path.to_path_buf(), message);
+        let manifest: Manifest = toml::from_str(data).map_err(|e| error(e.to_string()))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let shader = dir.join(&manifest.shader);
+        let shader_model = manifest
+            .model
+            .as_ref()
+            .map(|model| {
+                hlsl::ShaderModel::specify(model)
+                    .map_err(|_| error(format!("unsupported shader model '{}'", model)))
+            })
+            .transpose()?;
+        let mut directives = hlsl::Directives {
+            entry_point: manifest.entry,
+            vertex_entry_point: manifest.vertex,
+            shader_model,
+            mode: manifest.mode,
+            mesh: manifest.mesh.map(|mesh| dir.join(mesh)),
+            defines: manifest.defines,
+            ..Default::default()
+        };
+        let channels = [
+            manifest.channel0,
+            manifest.channel1,
+            manifest.channel2,
+            manifest.channel3,
+        ];
+        for (dest, src) in directives.channels.iter_mut().zip(channels) {
+            if let Some(src) = src {
+                *dest = hlsl::Channel {
+                    path: Some(dir.join(src.path)),
+                    wrap: src.wrap,
+                    filter: src.filter,
+                };
+            }
+        }
+        let passes = [
+            manifest.pass_a,
+            manifest.pass_b,
+            manifest.pass_c,
+            manifest.pass_d,
+        ];
+        for (dest, src) in directives.passes.iter_mut().zip(passes) {
+            if let Some(src) = src {
+                let resolution = src
+                    .resolution
+                    .map(check_resolution)
+                    .transpose()
+                    .map_err(error)?;
+                *dest = hlsl::Pass {
+                    path: Some(dir.join(src.shader)),
+                    entry_point: src.entry,
+                    resolution: resolution.map(|r| [r.width, r.height]),
+                };
+            }
+        }
+        let resolution = manifest
+            .resolution
+            .map(check_resolution)
+            .transpose()
+            .map_err(error)?;
+        Ok(Self {
+            shader: shader.canonicalize().unwrap_or(shader),
+            directives,
+            resolution,
+            uniforms: manifest.uniforms,
+            video: manifest.video,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path).map_err(|_| Error::ReadFile(path.into()))?;
+        Self::parse(&data, path)
+    }
+
+    pub fn video(&self, default: &settings::Video) -> settings::Video {
+        settings::Video {
+            frame_rate: self.video.frame_rate.unwrap_or(default.frame_rate),
+            end_frame: self.video.end_frame.unwrap_or(default.end_frame),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_project() {
+        let data = r#"
+shader = "image.hlsl"
+model = "6_6"
+mode = "compute"
+defines = ["FOO=1"]
+
+[resolution]
+width = 1280
+height = 720
+
+[channel0]
+path = "images/noise.png"
+filter = "point"
+
+[channel2]
+path = "../sky.hdr"
+wrap = "clamp"
+
+[pass_a]
+shader = "buffer_a.hlsl"
+entry = "simulate"
+resolution = { width = 256, height = 256 }
+
+[uniforms]
+radius = 0.5
+color = [1.0, 0.5, 0.0]
+
+[video]
+frame_rate = 60
+"#;
+        let project = Project::parse(data, Path::new("shaders/scene.hlslbox.toml")).unwrap();
+        assert!(project.shader == Path::new("shaders/image.hlsl"));
+        let directives = &project.directives;
+        assert!(directives.entry_point.is_none());
+        assert!(directives.shader_model == Some(hlsl::ShaderModel::specify("6_6").unwrap()));
+        assert!(directives.mode == hlsl::Mode::Compute);
+        assert!(directives.defines == ["FOO=1"]);
+        let channels = &directives.channels;
+        assert!(channels[0].path.as_deref() == Some(Path::new("shaders/images/noise.png")));
+        assert!(channels[0].wrap == hlsl::Wrap::Repeat);
+        assert!(channels[0].filter == hlsl::Filter::Point);
+        assert!(channels[1] == hlsl::Channel::default());
+        assert!(channels[2].path.as_deref() == Some(Path::new("shaders/../sky.hdr")));
+        assert!(channels[2].wrap == hlsl::Wrap::Clamp);
+        let passes = &directives.passes;
+        assert!(passes[0].path.as_deref() == Some(Path::new("shaders/buffer_a.hlsl")));
+        assert!(passes[0].entry_point.as_deref() == Some("simulate"));
+        assert!(passes[0].resolution == Some([256, 256]));
+        assert!(passes[1] == hlsl::Pass::default());
+        let resolution = project.resolution.unwrap();
+        assert!(resolution.width == 1280 && resolution.height == 720);
+        assert!(project.uniforms["radius"] == settings::ParamValue::Scalar(0.5));
+        assert!(project.uniforms["color"] == settings::ParamValue::Vector(vec![1.0, 0.5, 0.0]));
+        let video = project.video(&settings::Video {
+            frame_rate: 30,
+            end_frame: 300,
+        });
+        assert!(video.frame_rate == 60 && video.end_frame == 300);
+    }
+
+    #[test]
+    fn parse_project_mesh() {
+        let data = "shader = \"mesh.hlsl\"\nvertex = \"vs_main\"\nmesh = \"models/bunny.obj\"\n";
+        let project = Project::parse(data, Path::new("shaders/scene.hlslbox.toml")).unwrap();
+        let directives = &project.directives;
+        assert!(directives.vertex_entry_point.as_deref() == Some("vs_main"));
+        assert!(directives.mesh.as_deref() == Some(Path::new("shaders/models/bunny.obj")));
+    }
+
+    #[test]
+    fn parse_project_error() {
+        let path = Path::new("scene.hlslbox.toml");
+        let errors = [
+            "entry = \"main\"\n",
+            "shader = \"a.hlsl\"\nmodel = \"4_0\"\n",
+            "shader = \"a.hlsl\"\nmode = \"vertex\"\n",
+            "shader = \"a.hlsl\"\n[pass_b]\nshader = \"b.hlsl\"\nresolution = { width = 0, height = 1 }\n",
+            "shader = \"a.hlsl\"\n[channel0]\npath = \"a.png\"\nwrap = \"border\"\n",
+            "shader = \"a.hlsl\"\ndefine = [\"A=1\"]\n",
+            "shader = \"a.hlsl\"\n[chanel0]\npath = \"a.png\"\n",
+            "shader = \"a.hlsl\"\n[pass_e]\nshader = \"e.hlsl\"\n",
+            "shader = \"a.hlsl\"\n[channel0]\npath = \"a.png\"\nfilters = \"point\"\n",
+            "shader = \"a.hlsl\"\n[pass_a]\nshader = \"b.hlsl\"\nentry_point = \"main\"\n",
+            "shader = \"a.hlsl\"\n[video]\nframerate = 30\n",
+        ];
+        for data in errors {
+            match Project::parse(data, path) {
+                Err(Error::InvalidProject(p, _)) => assert!(p == path),
+                _ => panic!("{}", data),
+            }
+        }
+    }
+
+    #[test]
+    fn project_extension() {
+        assert!(is_project(Path::new("shaders/scene.hlslbox.toml")));
+        assert!(is_project(Path::new("SCENE.HLSLBOX.TOML")));
+        assert!(!is_project(Path::new("scene.hlsl")));
+        assert!(!is_project(Path::new("settings.toml")));
+    }
+}
@@ -0,0 +1,246 @@
+use crate::monitor::{Debouncer, DirMonitor, FileEvent};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::debug;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MonitorOptions {
+    pub polling: bool,
+    pub interval: Duration,
+    pub delay: Duration,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Reload {
+    Settings,
+    Shader,
+}
+
+fn create_monitor(path: &Path, options: &MonitorOptions) -> std::io::Result<Debouncer> {
+    Ok(Debouncer::new(
+        DirMonitor::new(path, options.polling, options.interval)?,
+        options.delay,
+    ))
+}
+
+fn drain(monitor: &mut Debouncer) -> impl Iterator<Item = FileEvent> + '_ {
+    std::iter::from_fn(move || monitor.try_recv())
+}
+
+pub struct Reloader {
+    settings_path: PathBuf,
+    window_setting_path: PathBuf,
+    options: MonitorOptions,
+    settings_monitor: Debouncer,
+    shader_monitors: Vec<Debouncer>,
+    dependencies: Vec<PathBuf>,
+}
+
+impl Reloader {
+    pub fn new(
+        settings_path: impl Into<PathBuf>,
+        window_setting_path: impl Into<PathBuf>,
+        options: MonitorOptions,
+    ) -> std::io::Result<Self> {
+        let settings_path = settings_path.into();
+        let settings_monitor = create_monitor(Self::settings_dir(&settings_path), &options)?;
+        Ok(Self {
+            settings_path,
+            window_setting_path: window_setting_path.into(),
+            options,
+            settings_monitor,
+            shader_monitors: Vec::new(),
+            dependencies: Vec::new(),
+        })
+    }
+
+    fn settings_dir(settings_path: &Path) -> &Path {
+        settings_path.parent().unwrap_or_else(|| Path::new("."))
+    }
+
+    pub fn is_setting(&self, path: &Path) -> bool {
+        path == self.settings_path || path == self.window_setting_path
+    }
+
+    pub fn set_options(&mut self, options: MonitorOptions) -> std::io::Result<()> {
+        if options.polling != self.options.polling || options.interval != self.options.interval {
+            self.options = options;
+            self.settings_monitor =
+                create_monitor(Self::settings_dir(&self.settings_path), &options)?;
+            self.shader_monitors.clear();
+            let dependencies = std::mem::take(&mut self.dependencies);
+            return self.watch(dependencies);
+        }
+        self.options = options;
+        self.settings_monitor.set_delay(options.delay);
+        for monitor in self.shader_monitors.iter_mut() {
+            monitor.set_delay(options.delay);
+        }
+        Ok(())
+    }
+
+    pub fn watch(&mut self, dependencies: Vec<PathBuf>) -> std::io::Result<()> {
+        let mut dirs = dependencies
+            .iter()
+            .filter_map(|d| d.parent())
+            .collect::<Vec<_>>();
+        dirs.sort();
+        dirs.dedup();
+        self.shader_monitors
+            .retain(|m| dirs.iter().any(|d| *d == m.path()));
+        for dir in dirs {
+            if self.shader_monitors.iter().all(|m| m.path() != dir) {
+                debug!("Reloader::watch: DirMonitor::new: {}", dir.display());
+                self.shader_monitors
+                    .push(create_monitor(dir, &self.options)?);
+            }
+        }
+        self.dependencies = dependencies;
+        Ok(())
+    }
+
+    /// Drains every monitor and returns what to load again, the settings first.
+    pub fn poll(&mut self) -> Vec<Reload> {
+        let settings = drain(&mut self.settings_monitor)
+            .filter(|ev| ev.is_written())
+            .any(|ev| ev.path() == self.settings_path);
+        let events = self
+            .shader_monitors
+            .iter_mut()
+            .flat_map(drain)
+            .collect::<Vec<_>>();
+        let shader = events
+            .iter()
+            .filter(|ev| ev.is_written())
+            .any(|ev| self.dependencies.iter().any(|d| d == ev.path()));
+        [(settings, Reload::Settings), (shader, Reload::Shader)]
+            .into_iter()
+            .filter_map(|(changed, reload)| changed.then_some(reload))
+            .collect()
+    }
+
+    /// Returns the file to load again for `Reload::Shader`.
+    ///
+    /// `current` is the rendered file or the file of the error message. An error in the settings
+    /// stays until the settings are fixed.
+    pub fn shader_path<'a>(&self, current: Option<&'a Path>) -> Option<&'a Path> {
+        current.filter(|path| !self.is_setting(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPTIONS: MonitorOptions = MonitorOptions {
+        polling: false,
+        interval: Duration::from_millis(100),
+        delay: Duration::from_millis(50),
+    };
+
+    fn create_dir(name: &str) -> PathBuf {
+        let dir = Path::new("./target/reload_test").join(name);
+        std::fs::create_dir_all(dir.join("include")).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    fn poll_until(reloader: &mut Reloader, expected: &[Reload]) -> bool {
+        let timeout = if expected.is_empty() {
+            Duration::from_millis(500)
+        } else {
+            Duration::from_secs(2)
+        };
+        let start = std::time::Instant::now();
+        while start.elapsed() < timeout {
+            let reloads = reloader.poll();
+            if !reloads.is_empty() {
+                return reloads == expected;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        expected.is_empty()
+    }
+
+    fn reload_events(options: MonitorOptions) {
+        let dir = create_dir(if options.polling { "polling" } else { "native" });
+        let settings = dir.join("settings.toml");
+        let shader = dir.join("shader.hlsl");
+        let include = dir.join("include/common.hlsli");
+        for path in [&settings, &shader, &include] {
+            std::fs::write(path, "").unwrap();
+        }
+        std::thread::sleep(options.interval);
+        let mut reloader = Reloader::new(&settings, dir.join("window.toml"), options).unwrap();
+        reloader
+            .watch(vec![shader.clone(), include.clone()])
+            .unwrap();
+        std::thread::sleep(options.interval);
+        std::fs::write(&include, "a").unwrap();
+        assert!(poll_until(&mut reloader, &[Reload::Shader]));
+        std::fs::write(&settings, "a").unwrap();
+        assert!(poll_until(&mut reloader, &[Reload::Settings]));
+        std::fs::write(dir.join("include/other.hlsli"), "a").unwrap();
+        std::fs::write(dir.join("window.toml"), "a").unwrap();
+        assert!(poll_until(&mut reloader, &[]));
+        std::fs::remove_file(&include).unwrap();
+        assert!(poll_until(&mut reloader, &[]));
+    }
+
+    #[test]
+    fn reload_native_events() {
+        reload_events(OPTIONS);
+    }
+
+    #[test]
+    fn reload_polling_events() {
+        reload_events(MonitorOptions {
+            polling: true,
+            ..OPTIONS
+        });
+    }
+
+    #[test]
+    fn reload_watch_dirs() {
+        let dir = create_dir("watch_dirs");
+        let mut reloader =
+            Reloader::new(dir.join("settings.toml"), dir.join("window.toml"), OPTIONS).unwrap();
+        let shader = dir.join("shader.hlsl");
+        let include = dir.join("include/common.hlsli");
+        reloader
+            .watch(vec![shader.clone(), include.clone()])
+            .unwrap();
+        assert!(reloader.shader_monitors.len() == 2);
+        reloader.watch(vec![shader.clone()]).unwrap();
+        assert!(reloader.shader_monitors.len() == 1);
+        assert!(reloader.shader_monitors[0].path() == dir);
+        reloader
+            .set_options(MonitorOptions {
+                polling: true,
+                ..OPTIONS
+            })
+            .unwrap();
+        assert!(reloader.shader_monitors.len() == 1);
+        assert!(reloader.dependencies == [shader]);
+    }
+
+    #[test]
+    fn reload_shader_path() {
+        let reloader = Reloader::new(
+            "./target/reload_test/settings.toml",
+            "./target/reload_test/window.toml",
+            OPTIONS,
+        )
+        .unwrap();
+        let shader = Path::new("shader.hlsl");
+        assert!(reloader.shader_path(Some(shader)) == Some(shader));
+        assert!(reloader
+            .shader_path(Some(Path::new("./target/reload_test/settings.toml")))
+            .is_none());
+        assert!(reloader
+            .shader_path(Some(Path::new("./target/reload_test/window.toml")))
+            .is_none());
+        assert!(reloader.shader_path(None).is_none());
+    }
+}
@@ -1,16 +1,19 @@
 mod buffers;
 mod command_list;
 mod command_queue;
+mod headless;
 mod layer_shader;
+pub mod mesh;
 pub mod pixel_shader;
 mod plane;
+mod scene;
 mod swap_chain;
 mod ui;
 mod utility;
 mod video;
 
 use crate::*;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::path::Path;
 use windows::core::{Interface, PCSTR};
 use windows::Win32::{
@@ -21,13 +24,16 @@ use windows::Win32::{
 use buffers::*;
 use command_list::*;
 use command_queue::*;
+pub use headless::Headless;
 use layer_shader::*;
 pub use pixel_shader::Pipeline;
 use pixel_shader::PixelShader;
+use scene::*;
 use swap_chain::*;
 pub use ui::RenderUi;
 use ui::*;
 use utility::*;
+pub use video::VideoFile;
 
 trait Resource {
     fn resource(&self) -> &ID3D12Resource;
@@ -78,6 +84,28 @@ trait Source: Resource {
     fn record(&self, cmd_list: &ID3D12GraphicsCommandList);
 }
 
+trait UnorderedAccess: Resource {
+    fn enter(&self) -> TransitionBarrier {
+        TransitionBarrier {
+            resource: self.resource().clone(),
+            subresource: 0,
+            state_before: D3D12_RESOURCE_STATE_COMMON,
+            state_after: D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+        }
+    }
+
+    fn leave(&self) -> TransitionBarrier {
+        TransitionBarrier {
+            resource: self.resource().clone(),
+            subresource: 0,
+            state_before: D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+            state_after: D3D12_RESOURCE_STATE_COMMON,
+        }
+    }
+
+    fn record(&self, cmd_list: &ID3D12GraphicsCommandList);
+}
+
 trait CopySource: Resource {
     fn enter(&self) -> TransitionBarrier {
         TransitionBarrier {
@@ -155,6 +183,34 @@ impl Target for RenderTarget {
     }
 }
 
+pub struct DepthTarget {
+    target: RenderTarget,
+    _depth: ID3D12Resource,
+    handle: D3D12_CPU_DESCRIPTOR_HANDLE,
+}
+
+impl Resource for DepthTarget {
+    fn resource(&self) -> &ID3D12Resource {
+        &self.target.resource
+    }
+}
+
+impl Target for DepthTarget {
+    fn clear(&self, cmd_list: &ID3D12GraphicsCommandList, clear_color: [f32; 4]) {
+        self.target.clear(cmd_list, clear_color);
+        unsafe {
+            cmd_list.ClearDepthStencilView(self.handle, D3D12_CLEAR_FLAG_DEPTH, 1.0, 0, &[]);
+        }
+    }
+
+    fn record(&self, cmd_list: &ID3D12GraphicsCommandList) {
+        self.target.record(cmd_list);
+        unsafe {
+            cmd_list.OMSetRenderTargets(1, [self.target.handle].as_ptr(), false, &self.handle);
+        }
+    }
+}
+
 pub struct CopyResource {
     resource: ID3D12Resource,
 }
@@ -206,7 +262,7 @@ pub struct Renderer {
     d3d12_device: ID3D12Device,
     swap_chain: SwapChain,
     render_target: RenderTargetBuffers,
-    pixel_shader: PixelShader,
+    scene: Scene,
     cmd_allocators: Vec<ID3D12CommandAllocator>,
     copy_allocators: Arc<Pool<(ID3D12CommandAllocator, Option<Signal>)>>,
     cmd_list: DirectCommandList,
@@ -214,7 +270,6 @@ pub struct Renderer {
     ui: Ui,
     copy_queue: CommandQueue<CopyCommandList>,
     main_queue: PresentableQueue,
-    filling_plane: plane::Buffer,
     adjusted_plane: plane::Buffer,
     read_back_buffers: Arc<Pool<ReadBackBuffer>>,
     video: video::Video,
@@ -263,9 +318,15 @@ impl Renderer {
             })?;
             let copy_queue = CommandQueue::new("Renderer::copy_queue", d3d12_device)?;
             let render_target = RenderTargetBuffers::new(d3d12_device, resolution, buffer_count)?;
-            let pixel_shader = PixelShader::new(d3d12_device, compiler, shader_model)?;
+            let scene = Scene::new(
+                d3d12_device,
+                &copy_queue,
+                compiler,
+                shader_model,
+                &render_target,
+            )
+            .await?;
             let ui = Ui::new(d3d12_device, buffer_count, window)?;
-            let filling_plane = plane::Buffer::new(d3d12_device, &copy_queue).await?;
             let adjusted_plane = plane::Buffer::new(d3d12_device, &copy_queue).await?;
             let layer_shader = LayerShader::new(d3d12_device, compiler, shader_model)?;
             let cmd_list = DirectCommandList::new(
@@ -289,7 +350,7 @@ impl Renderer {
                 d3d12_device: d3d12_device.clone(),
                 swap_chain,
                 render_target,
-                pixel_shader,
+                scene,
                 cmd_allocators,
                 copy_allocators,
                 cmd_list,
@@ -297,7 +358,6 @@ impl Renderer {
                 ui,
                 copy_queue,
                 main_queue: presentable_queue,
-                filling_plane,
                 adjusted_plane,
                 read_back_buffers,
                 video,
@@ -314,9 +374,58 @@ impl Renderer {
         &self,
         name: &str,
         ps: &hlsl::Blob,
+        reflection: &hlsl::Reflection,
+        vs: Option<(&hlsl::Blob, &hlsl::Reflection)>,
+        mesh: bool,
+    ) -> Result<Pipeline, Error> {
+        self.scene
+            .create_pixel_shader_pipeline(name, &self.d3d12_device, ps, reflection, vs, mesh)
+    }
+
+    pub fn create_pass_pipeline(
+        &self,
+        name: &str,
+        ps: &hlsl::Blob,
+        reflection: &hlsl::Reflection,
     ) -> Result<Pipeline, Error> {
-        self.pixel_shader
-            .create_pipeline(name, &self.d3d12_device, ps)
+        self.scene
+            .create_pass_pipeline(name, &self.d3d12_device, ps, reflection)
+    }
+
+    pub async fn set_passes(
+        &mut self,
+        sizes: [Option<wita::PhysicalSize<u32>>; hlsl::PASS_COUNT],
+    ) -> Result<(), Error> {
+        if *self.scene.pass_sizes() == sizes {
+            return Ok(());
+        }
+        self.wait_all_signals().await;
+        self.scene.set_passes(&self.d3d12_device, sizes)
+    }
+
+    pub fn clear_passes(&self) {
+        self.scene.clear_passes();
+    }
+
+    pub async fn set_mesh(&mut self, mesh: Option<&mesh::Mesh>) -> Result<(), Error> {
+        self.wait_all_signals().await;
+        self.scene
+            .set_mesh(&self.d3d12_device, &self.copy_queue, mesh)
+            .await
+    }
+
+    pub async fn set_channels(
+        &mut self,
+        channels: &[Option<pixel_shader::Channel>],
+    ) -> Result<(), Error> {
+        self.wait_all_signals().await;
+        self.scene
+            .set_channels(&self.d3d12_device, &self.copy_queue, channels)
+            .await
+    }
+
+    pub fn update_keyboard(&self, keyboard: &window::Keyboard) -> Result<(), Error> {
+        self.scene.update_keyboard(keyboard.data())
     }
 
     #[allow(clippy::await_holding_refcell_ref)]
@@ -326,6 +435,8 @@ impl Renderer {
         clear_color: [f32; 4],
         ps: Option<&Pipeline>,
         parameters: Option<&pixel_shader::Parameters>,
+        user_parameters: &[u8],
+        passes: &[pixel_shader::Pass],
         r: &impl RenderUi,
     ) -> anyhow::Result<()> {
         if let Some(frame_rate_tick) = self.frame_rate_tick.as_ref() {
@@ -342,16 +453,15 @@ impl Renderer {
         let ui_buffer = self.ui.source(index);
         let cmd_list = &self.cmd_list;
         cmd_list.record(&cmd_allocators[0], |cmd| {
-            if let Some(ps) = ps {
-                if let Some(parameters) = parameters {
-                    let shader = self.pixel_shader.apply(ps, parameters);
-                    let target = self.render_target.target(index);
-                    cmd.barrier([target.enter()]);
-                    cmd.clear(&target, [0.0, 0.0, 0.0, 0.0]);
-                    cmd.draw(&shader, &target, &self.filling_plane);
-                    cmd.barrier([target.leave()]);
-                }
-            }
+            self.scene.record(
+                &cmd,
+                &self.render_target,
+                index,
+                ps,
+                parameters,
+                user_parameters,
+                passes,
+            );
             cmd.barrier([ps_result.enter(), back_buffer.enter()]);
             cmd.clear(&back_buffer, clear_color);
             cmd.layer(&ps_result, &back_buffer, &self.adjusted_plane);
@@ -385,7 +495,7 @@ impl Renderer {
         }
         cmd_list.record(&cmd_allocators[1], |cmd| {
             cmd.barrier([ui_buffer.enter()]);
-            cmd.layer(&ui_buffer, &back_buffer, &self.filling_plane);
+            cmd.layer(&ui_buffer, &back_buffer, self.scene.filling_plane());
             cmd.barrier([ps_result.leave(), back_buffer.leave(), ui_buffer.leave()]);
         })?;
         let ui_signal = self.ui.render(index, r)?;
@@ -417,7 +527,7 @@ impl Renderer {
             path,
             self.render_target.size(),
             frame_rate,
-            1_500_000,
+            video::BIT_RATE,
             end_frame,
         )
     }
@@ -461,6 +571,29 @@ impl Renderer {
         Ok(Some(img))
     }
 
+    pub fn resolution(&self) -> wita::PhysicalSize<u32> {
+        self.render_target.size()
+    }
+
+    pub async fn set_resolution(
+        &mut self,
+        resolution: wita::PhysicalSize<u32>,
+    ) -> anyhow::Result<()> {
+        if self.render_target.size() == resolution {
+            return Ok(());
+        }
+        self.wait_all_signals().await;
+        let buffer_count = TargetableBuffers::len(&self.render_target);
+        self.render_target =
+            RenderTargetBuffers::new(&self.d3d12_device, resolution, buffer_count)?;
+        self.scene
+            .set_render_target(&self.d3d12_device, &self.render_target)?;
+        self.read_back_buffers = Pool::with_initializer(Self::READ_BACK_BUFFER_COUNT, |_| {
+            ReadBackBuffer::new(&self.d3d12_device, resolution).map_err(|e| e.into())
+        })?;
+        Ok(())
+    }
+
     pub async fn resize(&mut self, size: wita::PhysicalSize<u32>) -> Result<(), Error> {
         self.wait_all_signals().await;
         self.swap_chain.resize(&self.d3d12_device, None, size)?;
@@ -529,7 +662,12 @@ impl Renderer {
             .resize(&self.d3d12_device, Some(buffer_count), resolution.into())?;
         let render_target =
             RenderTargetBuffers::new(&self.d3d12_device, resolution.into(), buffer_count as _)?;
-        let pixel_shader = PixelShader::new(&self.d3d12_device, compiler, shader_model)?;
+        self.scene.recreate_pixel_shader(
+            &self.d3d12_device,
+            compiler,
+            shader_model,
+            &render_target,
+        )?;
         let layer_shader = LayerShader::new(&self.d3d12_device, compiler, shader_model)?;
         let cmd_list = DirectCommandList::new(
             "Renderer::cmd_list",
@@ -566,7 +704,6 @@ impl Renderer {
         self.swap_chain
             .set_max_frame_latency(setting.max_frame_latency)?;
         self.render_target = render_target;
-        self.pixel_shader = pixel_shader;
         self.cmd_list = cmd_list;
         Ok(())
     }
@@ -586,6 +723,115 @@ impl Drop for Renderer {
 mod tests {
     use super::*;
 
+    #[test]
+    fn pass_buffer_sides() {
+        for frame in 0..4 {
+            let written = PassBuffers::write_side(frame);
+            assert!(PassBuffers::read_side(frame, 1, 0) == written);
+            assert!(PassBuffers::read_side(frame, pixel_shader::FINAL_PASS, 3) == written);
+            assert!(PassBuffers::read_side(frame, 0, 0) != written);
+            assert!(PassBuffers::read_side(frame, 1, 2) != written);
+            assert!(PassBuffers::read_side(frame + 1, 0, 0) == written);
+        }
+    }
+
+    #[test]
+    fn check_pixel_shader_bindings() {
+        let binding = |name: &str, kind, register, space| hlsl::Binding {
+            name: name.to_string(),
+            kind,
+            register,
+            count: 1,
+            space,
+        };
+        let variable = |name: &str, type_name: &str, offset, size| hlsl::Variable {
+            name: name.to_string(),
+            type_name: type_name.to_string(),
+            offset,
+            size,
+        };
+        let mut reflection = hlsl::Reflection {
+            bindings: vec![
+                binding("HLSLBox", hlsl::BindingKind::ConstantBuffer, 0, 0),
+                binding("User", hlsl::BindingKind::ConstantBuffer, 1, 0),
+                binding("HLSLBoxKeyboard", hlsl::BindingKind::Texture, 0, 1),
+                binding("HLSLBoxChannel0", hlsl::BindingKind::Texture, 0, 0),
+                binding("HLSLBoxSampler3", hlsl::BindingKind::Sampler, 3, 0),
+                binding("HLSLBoxPassD", hlsl::BindingKind::Texture, 3, 2),
+                binding("HLSLBoxPassSampler", hlsl::BindingKind::Sampler, 0, 2),
+            ],
+            constant_buffers: vec![
+                hlsl::ConstantBuffer {
+                    name: "HLSLBox".to_string(),
+                    size: 64,
+                    variables: vec![
+                        variable("HLSLBox.resolution", "float2", 0, 8),
+                        variable("HLSLBox.mouse", "float2", 8, 8),
+                        variable("HLSLBox.time", "float", 16, 4),
+                        variable("HLSLBox.click", "float2", 48, 8),
+                    ],
+                },
+                hlsl::ConstantBuffer {
+                    name: "User".to_string(),
+                    size: 16,
+                    variables: vec![variable("speed", "float", 0, 4)],
+                },
+            ],
+            thread_group_size: None,
+        };
+        assert!(pixel_shader::check_bindings(&reflection).is_ok());
+        assert!(
+            pixel_shader::user_constant_buffer(&reflection)
+                .unwrap()
+                .name
+                == "User"
+        );
+        reflection.constant_buffers[0]
+            .variables
+            .push(variable("HLSLBox.extra", "float", 256, 4));
+        reflection
+            .bindings
+            .push(binding("tex", hlsl::BindingKind::Texture, 4, 0));
+        reflection
+            .bindings
+            .push(binding("params", hlsl::BindingKind::ConstantBuffer, 2, 0));
+        match pixel_shader::check_bindings(&reflection) {
+            Err(Error::Compile(diagnostics)) => {
+                assert!(diagnostics.len() == 3);
+                assert!(diagnostics[0]
+                    .message
+                    .starts_with("'HLSLBox.extra' (offset 256, 4 bytes)"));
+                assert!(
+                    diagnostics[1].message
+                        == "texture 'tex' (t4, space0) is not provided by hlsl_box"
+                );
+                assert!(
+                    diagnostics[2].message
+                        == "constant buffer 'params' (b2, space0) is not provided by hlsl_box"
+                );
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn check_compute_shader_bindings() {
+        let mut reflection = hlsl::Reflection {
+            bindings: vec![hlsl::Binding {
+                name: "HLSLBoxOutput".to_string(),
+                kind: hlsl::BindingKind::UnorderedAccess,
+                register: 0,
+                count: 1,
+                space: 0,
+            }],
+            constant_buffers: vec![],
+            thread_group_size: Some([8, 8, 1]),
+        };
+        assert!(pixel_shader::check_bindings(&reflection).is_ok());
+        reflection.thread_group_size = None;
+        assert!(pixel_shader::check_bindings(&reflection).is_err());
+    }
+
     #[tokio::test]
     async fn render_fill_test() {
         let device: ID3D12Device = unsafe {
@@ -624,15 +870,19 @@ mod tests {
             )
             .unwrap();
         let ps = pixel_shader
-            .create_pipeline("render_test::ps", &device, &blob)
+            .create_pipeline(
+                "render_test::ps",
+                &device,
+                &blob,
+                DXGI_FORMAT_R8G8B8A8_UNORM,
+            )
             .unwrap();
         let parameters = pixel_shader::Parameters {
             resolution: [resolution.width as f32, resolution.height as f32],
-            mouse: [0.0, 0.0],
-            time: 0.0,
+            ..Default::default()
         };
         let buffers = RenderTargetBuffers::new(&device, resolution, 1).unwrap();
-        let shader = pixel_shader.apply(&ps, &parameters);
+        let shader = pixel_shader.apply(&ps, &parameters, &[], pixel_shader::FINAL_PASS, 0);
         let target = buffers.target(0);
         cmd_list
             .record(&cmd_allocator, |cmd| {
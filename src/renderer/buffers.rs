@@ -43,9 +43,13 @@ impl RenderTargetBuffers {
                     device,
                     size.width as _,
                     size.height,
+                    DXGI_FORMAT_R8G8B8A8_UNORM,
                     D3D12_RESOURCE_STATE_COMMON,
                     None,
-                    Some(D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET),
+                    Some(
+                        D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET
+                            | D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS,
+                    ),
                     &[0.0, 0.0, 0.0, 0.0],
                 )?;
                 let rtv_desc = D3D12_RENDER_TARGET_VIEW_DESC {
@@ -87,6 +91,10 @@ impl RenderTargetBuffers {
         self.size
     }
 
+    pub fn texture(&self, index: usize) -> Option<&Texture2D> {
+        self.buffers.get(index)
+    }
+
     pub fn copy_resource(&self, index: usize) -> CopyResource {
         CopyResource {
             resource: self.buffers[index].handle().clone(),
@@ -130,6 +138,106 @@ impl PixelShaderResourceBuffers for RenderTargetBuffers {
     }
 }
 
+pub struct PassBuffers {
+    rtv_heap: ID3D12DescriptorHeap,
+    rtv_size: usize,
+    buffers: Vec<Option<[Texture2D; 2]>>,
+    sizes: [Option<wita::PhysicalSize<u32>>; hlsl::PASS_COUNT],
+}
+
+impl PassBuffers {
+    pub const FORMAT: DXGI_FORMAT = DXGI_FORMAT_R32G32B32A32_FLOAT;
+
+    pub fn new(
+        device: &ID3D12Device,
+        sizes: [Option<wita::PhysicalSize<u32>>; hlsl::PASS_COUNT],
+    ) -> Result<Self, Error> {
+        unsafe {
+            let rtv_heap: ID3D12DescriptorHeap =
+                device.CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
+                    Type: D3D12_DESCRIPTOR_HEAP_TYPE_RTV,
+                    NumDescriptors: (hlsl::PASS_COUNT * 2) as _,
+                    ..Default::default()
+                })?;
+            let rtv_size =
+                device.GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_RTV) as usize;
+            let rtv_desc = D3D12_RENDER_TARGET_VIEW_DESC {
+                Format: Self::FORMAT,
+                ViewDimension: D3D12_RTV_DIMENSION_TEXTURE2D,
+                Anonymous: D3D12_RENDER_TARGET_VIEW_DESC_0 {
+                    Texture2D: D3D12_TEX2D_RTV::default(),
+                },
+            };
+            let mut buffers = Vec::with_capacity(hlsl::PASS_COUNT);
+            for (i, size) in sizes.iter().enumerate() {
+                let size = match size {
+                    Some(size) => size,
+                    None => {
+                        buffers.push(None);
+                        continue;
+                    }
+                };
+                let create = |side: usize| -> Result<Texture2D, Error> {
+                    let texture = Texture2D::new(
+                        &format!("PassBuffers::buffers[{}][{}]", i, side),
+                        device,
+                        size.width as _,
+                        size.height,
+                        Self::FORMAT,
+                        D3D12_RESOURCE_STATE_COMMON,
+                        None,
+                        Some(D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET),
+                        &[0.0, 0.0, 0.0, 0.0],
+                    )?;
+                    let mut handle = rtv_heap.GetCPUDescriptorHandleForHeapStart();
+                    handle.ptr += (i * 2 + side) * rtv_size;
+                    device.CreateRenderTargetView(texture.handle(), &rtv_desc, handle);
+                    Ok(texture)
+                };
+                buffers.push(Some([create(0)?, create(1)?]));
+            }
+            Ok(Self {
+                rtv_heap,
+                rtv_size,
+                buffers,
+                sizes,
+            })
+        }
+    }
+
+    pub fn sizes(&self) -> &[Option<wita::PhysicalSize<u32>>; hlsl::PASS_COUNT] {
+        &self.sizes
+    }
+
+    pub fn read_side(frame: usize, reader: usize, pass: usize) -> usize {
+        (frame + (pass < reader) as usize) % 2
+    }
+
+    pub fn write_side(frame: usize) -> usize {
+        (frame + 1) % 2
+    }
+
+    pub fn texture(&self, pass: usize, side: usize) -> Option<&Texture2D> {
+        self.buffers
+            .get(pass)
+            .and_then(|b| b.as_ref())
+            .map(|b| &b[side])
+    }
+
+    pub fn target(&self, pass: usize, side: usize) -> Option<RenderTarget> {
+        let texture = self.texture(pass, side)?;
+        unsafe {
+            let mut handle = self.rtv_heap.GetCPUDescriptorHandleForHeapStart();
+            handle.ptr += (pass * 2 + side) * self.rtv_size;
+            Some(RenderTarget {
+                resource: texture.handle().clone(),
+                handle,
+                size: self.sizes[pass].unwrap(),
+            })
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct ReadBackBuffer {
     buffer: Buffer,
@@ -169,6 +277,74 @@ impl Resource for ReadBackBuffer {
 unsafe impl Send for ReadBackBuffer {}
 unsafe impl Sync for ReadBackBuffer {}
 
+pub struct DepthBuffer {
+    dsv_heap: ID3D12DescriptorHeap,
+    resource: ID3D12Resource,
+}
+
+impl DepthBuffer {
+    pub const FORMAT: DXGI_FORMAT = DXGI_FORMAT_D32_FLOAT;
+
+    pub fn new(device: &ID3D12Device, size: wita::PhysicalSize<u32>) -> Result<Self, Error> {
+        unsafe {
+            let dsv_heap: ID3D12DescriptorHeap =
+                device.CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
+                    Type: D3D12_DESCRIPTOR_HEAP_TYPE_DSV,
+                    NumDescriptors: 1,
+                    ..Default::default()
+                })?;
+            let desc = D3D12_RESOURCE_DESC {
+                Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+                Width: size.width as _,
+                Height: size.height,
+                DepthOrArraySize: 1,
+                MipLevels: 1,
+                Format: Self::FORMAT,
+                Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+                Flags: D3D12_RESOURCE_FLAG_ALLOW_DEPTH_STENCIL,
+                SampleDesc: SampleDesc::default().into(),
+                ..Default::default()
+            };
+            let mut resource: Option<ID3D12Resource> = None;
+            let resource = device
+                .CreateCommittedResource(
+                    &HeapProperties::new(D3D12_HEAP_TYPE_DEFAULT).into(),
+                    D3D12_HEAP_FLAG_NONE,
+                    &desc,
+                    D3D12_RESOURCE_STATE_DEPTH_WRITE,
+                    &D3D12_CLEAR_VALUE {
+                        Format: Self::FORMAT,
+                        Anonymous: D3D12_CLEAR_VALUE_0 {
+                            DepthStencil: D3D12_DEPTH_STENCIL_VALUE {
+                                Depth: 1.0,
+                                Stencil: 0,
+                            },
+                        },
+                    },
+                    &mut resource,
+                )
+                .map(|_| resource.unwrap())?;
+            resource.SetName("DepthBuffer::resource")?;
+            device.CreateDepthStencilView(
+                &resource,
+                std::ptr::null(),
+                dsv_heap.GetCPUDescriptorHandleForHeapStart(),
+            );
+            Ok(Self { dsv_heap, resource })
+        }
+    }
+
+    pub fn target(&self, target: RenderTarget) -> DepthTarget {
+        unsafe {
+            DepthTarget {
+                target,
+                _depth: self.resource.clone(),
+                handle: self.dsv_heap.GetCPUDescriptorHandleForHeapStart(),
+            }
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct DefaultBuffer(pub Buffer);
@@ -220,3 +396,78 @@ impl Resource for UploadBuffer {
 }
 
 impl CopySource for UploadBuffer {}
+
+#[derive(Clone)]
+pub struct ImageTexture {
+    texture: ID3D12Resource,
+    footprint: D3D12_PLACED_SUBRESOURCE_FOOTPRINT,
+    upload_size: u64,
+}
+
+impl ImageTexture {
+    pub fn new(
+        name: &str,
+        device: &ID3D12Device,
+        size: wita::PhysicalSize<u32>,
+        format: DXGI_FORMAT,
+    ) -> Result<Self, Error> {
+        let desc = D3D12_RESOURCE_DESC {
+            Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+            Width: size.width as _,
+            Height: size.height,
+            DepthOrArraySize: 1,
+            MipLevels: 1,
+            Format: format,
+            Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+            SampleDesc: SampleDesc::default().into(),
+            ..Default::default()
+        };
+        unsafe {
+            let mut texture: Option<ID3D12Resource> = None;
+            let texture = device
+                .CreateCommittedResource(
+                    &HeapProperties::new(D3D12_HEAP_TYPE_DEFAULT).into(),
+                    D3D12_HEAP_FLAG_NONE,
+                    &desc,
+                    D3D12_RESOURCE_STATE_COMMON,
+                    std::ptr::null(),
+                    &mut texture,
+                )
+                .map(|_| texture.unwrap())?;
+            texture.SetName(name)?;
+            let mut footprint = D3D12_PLACED_SUBRESOURCE_FOOTPRINT::default();
+            let mut upload_size = 0;
+            device.GetCopyableFootprints(
+                &desc,
+                0,
+                1,
+                0,
+                &mut footprint,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                &mut upload_size,
+            );
+            Ok(Self {
+                texture,
+                footprint,
+                upload_size,
+            })
+        }
+    }
+
+    pub fn footprint(&self) -> &D3D12_PLACED_SUBRESOURCE_FOOTPRINT {
+        &self.footprint
+    }
+
+    pub fn upload_size(&self) -> u64 {
+        self.upload_size
+    }
+}
+
+impl Resource for ImageTexture {
+    fn resource(&self) -> &ID3D12Resource {
+        &self.texture
+    }
+}
+
+impl CopyDest for ImageTexture {}
@@ -30,16 +30,39 @@ impl<'a> DirectCommand<'a> {
         self.draw_plane(plane);
     }
 
+    pub fn dispatch(&self, shader: &impl Shader, target: &impl UnorderedAccess, groups: [u32; 3]) {
+        shader.record(&self.0.cmd_list);
+        target.record(&self.0.cmd_list);
+        unsafe {
+            self.0.cmd_list.Dispatch(groups[0], groups[1], groups[2]);
+        }
+    }
+
+    pub fn draw_mesh(&self, shader: &impl Shader, target: &impl Target, mesh: &mesh::Buffer) {
+        shader.record(&self.0.cmd_list);
+        target.record(&self.0.cmd_list);
+        self.draw_indexed(&mesh.vbv, &mesh.ibv, mesh.indices_len());
+    }
+
     fn draw_plane(&self, plane: &plane::Buffer) {
+        self.draw_indexed(&plane.vbv, &plane.ibv, plane.indices_len());
+    }
+
+    fn draw_indexed(
+        &self,
+        vbv: &D3D12_VERTEX_BUFFER_VIEW,
+        ibv: &D3D12_INDEX_BUFFER_VIEW,
+        indices_len: usize,
+    ) {
         unsafe {
             self.0
                 .cmd_list
                 .IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
-            self.0.cmd_list.IASetVertexBuffers(0, &[plane.vbv]);
-            self.0.cmd_list.IASetIndexBuffer(&plane.ibv);
+            self.0.cmd_list.IASetVertexBuffers(0, &[*vbv]);
+            self.0.cmd_list.IASetIndexBuffer(ibv);
             self.0
                 .cmd_list
-                .DrawIndexedInstanced(plane.indices_len() as _, 1, 0, 0, 0);
+                .DrawIndexedInstanced(indices_len as _, 1, 0, 0, 0);
         }
     }
 }
@@ -123,6 +146,30 @@ impl<'a> CopyCommand<'a, UploadBuffer, DefaultBuffer> {
     }
 }
 
+impl<'a> CopyCommand<'a, UploadBuffer, ImageTexture> {
+    pub fn copy(&self, src: &UploadBuffer, dest: &ImageTexture) {
+        unsafe {
+            let copy_src = D3D12_TEXTURE_COPY_LOCATION {
+                Type: D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
+                pResource: Some(src.resource().clone()),
+                Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+                    PlacedFootprint: *dest.footprint(),
+                },
+            };
+            let copy_dest = D3D12_TEXTURE_COPY_LOCATION {
+                Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
+                pResource: Some(dest.resource().clone()),
+                Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+                    SubresourceIndex: 0,
+                },
+            };
+            self.cmd_list
+                .0
+                .CopyTextureRegion(&copy_dest, 0, 0, 0, &copy_src, std::ptr::null());
+        }
+    }
+}
+
 impl<'a, T> CopyCommand<'a, T, ReadBackBuffer>
 where
     T: CopySource,
@@ -0,0 +1,146 @@
+use super::*;
+
+pub struct Headless {
+    d3d12_device: ID3D12Device,
+    render_target: RenderTargetBuffers,
+    scene: Scene,
+    cmd_allocator: ID3D12CommandAllocator,
+    cmd_list: DirectCommandList,
+    main_queue: CommandQueue<DirectCommandList>,
+    copy_allocator: ID3D12CommandAllocator,
+    copy_list: CopyCommandList,
+    copy_queue: CommandQueue<CopyCommandList>,
+    read_back_buffer: ReadBackBuffer,
+}
+
+impl Headless {
+    pub async fn new(
+        d3d12_device: &ID3D12Device,
+        resolution: wita::PhysicalSize<u32>,
+        compiler: &hlsl::Compiler,
+        shader_model: hlsl::ShaderModel,
+    ) -> anyhow::Result<Self> {
+        unsafe {
+            let cmd_allocator: ID3D12CommandAllocator =
+                d3d12_device.CreateCommandAllocator(D3D12_COMMAND_LIST_TYPE_DIRECT)?;
+            cmd_allocator.SetName("Headless::cmd_allocator")?;
+            let copy_allocator: ID3D12CommandAllocator =
+                d3d12_device.CreateCommandAllocator(D3D12_COMMAND_LIST_TYPE_COPY)?;
+            copy_allocator.SetName("Headless::copy_allocator")?;
+            let main_queue = CommandQueue::new("Headless::main_queue", d3d12_device)?;
+            let copy_queue = CommandQueue::new("Headless::copy_queue", d3d12_device)?;
+            let render_target = RenderTargetBuffers::new(d3d12_device, resolution, 1)?;
+            let scene = Scene::new(
+                d3d12_device,
+                &copy_queue,
+                compiler,
+                shader_model,
+                &render_target,
+            )
+            .await?;
+            let layer_shader = LayerShader::new(d3d12_device, compiler, shader_model)?;
+            let cmd_list = DirectCommandList::new(
+                "Headless::cmd_list",
+                d3d12_device,
+                &cmd_allocator,
+                layer_shader,
+            )?;
+            let copy_list =
+                CopyCommandList::new("Headless::copy_list", d3d12_device, &copy_allocator)?;
+            let read_back_buffer = ReadBackBuffer::new(d3d12_device, resolution)?;
+            Ok(Self {
+                d3d12_device: d3d12_device.clone(),
+                render_target,
+                scene,
+                cmd_allocator,
+                cmd_list,
+                main_queue,
+                copy_allocator,
+                copy_list,
+                copy_queue,
+                read_back_buffer,
+            })
+        }
+    }
+
+    pub fn resolution(&self) -> wita::PhysicalSize<u32> {
+        self.render_target.size()
+    }
+
+    pub fn create_pixel_shader_pipeline(
+        &self,
+        name: &str,
+        ps: &hlsl::Blob,
+        reflection: &hlsl::Reflection,
+        vs: Option<(&hlsl::Blob, &hlsl::Reflection)>,
+        mesh: bool,
+    ) -> Result<Pipeline, Error> {
+        self.scene
+            .create_pixel_shader_pipeline(name, &self.d3d12_device, ps, reflection, vs, mesh)
+    }
+
+    pub fn create_pass_pipeline(
+        &self,
+        name: &str,
+        ps: &hlsl::Blob,
+        reflection: &hlsl::Reflection,
+    ) -> Result<Pipeline, Error> {
+        self.scene
+            .create_pass_pipeline(name, &self.d3d12_device, ps, reflection)
+    }
+
+    pub fn set_passes(
+        &mut self,
+        sizes: [Option<wita::PhysicalSize<u32>>; hlsl::PASS_COUNT],
+    ) -> Result<(), Error> {
+        self.scene.set_passes(&self.d3d12_device, sizes)
+    }
+
+    pub async fn set_mesh(&mut self, mesh: Option<&mesh::Mesh>) -> Result<(), Error> {
+        self.scene
+            .set_mesh(&self.d3d12_device, &self.copy_queue, mesh)
+            .await
+    }
+
+    pub async fn set_channels(
+        &mut self,
+        channels: &[Option<pixel_shader::Channel>],
+    ) -> Result<(), Error> {
+        self.scene
+            .set_channels(&self.d3d12_device, &self.copy_queue, channels)
+            .await
+    }
+
+    pub async fn render(
+        &self,
+        ps: &Pipeline,
+        parameters: &pixel_shader::Parameters,
+        user_parameters: &[u8],
+        passes: &[pixel_shader::Pass],
+    ) -> anyhow::Result<image::RgbaImage> {
+        self.cmd_list.record(&self.cmd_allocator, |cmd| {
+            self.scene.record(
+                &cmd,
+                &self.render_target,
+                0,
+                Some(ps),
+                Some(parameters),
+                user_parameters,
+                passes,
+            );
+        })?;
+        let signal = self.main_queue.execute([&self.cmd_list])?;
+        let src = self.render_target.copy_resource(0);
+        self.copy_list.record(
+            &self.copy_allocator,
+            |cmd: CopyCommand<CopyResource, ReadBackBuffer>| {
+                cmd.barrier([src.enter()]);
+                cmd.copy(&src, &self.read_back_buffer);
+                cmd.barrier([src.leave()]);
+            },
+        )?;
+        self.copy_queue.wait(&signal)?;
+        self.copy_queue.execute([&self.copy_list])?.wait().await?;
+        Ok(self.read_back_buffer.to_image()?)
+    }
+}
@@ -0,0 +1,394 @@
+use super::*;
+use pixel_shader::Matrix;
+
+// must match `MeshVertex` in include/hlsl_box.hlsli
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub coord: [f32; 2],
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if len > 0.0 {
+        [v[0] / len, v[1] / len, v[2] / len]
+    } else {
+        v
+    }
+}
+
+fn transform(m: &Matrix, v: [f32; 3], w: f32) -> [f32; 3] {
+    let mut ret = [0.0; 3];
+    for (i, r) in ret.iter_mut().enumerate() {
+        *r = m[0][i] * v[0] + m[1][i] * v[1] + m[2][i] * v[2] + m[3][i] * w;
+    }
+    ret
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut ret = [[0.0; 4]; 4];
+    for (c, column) in ret.iter_mut().enumerate() {
+        for (r, v) in column.iter_mut().enumerate() {
+            *v = (0..4).map(|k| a[k][r] * b[c][k]).sum();
+        }
+    }
+    ret
+}
+
+impl Mesh {
+    pub fn plane() -> Self {
+        let vertex = |x: f32, y: f32| Vertex {
+            position: [x, y, 0.0],
+            normal: [0.0, 0.0, 1.0],
+            coord: [(x + 1.0) * 0.5, (1.0 - y) * 0.5],
+        };
+        Self {
+            vertices: vec![
+                vertex(-1.0, 1.0),
+                vertex(1.0, 1.0),
+                vertex(-1.0, -1.0),
+                vertex(1.0, -1.0),
+            ],
+            indices: vec![0, 2, 1, 1, 2, 3],
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let error = |message: String| Error::LoadMesh(path.to_path_buf(), message);
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        let mesh = match extension.as_deref() {
+            Some("obj") => {
+                let file = std::fs::File::open(path).map_err(|_| Error::ReadFile(path.into()))?;
+                Self::from_obj(&mut std::io::BufReader::new(file)).map_err(error)?
+            }
+            Some("gltf" | "glb") => Self::from_gltf(path).map_err(error)?,
+            _ => return Err(error("unsupported format".to_string())),
+        };
+        if mesh.indices.is_empty() {
+            return Err(error("no triangles".to_string()));
+        }
+        Ok(mesh)
+    }
+
+    fn from_obj(reader: &mut impl std::io::BufRead) -> Result<Self, String> {
+        let options = tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ..Default::default()
+        };
+        let (models, _) =
+            tobj::load_obj_buf(reader, &options, |_| Err(tobj::LoadError::OpenFileFailed))
+                .map_err(|e| e.to_string())?;
+        let mut this = Self::default();
+        for model in models {
+            let mesh = model.mesh;
+            let base = this.vertices.len() as u32;
+            for i in 0..mesh.positions.len() / 3 {
+                this.vertices.push(Vertex {
+                    position: [
+                        mesh.positions[i * 3],
+                        mesh.positions[i * 3 + 1],
+                        mesh.positions[i * 3 + 2],
+                    ],
+                    normal: mesh
+                        .normals
+                        .get(i * 3..i * 3 + 3)
+                        .map_or([0.0; 3], |n| [n[0], n[1], n[2]]),
+                    coord: mesh
+                        .texcoords
+                        .get(i * 2..i * 2 + 2)
+                        .map_or([0.0; 2], |t| [t[0], 1.0 - t[1]]),
+                });
+            }
+            this.push_indices(base, mesh.indices, mesh.normals.is_empty())?;
+        }
+        Ok(this)
+    }
+
+    fn from_gltf(path: &Path) -> Result<Self, String> {
+        let gltf = gltf::Gltf::open(path).map_err(|e| e.to_string())?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut buffers = Vec::with_capacity(gltf.buffers().len());
+        for buffer in gltf.buffers() {
+            let data = match buffer.source() {
+                gltf::buffer::Source::Bin => gltf.blob.clone().ok_or("missing the binary chunk")?,
+                gltf::buffer::Source::Uri(uri) if uri.starts_with("data:") => {
+                    return Err("embedded buffers are not supported".to_string())
+                }
+                gltf::buffer::Source::Uri(uri) => {
+                    std::fs::read(dir.join(uri)).map_err(|_| format!("cannot read '{}'", uri))?
+                }
+            };
+            buffers.push(data);
+        }
+        let mut this = Self::default();
+        let mut nodes = match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+            Some(scene) => scene
+                .nodes()
+                .map(|node| (node, pixel_shader::IDENTITY))
+                .collect::<Vec<_>>(),
+            None => vec![],
+        };
+        while let Some((node, parent)) = nodes.pop() {
+            let matrix = multiply(&parent, &node.transform().matrix());
+            nodes.extend(node.children().map(|child| (child, matrix)));
+            let mesh = match node.mesh() {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            for primitive in mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    continue;
+                }
+                let reader = primitive.reader(|b| buffers.get(b.index()).map(|d| d.as_slice()));
+                let positions = match reader.read_positions() {
+                    Some(positions) => positions.collect::<Vec<_>>(),
+                    None => continue,
+                };
+                let normals = reader
+                    .read_normals()
+                    .map(|normals| normals.collect::<Vec<_>>())
+                    .unwrap_or_default();
+                let coords = reader
+                    .read_tex_coords(0)
+                    .map(|coords| coords.into_f32().collect::<Vec<_>>())
+                    .unwrap_or_default();
+                let indices = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect(),
+                    None => (0..positions.len() as u32).collect(),
+                };
+                let base = this.vertices.len() as u32;
+                for (i, position) in positions.into_iter().enumerate() {
+                    this.vertices.push(Vertex {
+                        position: transform(&matrix, position, 1.0),
+                        normal: normals
+                            .get(i)
+                            .map_or([0.0; 3], |n| normalize(transform(&matrix, *n, 0.0))),
+                        coord: coords.get(i).copied().unwrap_or_default(),
+                    });
+                }
+                this.push_indices(base, indices, normals.is_empty())?;
+            }
+        }
+        Ok(this)
+    }
+
+    fn push_indices(
+        &mut self,
+        base: u32,
+        indices: Vec<u32>,
+        compute_normals: bool,
+    ) -> Result<(), String> {
+        let count = self.vertices.len() - base as usize;
+        if indices.iter().any(|&i| i as usize >= count) {
+            return Err("index out of range".to_string());
+        }
+        if compute_normals {
+            for face in indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| (base + face[i]) as usize);
+                let normal = cross(
+                    sub(self.vertices[b].position, self.vertices[a].position),
+                    sub(self.vertices[c].position, self.vertices[a].position),
+                );
+                for i in [a, b, c] {
+                    let n = &mut self.vertices[i].normal;
+                    *n = [n[0] + normal[0], n[1] + normal[1], n[2] + normal[2]];
+                }
+            }
+            for v in &mut self.vertices[base as usize..] {
+                v.normal = normalize(v.normal);
+            }
+        }
+        self.indices.extend(indices.into_iter().map(|i| base + i));
+        Ok(())
+    }
+
+    pub fn bounds(&self) -> [[f32; 3]; 2] {
+        self.vertices
+            .iter()
+            .fold([[f32::MAX; 3], [f32::MIN; 3]], |[min, max], v| {
+                [
+                    [0, 1, 2].map(|i| min[i].min(v.position[i])),
+                    [0, 1, 2].map(|i| max[i].max(v.position[i])),
+                ]
+            })
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct Buffer {
+    _buffer: DefaultBuffer,
+    pub vbv: D3D12_VERTEX_BUFFER_VIEW,
+    pub ibv: D3D12_INDEX_BUFFER_VIEW,
+    indices_len: usize,
+}
+
+impl Buffer {
+    pub async fn new(
+        device: &ID3D12Device,
+        copy_queue: &CommandQueue<CopyCommandList>,
+        mesh: &Mesh,
+    ) -> Result<Self, Error> {
+        let vertices_size = std::mem::size_of_val(mesh.vertices.as_slice());
+        let indices_size = std::mem::size_of_val(mesh.indices.as_slice());
+        let size = (vertices_size + indices_size) as u64;
+        let buffer = DefaultBuffer::new("mesh::Buffer::buffer", device, size)?;
+        unsafe {
+            let uploader = UploadBuffer::new("mesh::Buffer::uploader", device, size)?;
+            {
+                let data = uploader.0.map::<u8>()?;
+                let dest = data.as_mut() as *mut u8;
+                std::ptr::copy_nonoverlapping(
+                    mesh.vertices.as_ptr() as *const u8,
+                    dest,
+                    vertices_size,
+                );
+                std::ptr::copy_nonoverlapping(
+                    mesh.indices.as_ptr() as *const u8,
+                    dest.add(vertices_size),
+                    indices_size,
+                );
+            }
+            let cmd_allocator: ID3D12CommandAllocator =
+                device.CreateCommandAllocator(D3D12_COMMAND_LIST_TYPE_COPY)?;
+            let cmd_list = CopyCommandList::new("mesh::Buffer::cmd_list", device, &cmd_allocator)?;
+            cmd_list.record(
+                &cmd_allocator,
+                |cmd: CopyCommand<UploadBuffer, DefaultBuffer>| {
+                    cmd.barrier([buffer.enter()]);
+                    cmd.copy(&uploader, &buffer);
+                    cmd.barrier([buffer.leave()]);
+                },
+            )?;
+            copy_queue.execute([&cmd_list])?.wait().await?;
+        }
+        let vbv = D3D12_VERTEX_BUFFER_VIEW {
+            BufferLocation: buffer.0.gpu_virtual_address(),
+            SizeInBytes: vertices_size as _,
+            StrideInBytes: std::mem::size_of::<Vertex>() as _,
+        };
+        let ibv = D3D12_INDEX_BUFFER_VIEW {
+            BufferLocation: buffer.0.gpu_virtual_address() + vertices_size as u64,
+            SizeInBytes: indices_size as _,
+            Format: DXGI_FORMAT_R32_UINT,
+        };
+        Ok(Self {
+            _buffer: buffer,
+            vbv,
+            ibv,
+            indices_len: mesh.indices.len(),
+        })
+    }
+
+    pub fn indices_len(&self) -> usize {
+        self.indices_len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_obj() {
+        let data = "\
+v -1.0 -1.0 0.0
+v 1.0 -1.0 0.0
+v 1.0 1.0 0.0
+v -1.0 1.0 0.0
+vt 0.0 0.0
+vt 1.0 0.0
+vt 1.0 1.0
+vt 0.0 1.0
+f 1/1 2/2 3/3 4/4
+";
+        let mesh = Mesh::from_obj(&mut data.as_bytes()).unwrap();
+        assert!(mesh.vertices.len() == 4);
+        assert!(mesh.indices == [0, 1, 2, 0, 2, 3]);
+        assert!(mesh.vertices[0].coord == [0.0, 1.0]);
+        assert!(mesh.vertices[2].coord == [1.0, 0.0]);
+        assert!(mesh.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0]));
+        assert!(mesh.bounds() == [[-1.0, -1.0, 0.0], [1.0, 1.0, 0.0]]);
+    }
+
+    #[test]
+    fn load_gltf() {
+        let dir = Path::new("./target/load_gltf_test");
+        std::fs::create_dir_all(dir).unwrap();
+        let mut bin = vec![];
+        for v in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            bin.extend(v.to_le_bytes());
+        }
+        for i in [0u16, 1, 2, 0] {
+            bin.extend(i.to_le_bytes());
+        }
+        std::fs::write(dir.join("triangle.bin"), &bin).unwrap();
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [{ "mesh": 0, "translation": [0.0, 0.0, 2.0] }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1 }] }],
+            "buffers": [{ "uri": "triangle.bin", "byteLength": 44 }],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+                { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+            ],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                  "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] },
+                { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+            ]
+        }"#;
+        let path = dir.join("triangle.gltf");
+        std::fs::write(&path, json).unwrap();
+        let mesh = Mesh::load(&path).unwrap();
+        assert!(mesh.indices == [0, 1, 2]);
+        assert!(mesh.vertices[1].position == [1.0, 0.0, 2.0]);
+        assert!(mesh.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn push_indices_out_of_range() {
+        let mut mesh = Mesh::plane();
+        let base = mesh.vertices.len() as u32 - 2;
+        assert!(mesh
+            .push_indices(base, vec![0, 1, u32::MAX], false)
+            .is_err());
+        assert!(mesh.push_indices(base, vec![2], false).is_err());
+        mesh.push_indices(base, vec![0, 1], false).unwrap();
+        assert!(mesh.indices.ends_with(&[base, base + 1]));
+    }
+
+    #[test]
+    fn load_unsupported_mesh() {
+        match Mesh::load("model.fbx") {
+            Err(Error::LoadMesh(path, _)) => assert!(path == Path::new("model.fbx")),
+            _ => panic!(),
+        }
+    }
+}
@@ -1,20 +1,418 @@
 use super::*;
 
+pub const MOUSE_LEFT: u32 = 0x1;
+pub const MOUSE_RIGHT: u32 = 0x2;
+pub const MOUSE_MIDDLE: u32 = 0x4;
+
+// column-major like HLSL constant buffers
+pub type Matrix = [[f32; 4]; 4];
+
+pub const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+// must match `Parameters` in include/hlsl_box.hlsli; only append new fields
 #[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Parameters {
     pub resolution: [f32; 2],
     pub mouse: [f32; 2],
     pub time: f32,
+    pub time_delta: f32,
+    pub frame: u32,
+    pub mouse_buttons: u32,
+    pub date: [f32; 4],
+    pub click: [f32; 2],
+    pub _padding: [f32; 2],
+    pub model: Matrix,
+    pub view: Matrix,
+    pub projection: Matrix,
+}
+
+impl Default for Parameters {
+    fn default() -> Self {
+        Self {
+            resolution: [0.0; 2],
+            mouse: [0.0; 2],
+            time: 0.0,
+            time_delta: 0.0,
+            frame: 0,
+            mouse_buttons: 0,
+            date: [0.0; 4],
+            click: [0.0; 2],
+            _padding: [0.0; 2],
+            model: IDENTITY,
+            view: IDENTITY,
+            projection: IDENTITY,
+        }
+    }
+}
+
+impl Parameters {
+    pub fn set_date(&mut self, now: chrono::DateTime<chrono::Local>) {
+        use chrono::{Datelike, Timelike};
+        let seconds = now.num_seconds_from_midnight() as f32 + now.nanosecond() as f32 * 1e-9;
+        self.date = [now.year() as _, now.month() as _, now.day() as _, seconds];
+    }
+}
+
+pub const USER_PARAMETERS_REGISTER: u32 = 1;
+const USER_PARAMETERS_SIZE: u32 = 4096;
+const PARAMETERS_SLOT_SIZE: u32 = D3D12_CONSTANT_BUFFER_DATA_PLACEMENT_ALIGNMENT;
+const KEYBOARD_REGISTER_SPACE: u32 = 1;
+const PASS_REGISTER_SPACE: u32 = 2;
+const KEYBOARD_DESCRIPTOR: usize = hlsl::CHANNEL_COUNT;
+const PASS_TABLE_DESCRIPTOR: usize = KEYBOARD_DESCRIPTOR + 1;
+const SLOT_COUNT: usize = hlsl::PASS_COUNT + 1;
+const OUTPUT_DESCRIPTOR: usize = PASS_TABLE_DESCRIPTOR + 2 * SLOT_COUNT * hlsl::PASS_COUNT;
+// DXGI_MAX_SWAP_CHAIN_BUFFERS
+const OUTPUT_COUNT: usize = 16;
+const DESCRIPTOR_COUNT: usize = OUTPUT_DESCRIPTOR + OUTPUT_COUNT;
+
+pub const FINAL_PASS: usize = hlsl::PASS_COUNT;
+
+fn is_constant_buffer(binding: &hlsl::Binding, register: u32) -> bool {
+    binding.kind == hlsl::BindingKind::ConstantBuffer
+        && binding.register == register
+        && binding.space == 0
+}
+
+pub fn user_constant_buffer(reflection: &hlsl::Reflection) -> Option<&hlsl::ConstantBuffer> {
+    reflection
+        .bindings
+        .iter()
+        .find(|b| is_constant_buffer(b, USER_PARAMETERS_REGISTER))
+        .and_then(|b| reflection.constant_buffer(&b.name))
+}
+
+fn is_channel(binding: &hlsl::Binding) -> bool {
+    let count = hlsl::CHANNEL_COUNT as u32;
+    matches!(
+        binding.kind,
+        hlsl::BindingKind::Texture | hlsl::BindingKind::Sampler
+    ) && binding.space == 0
+        && binding.register < count
+        && binding.count <= count - binding.register
+}
+
+fn is_pass(binding: &hlsl::Binding) -> bool {
+    let count = hlsl::PASS_COUNT as u32;
+    binding.space == PASS_REGISTER_SPACE
+        && match binding.kind {
+            hlsl::BindingKind::Texture => {
+                binding.register < count && binding.count <= count - binding.register
+            }
+            hlsl::BindingKind::Sampler => binding.register == 0 && binding.count == 1,
+            _ => false,
+        }
+}
+
+fn is_output(binding: &hlsl::Binding) -> bool {
+    binding.kind == hlsl::BindingKind::UnorderedAccess
+        && binding.register == 0
+        && binding.space == 0
+        && binding.count == 1
+}
+
+pub fn check_bindings(reflection: &hlsl::Reflection) -> Result<(), Error> {
+    let compute = reflection.thread_group_size.is_some();
+    let mut diagnostics = vec![];
+    for binding in reflection.bindings.iter() {
+        if is_channel(binding)
+            || is_pass(binding)
+            || (compute && is_output(binding))
+            || (binding.kind == hlsl::BindingKind::Texture
+                && binding.register == 0
+                && binding.space == KEYBOARD_REGISTER_SPACE)
+        {
+            continue;
+        }
+        let provided = if is_constant_buffer(binding, 0) {
+            std::mem::size_of::<Parameters>() as u32
+        } else if is_constant_buffer(binding, USER_PARAMETERS_REGISTER) {
+            USER_PARAMETERS_SIZE
+        } else {
+            diagnostics.push(hlsl::Diagnostic::new(
+                hlsl::Severity::Error,
+                format!("{} is not provided by hlsl_box", binding),
+            ));
+            continue;
+        };
+        let cb = match reflection.constant_buffer(&binding.name) {
+            Some(cb) => cb,
+            None => continue,
+        };
+        for v in cb.variables.iter().filter(|v| v.offset + v.size > provided) {
+            diagnostics.push(hlsl::Diagnostic::new(
+                hlsl::Severity::Error,
+                format!(
+                    "'{}' (offset {}, {} bytes) in {} is outside of the {} bytes provided by hlsl_box",
+                    v.name, v.offset, v.size, binding, provided
+                ),
+            ));
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Err(Error::Compile(diagnostics));
+    }
+    Ok(())
+}
+
+pub struct Channel {
+    image: image::DynamicImage,
+    wrap: hlsl::Wrap,
+    filter: hlsl::Filter,
+}
+
+impl Channel {
+    pub fn load(channel: &hlsl::Channel) -> Result<Option<Self>, Error> {
+        let path = match channel.path.as_ref() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let image = image::open(path).map_err(|e| Error::LoadImage(path.clone(), e.to_string()))?;
+        Ok(Some(Self {
+            image,
+            wrap: channel.wrap,
+            filter: channel.filter,
+        }))
+    }
+
+    fn size(&self) -> wita::PhysicalSize<u32> {
+        use image::GenericImageView;
+        let (width, height) = self.image.dimensions();
+        wita::PhysicalSize::new(width, height)
+    }
+
+    fn pixels(&self) -> (DXGI_FORMAT, Vec<u8>) {
+        use image::DynamicImage::*;
+        match &self.image {
+            ImageRgb32F(_) | ImageRgba32F(_) => (
+                DXGI_FORMAT_R32G32B32A32_FLOAT,
+                self.image
+                    .to_rgba32f()
+                    .iter()
+                    .flat_map(|v| v.to_ne_bytes())
+                    .collect(),
+            ),
+            ImageLuma16(_) | ImageLumaA16(_) | ImageRgb16(_) | ImageRgba16(_) => (
+                DXGI_FORMAT_R16G16B16A16_UNORM,
+                self.image
+                    .to_rgba16()
+                    .iter()
+                    .flat_map(|v| v.to_ne_bytes())
+                    .collect(),
+            ),
+            _ => (DXGI_FORMAT_R8G8B8A8_UNORM, self.image.to_rgba8().into_raw()),
+        }
+    }
+}
+
+fn texture_srv_desc(format: DXGI_FORMAT) -> D3D12_SHADER_RESOURCE_VIEW_DESC {
+    D3D12_SHADER_RESOURCE_VIEW_DESC {
+        Format: format,
+        ViewDimension: D3D12_SRV_DIMENSION_TEXTURE2D,
+        Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+        Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+            Texture2D: D3D12_TEX2D_SRV {
+                MipLevels: 1,
+                ..Default::default()
+            },
+        },
+    }
+}
+
+fn sampler_desc(wrap: hlsl::Wrap, filter: hlsl::Filter) -> D3D12_SAMPLER_DESC {
+    let address = match wrap {
+        hlsl::Wrap::Repeat => D3D12_TEXTURE_ADDRESS_MODE_WRAP,
+        hlsl::Wrap::Clamp => D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
+        hlsl::Wrap::Mirror => D3D12_TEXTURE_ADDRESS_MODE_MIRROR,
+    };
+    D3D12_SAMPLER_DESC {
+        Filter: match filter {
+            hlsl::Filter::Linear => D3D12_FILTER_MIN_MAG_MIP_LINEAR,
+            hlsl::Filter::Point => D3D12_FILTER_MIN_MAG_MIP_POINT,
+        },
+        AddressU: address,
+        AddressV: address,
+        AddressW: address,
+        MipLODBias: 0.0,
+        MaxAnisotropy: 1,
+        ComparisonFunc: D3D12_COMPARISON_FUNC_NEVER,
+        BorderColor: [0.0; 4],
+        MinLOD: 0.0,
+        MaxLOD: f32::MAX,
+    }
+}
+
+fn default_sampler_desc() -> D3D12_SAMPLER_DESC {
+    sampler_desc(hlsl::Wrap::default(), hlsl::Filter::default())
+}
+
+fn input_element(semantic: &'static [u8], format: DXGI_FORMAT) -> D3D12_INPUT_ELEMENT_DESC {
+    D3D12_INPUT_ELEMENT_DESC {
+        SemanticName: PCSTR(semantic.as_ptr()),
+        SemanticIndex: 0,
+        Format: format,
+        InputSlot: 0,
+        AlignedByteOffset: D3D12_APPEND_ALIGNED_ELEMENT,
+        InputSlotClass: D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA,
+        InstanceDataStepRate: 0,
+    }
+}
+
+unsafe fn create_root_signature(
+    device: &ID3D12Device,
+    compute: bool,
+) -> Result<ID3D12RootSignature, Error> {
+    let flags = if compute {
+        D3D12_ROOT_SIGNATURE_FLAG_NONE
+    } else {
+        D3D12_ROOT_SIGNATURE_FLAG_ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT
+            | D3D12_ROOT_SIGNATURE_FLAG_DENY_DOMAIN_SHADER_ROOT_ACCESS
+            | D3D12_ROOT_SIGNATURE_FLAG_DENY_GEOMETRY_SHADER_ROOT_ACCESS
+            | D3D12_ROOT_SIGNATURE_FLAG_DENY_HULL_SHADER_ROOT_ACCESS
+    };
+    let cbv = |register| D3D12_ROOT_PARAMETER {
+        ParameterType: D3D12_ROOT_PARAMETER_TYPE_CBV,
+        ShaderVisibility: D3D12_SHADER_VISIBILITY_ALL,
+        Anonymous: D3D12_ROOT_PARAMETER_0 {
+            Descriptor: D3D12_ROOT_DESCRIPTOR {
+                ShaderRegister: register,
+                RegisterSpace: 0,
+            },
+        },
+    };
+    let table = |ranges: &[D3D12_DESCRIPTOR_RANGE]| D3D12_ROOT_PARAMETER {
+        ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+        ShaderVisibility: D3D12_SHADER_VISIBILITY_ALL,
+        Anonymous: D3D12_ROOT_PARAMETER_0 {
+            DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE {
+                NumDescriptorRanges: ranges.len() as _,
+                pDescriptorRanges: ranges.as_ptr(),
+            },
+        },
+    };
+    let ranges = [
+        D3D12_DESCRIPTOR_RANGE {
+            RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+            NumDescriptors: hlsl::CHANNEL_COUNT as _,
+            BaseShaderRegister: 0,
+            RegisterSpace: 0,
+            OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
+        },
+        D3D12_DESCRIPTOR_RANGE {
+            RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+            NumDescriptors: 1,
+            BaseShaderRegister: 0,
+            RegisterSpace: KEYBOARD_REGISTER_SPACE,
+            OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
+        },
+    ];
+    let pass_ranges = [D3D12_DESCRIPTOR_RANGE {
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+        NumDescriptors: hlsl::PASS_COUNT as _,
+        BaseShaderRegister: 0,
+        RegisterSpace: PASS_REGISTER_SPACE,
+        OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
+    }];
+    let sampler_ranges = [D3D12_DESCRIPTOR_RANGE {
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SAMPLER,
+        NumDescriptors: hlsl::CHANNEL_COUNT as _,
+        BaseShaderRegister: 0,
+        RegisterSpace: 0,
+        OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
+    }];
+    let output_ranges = [D3D12_DESCRIPTOR_RANGE {
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_UAV,
+        NumDescriptors: 1,
+        BaseShaderRegister: 0,
+        RegisterSpace: 0,
+        OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
+    }];
+    let mut params = vec![
+        cbv(0),
+        cbv(USER_PARAMETERS_REGISTER),
+        table(&ranges),
+        table(&sampler_ranges),
+        table(&pass_ranges),
+    ];
+    if compute {
+        params.push(table(&output_ranges));
+    }
+    let static_samplers = [D3D12_STATIC_SAMPLER_DESC {
+        Filter: D3D12_FILTER_MIN_MAG_MIP_LINEAR,
+        AddressU: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
+        AddressV: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
+        AddressW: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
+        MinLOD: 0.0,
+        MaxLOD: f32::MAX,
+        ShaderVisibility: D3D12_SHADER_VISIBILITY_ALL,
+        ShaderRegister: 0,
+        RegisterSpace: PASS_REGISTER_SPACE,
+        ..Default::default()
+    }];
+    let desc = D3D12_ROOT_SIGNATURE_DESC {
+        NumParameters: params.len() as _,
+        pParameters: params.as_ptr(),
+        NumStaticSamplers: static_samplers.len() as _,
+        pStaticSamplers: static_samplers.as_ptr(),
+        Flags: flags,
+    };
+    let mut blob: Option<ID3DBlob> = None;
+    let blob = D3D12SerializeRootSignature(
+        &desc,
+        D3D_ROOT_SIGNATURE_VERSION_1_0,
+        &mut blob,
+        std::ptr::null_mut(),
+    )
+    .map(|_| blob.unwrap())?;
+    let root_signature = device.CreateRootSignature(
+        0,
+        std::slice::from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize()),
+    )?;
+    Ok(root_signature)
 }
 
 #[derive(Clone)]
-#[repr(transparent)]
-pub struct Pipeline(ID3D12PipelineState);
+pub struct Pipeline {
+    state: ID3D12PipelineState,
+    thread_group_size: Option<[u32; 3]>,
+    mesh: bool,
+}
+
+impl Pipeline {
+    pub fn uses_mesh(&self) -> bool {
+        self.mesh
+    }
+
+    pub fn thread_groups(&self, size: wita::PhysicalSize<u32>) -> Option<[u32; 3]> {
+        self.thread_group_size
+            .map(|[x, y, _]| [size.width.div_ceil(x), size.height.div_ceil(y), 1])
+    }
+}
+
+pub struct Pass {
+    pub index: usize,
+    pub pipeline: Pipeline,
+    pub user_parameters: Vec<u8>,
+}
 
 pub struct PixelShader {
     root_signature: ID3D12RootSignature,
+    compute_root_signature: ID3D12RootSignature,
     parameters: Buffer,
+    user_parameters: Buffer,
+    keyboard: ID3D12Resource,
+    channels: Vec<Option<ImageTexture>>,
+    heap: ID3D12DescriptorHeap,
+    desc_size: usize,
+    sampler_heap: ID3D12DescriptorHeap,
     vs: hlsl::Blob,
+    mesh_vs: hlsl::Blob,
 }
 
 impl PixelShader {
@@ -24,63 +422,115 @@ impl PixelShader {
         shader_model: hlsl::ShaderModel,
     ) -> Result<Self, Error> {
         unsafe {
-            let root_signature: ID3D12RootSignature = {
-                let params = [D3D12_ROOT_PARAMETER {
-                    ParameterType: D3D12_ROOT_PARAMETER_TYPE_CBV,
-                    ShaderVisibility: D3D12_SHADER_VISIBILITY_ALL,
-                    Anonymous: D3D12_ROOT_PARAMETER_0 {
-                        Descriptor: D3D12_ROOT_DESCRIPTOR {
-                            ShaderRegister: 0,
-                            RegisterSpace: 0,
-                        },
-                    },
-                }];
-                let desc = D3D12_ROOT_SIGNATURE_DESC {
-                    NumParameters: params.len() as _,
-                    pParameters: params.as_ptr(),
-                    NumStaticSamplers: 0,
-                    pStaticSamplers: std::ptr::null(),
-                    Flags: D3D12_ROOT_SIGNATURE_FLAG_ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT
-                        | D3D12_ROOT_SIGNATURE_FLAG_DENY_DOMAIN_SHADER_ROOT_ACCESS
-                        | D3D12_ROOT_SIGNATURE_FLAG_DENY_GEOMETRY_SHADER_ROOT_ACCESS
-                        | D3D12_ROOT_SIGNATURE_FLAG_DENY_HULL_SHADER_ROOT_ACCESS,
-                };
-                let mut blob: Option<ID3DBlob> = None;
-                let blob = D3D12SerializeRootSignature(
-                    &desc,
-                    D3D_ROOT_SIGNATURE_VERSION_1_0,
-                    &mut blob,
-                    std::ptr::null_mut(),
-                )
-                .map(|_| blob.unwrap())?;
-                device.CreateRootSignature(
-                    0,
-                    std::slice::from_raw_parts(
-                        blob.GetBufferPointer() as *const u8,
-                        blob.GetBufferSize(),
-                    ),
-                )?
-            };
+            let root_signature = create_root_signature(device, false)?;
             root_signature.SetName("PixelShader::root_signature")?;
+            let compute_root_signature = create_root_signature(device, true)?;
+            compute_root_signature.SetName("PixelShader::compute_root_signature")?;
             let parameters = Buffer::new(
                 "PixelShader::parameters",
                 device,
                 HeapProperties::new(D3D12_HEAP_TYPE_UPLOAD),
-                std::mem::size_of::<Parameters>() as _,
+                (PARAMETERS_SLOT_SIZE as usize * SLOT_COUNT) as _,
+                D3D12_RESOURCE_STATE_GENERIC_READ,
+                None,
+            )?;
+            let user_parameters = Buffer::new(
+                "PixelShader::user_parameters",
+                device,
+                HeapProperties::new(D3D12_HEAP_TYPE_UPLOAD),
+                (USER_PARAMETERS_SIZE as usize * SLOT_COUNT) as _,
                 D3D12_RESOURCE_STATE_GENERIC_READ,
                 None,
             )?;
+            let keyboard: ID3D12Resource = {
+                let heap_props = D3D12_HEAP_PROPERTIES {
+                    Type: D3D12_HEAP_TYPE_CUSTOM,
+                    CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_WRITE_COMBINE,
+                    MemoryPoolPreference: D3D12_MEMORY_POOL_L0,
+                    CreationNodeMask: 1,
+                    VisibleNodeMask: 1,
+                };
+                let desc = D3D12_RESOURCE_DESC {
+                    Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+                    Width: window::KEYBOARD_WIDTH as _,
+                    Height: window::KEYBOARD_HEIGHT,
+                    DepthOrArraySize: 1,
+                    MipLevels: 1,
+                    Format: DXGI_FORMAT_R8_UNORM,
+                    Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+                    SampleDesc: SampleDesc::default().into(),
+                    ..Default::default()
+                };
+                let mut resource: Option<ID3D12Resource> = None;
+                device
+                    .CreateCommittedResource(
+                        &heap_props,
+                        D3D12_HEAP_FLAG_NONE,
+                        &desc,
+                        D3D12_RESOURCE_STATE_COMMON,
+                        std::ptr::null(),
+                        &mut resource,
+                    )
+                    .map(|_| resource.unwrap())?
+            };
+            keyboard.SetName("PixelShader::keyboard")?;
+            keyboard.Map(0, std::ptr::null(), std::ptr::null_mut())?;
+            let heap: ID3D12DescriptorHeap =
+                device.CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
+                    Type: D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
+                    NumDescriptors: DESCRIPTOR_COUNT as _,
+                    Flags: D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
+                    ..Default::default()
+                })?;
+            heap.SetName("PixelShader::heap")?;
+            let desc_size = device
+                .GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV)
+                as usize;
+            let sampler_heap: ID3D12DescriptorHeap =
+                device.CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
+                    Type: D3D12_DESCRIPTOR_HEAP_TYPE_SAMPLER,
+                    NumDescriptors: hlsl::CHANNEL_COUNT as _,
+                    Flags: D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
+                    ..Default::default()
+                })?;
+            sampler_heap.SetName("PixelShader::sampler_heap")?;
             let vs = compiler.compile_from_str(
                 include_str!("../shader/plane.hlsl"),
                 "main",
                 hlsl::Target::VS(shader_model),
                 &[],
             )?;
-            Ok(Self {
+            let mesh_vs = compiler.compile_from_str(
+                include_str!("../shader/mesh.hlsl"),
+                "main",
+                hlsl::Target::VS(shader_model),
+                &[],
+            )?;
+            let this = Self {
                 root_signature,
+                compute_root_signature,
                 parameters,
+                user_parameters,
+                keyboard,
+                channels: vec![],
+                heap,
+                desc_size,
+                sampler_heap,
                 vs,
-            })
+                mesh_vs,
+            };
+            device.CreateShaderResourceView(
+                &this.keyboard,
+                &texture_srv_desc(DXGI_FORMAT_R8_UNORM),
+                this.descriptor(KEYBOARD_DESCRIPTOR),
+            );
+            for i in 0..hlsl::CHANNEL_COUNT {
+                this.write_channel(device, i, None, &default_sampler_desc());
+            }
+            this.set_pass_buffers(device, None);
+            this.set_outputs(device, None);
+            this.update_keyboard(window::Keyboard::default().data())?;
+            Ok(this)
         }
     }
 
@@ -89,28 +539,44 @@ impl PixelShader {
         name: &str,
         device: &ID3D12Device,
         ps: &hlsl::Blob,
+        format: DXGI_FORMAT,
+    ) -> Result<Pipeline, Error> {
+        let input_elements = [
+            input_element(b"POSITION\0", DXGI_FORMAT_R32G32B32_FLOAT),
+            input_element(b"TEXCOORD\0", DXGI_FORMAT_R32G32_FLOAT),
+        ];
+        self.create_graphics_pipeline(name, device, &self.vs, ps, format, &input_elements, false)
+    }
+
+    pub fn create_mesh_pipeline(
+        &self,
+        name: &str,
+        device: &ID3D12Device,
+        vs: Option<&hlsl::Blob>,
+        ps: &hlsl::Blob,
+        format: DXGI_FORMAT,
+    ) -> Result<Pipeline, Error> {
+        let input_elements = [
+            input_element(b"POSITION\0", DXGI_FORMAT_R32G32B32_FLOAT),
+            input_element(b"NORMAL\0", DXGI_FORMAT_R32G32B32_FLOAT),
+            input_element(b"TEXCOORD\0", DXGI_FORMAT_R32G32_FLOAT),
+        ];
+        let vs = vs.unwrap_or(&self.mesh_vs);
+        self.create_graphics_pipeline(name, device, vs, ps, format, &input_elements, true)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_graphics_pipeline(
+        &self,
+        name: &str,
+        device: &ID3D12Device,
+        vs: &hlsl::Blob,
+        ps: &hlsl::Blob,
+        format: DXGI_FORMAT,
+        input_elements: &[D3D12_INPUT_ELEMENT_DESC],
+        mesh: bool,
     ) -> Result<Pipeline, Error> {
         unsafe {
-            let input_elements = [
-                D3D12_INPUT_ELEMENT_DESC {
-                    SemanticName: PCSTR(b"POSITION\0".as_ptr()),
-                    SemanticIndex: 0,
-                    Format: DXGI_FORMAT_R32G32B32_FLOAT,
-                    InputSlot: 0,
-                    AlignedByteOffset: 0,
-                    InputSlotClass: D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA,
-                    InstanceDataStepRate: 0,
-                },
-                D3D12_INPUT_ELEMENT_DESC {
-                    SemanticName: PCSTR(b"TEXCOORD\0".as_ptr()),
-                    SemanticIndex: 0,
-                    Format: DXGI_FORMAT_R32G32_FLOAT,
-                    InputSlot: 0,
-                    AlignedByteOffset: D3D12_APPEND_ALIGNED_ELEMENT,
-                    InputSlotClass: D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA,
-                    InstanceDataStepRate: 0,
-                },
-            ];
             let mut render_target_blend = [D3D12_RENDER_TARGET_BLEND_DESC::default(); 8];
             render_target_blend[0] = D3D12_RENDER_TARGET_BLEND_DESC {
                 BlendEnable: false.into(),
@@ -125,10 +591,10 @@ impl PixelShader {
                 RenderTargetWriteMask: D3D12_COLOR_WRITE_ENABLE_ALL.0 as _,
             };
             let mut rtv_formats = [DXGI_FORMAT_UNKNOWN; 8];
-            rtv_formats[0] = DXGI_FORMAT_R8G8B8A8_UNORM;
+            rtv_formats[0] = format;
             let desc = D3D12_GRAPHICS_PIPELINE_STATE_DESC {
                 pRootSignature: Some(self.root_signature.clone()),
-                VS: self.vs.as_shader_bytecode(),
+                VS: vs.as_shader_bytecode(),
                 PS: ps.as_shader_bytecode(),
                 PrimitiveTopologyType: D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE,
                 InputLayout: D3D12_INPUT_LAYOUT_DESC {
@@ -141,9 +607,25 @@ impl PixelShader {
                 },
                 RasterizerState: D3D12_RASTERIZER_DESC {
                     FillMode: D3D12_FILL_MODE_SOLID,
-                    CullMode: D3D12_CULL_MODE_BACK,
+                    CullMode: if mesh {
+                        D3D12_CULL_MODE_NONE
+                    } else {
+                        D3D12_CULL_MODE_BACK
+                    },
+                    DepthClipEnable: mesh.into(),
                     ..Default::default()
                 },
+                DepthStencilState: D3D12_DEPTH_STENCIL_DESC {
+                    DepthEnable: mesh.into(),
+                    DepthWriteMask: D3D12_DEPTH_WRITE_MASK_ALL,
+                    DepthFunc: D3D12_COMPARISON_FUNC_LESS,
+                    ..Default::default()
+                },
+                DSVFormat: if mesh {
+                    DepthBuffer::FORMAT
+                } else {
+                    DXGI_FORMAT_UNKNOWN
+                },
                 NumRenderTargets: 1,
                 RTVFormats: rtv_formats,
                 SampleMask: u32::MAX,
@@ -152,22 +634,275 @@ impl PixelShader {
             };
             let pipeline: ID3D12PipelineState = device.CreateGraphicsPipelineState(&desc)?;
             pipeline.SetName(name)?;
-            Ok(Pipeline(pipeline))
+            Ok(Pipeline {
+                state: pipeline,
+                thread_group_size: None,
+                mesh,
+            })
         }
     }
 
-    pub fn apply<'a, 'b>(&'a self, pipeline: &'b Pipeline, parameters: &Parameters) -> State<'a>
+    pub fn create_compute_pipeline(
+        &self,
+        name: &str,
+        device: &ID3D12Device,
+        cs: &hlsl::Blob,
+        thread_group_size: [u32; 3],
+    ) -> Result<Pipeline, Error> {
+        unsafe {
+            let desc = D3D12_COMPUTE_PIPELINE_STATE_DESC {
+                pRootSignature: Some(self.compute_root_signature.clone()),
+                CS: cs.as_shader_bytecode(),
+                ..Default::default()
+            };
+            let pipeline: ID3D12PipelineState = device.CreateComputePipelineState(&desc)?;
+            pipeline.SetName(name)?;
+            Ok(Pipeline {
+                state: pipeline,
+                thread_group_size: Some(thread_group_size),
+                mesh: false,
+            })
+        }
+    }
+
+    pub fn update_keyboard(&self, data: &[u8]) -> Result<(), Error> {
+        unsafe {
+            self.keyboard.WriteToSubresource(
+                0,
+                std::ptr::null(),
+                data.as_ptr() as _,
+                window::KEYBOARD_WIDTH,
+                data.len() as _,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub(super) async fn set_channels(
+        &mut self,
+        device: &ID3D12Device,
+        copy_queue: &CommandQueue<CopyCommandList>,
+        channels: &[Option<Channel>],
+    ) -> Result<(), Error> {
+        let mut textures = Vec::with_capacity(hlsl::CHANNEL_COUNT);
+        for i in 0..hlsl::CHANNEL_COUNT {
+            let texture = match channels.get(i).and_then(|c| c.as_ref()) {
+                Some(channel) => Some(upload_image(device, copy_queue, i, channel).await?),
+                None => None,
+            };
+            textures.push(texture);
+        }
+        for (i, texture) in textures.iter().enumerate() {
+            let sampler = channels
+                .get(i)
+                .and_then(|c| c.as_ref())
+                .map_or_else(default_sampler_desc, |c| sampler_desc(c.wrap, c.filter));
+            self.write_channel(device, i, texture.as_ref(), &sampler);
+        }
+        self.channels = textures;
+        Ok(())
+    }
+
+    pub(super) fn set_pass_buffers(&self, device: &ID3D12Device, buffers: Option<&PassBuffers>) {
+        for frame in 0..2 {
+            for reader in 0..SLOT_COUNT {
+                let table = pass_table(frame, reader);
+                for pass in 0..hlsl::PASS_COUNT {
+                    let texture = buffers
+                        .and_then(|b| b.texture(pass, PassBuffers::read_side(frame, reader, pass)));
+                    unsafe {
+                        device.CreateShaderResourceView(
+                            texture.map(|t| t.handle().clone()),
+                            &texture_srv_desc(PassBuffers::FORMAT),
+                            self.descriptor(table + pass),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    pub(super) fn set_outputs(&self, device: &ID3D12Device, buffers: Option<&RenderTargetBuffers>) {
+        let desc = D3D12_UNORDERED_ACCESS_VIEW_DESC {
+            Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+            ViewDimension: D3D12_UAV_DIMENSION_TEXTURE2D,
+            Anonymous: D3D12_UNORDERED_ACCESS_VIEW_DESC_0 {
+                Texture2D: D3D12_TEX2D_UAV::default(),
+            },
+        };
+        for i in 0..OUTPUT_COUNT {
+            let texture = buffers.and_then(|b| b.texture(i));
+            unsafe {
+                device.CreateUnorderedAccessView(
+                    texture.map(|t| t.handle().clone()),
+                    None::<ID3D12Resource>,
+                    &desc,
+                    self.descriptor(OUTPUT_DESCRIPTOR + i),
+                );
+            }
+        }
+    }
+
+    pub(super) fn output(&self, buffers: &RenderTargetBuffers, index: usize) -> OutputResource {
+        assert!(index < OUTPUT_COUNT);
+        unsafe {
+            let mut handle = self.heap.GetGPUDescriptorHandleForHeapStart();
+            handle.ptr += ((OUTPUT_DESCRIPTOR + index) * self.desc_size) as u64;
+            OutputResource {
+                resource: buffers.texture(index).unwrap().handle().clone(),
+                handle,
+            }
+        }
+    }
+
+    fn descriptor(&self, index: usize) -> D3D12_CPU_DESCRIPTOR_HANDLE {
+        unsafe {
+            let mut handle = self.heap.GetCPUDescriptorHandleForHeapStart();
+            handle.ptr += index * self.desc_size;
+            handle
+        }
+    }
+
+    fn write_channel(
+        &self,
+        device: &ID3D12Device,
+        index: usize,
+        texture: Option<&ImageTexture>,
+        sampler: &D3D12_SAMPLER_DESC,
+    ) {
+        unsafe {
+            let format = texture.map_or(DXGI_FORMAT_R8G8B8A8_UNORM, |t| {
+                t.resource().GetDesc().Format
+            });
+            device.CreateShaderResourceView(
+                texture.map(|t| t.resource().clone()),
+                &texture_srv_desc(format),
+                self.descriptor(index),
+            );
+            let mut handle = self.sampler_heap.GetCPUDescriptorHandleForHeapStart();
+            handle.ptr += index
+                * device.GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_SAMPLER)
+                    as usize;
+            device.CreateSampler(sampler, handle);
+        }
+    }
+
+    pub fn apply<'a, 'b>(
+        &'a self,
+        pipeline: &'b Pipeline,
+        parameters: &Parameters,
+        user_parameters: &[u8],
+        pass: usize,
+        frame: usize,
+    ) -> State<'a>
     where
         'b: 'a,
     {
+        let parameters_offset = pass * PARAMETERS_SLOT_SIZE as usize;
+        let user_parameters_offset = pass * USER_PARAMETERS_SIZE as usize;
         unsafe {
-            let data = self.parameters.map().unwrap();
-            std::ptr::copy_nonoverlapping(parameters, data.as_mut(), 1);
+            let data = self.parameters.map::<u8>().unwrap();
+            std::ptr::copy_nonoverlapping(
+                parameters,
+                (data.as_mut() as *mut u8).add(parameters_offset) as *mut Parameters,
+                1,
+            );
+            let data = self.user_parameters.map::<u8>().unwrap();
+            std::ptr::copy_nonoverlapping(
+                user_parameters.as_ptr(),
+                (data.as_mut() as *mut u8).add(user_parameters_offset),
+                user_parameters.len().min(USER_PARAMETERS_SIZE as _),
+            );
         }
+        let passes = unsafe {
+            let mut handle = self.heap.GetGPUDescriptorHandleForHeapStart();
+            handle.ptr += (pass_table(frame, pass) * self.desc_size) as u64;
+            handle
+        };
         State {
-            root_signature: &self.root_signature,
+            root_signature: if pipeline.thread_group_size.is_some() {
+                &self.compute_root_signature
+            } else {
+                &self.root_signature
+            },
             pipeline,
-            parameters: self.parameters.gpu_virtual_address(),
+            parameters: self.parameters.gpu_virtual_address() + parameters_offset as u64,
+            user_parameters: self.user_parameters.gpu_virtual_address()
+                + user_parameters_offset as u64,
+            heap: &self.heap,
+            sampler_heap: &self.sampler_heap,
+            passes,
+        }
+    }
+}
+
+fn pass_table(frame: usize, reader: usize) -> usize {
+    PASS_TABLE_DESCRIPTOR + ((frame % 2) * SLOT_COUNT + reader) * hlsl::PASS_COUNT
+}
+
+async fn upload_image(
+    device: &ID3D12Device,
+    copy_queue: &CommandQueue<CopyCommandList>,
+    index: usize,
+    channel: &Channel,
+) -> Result<ImageTexture, Error> {
+    let size = channel.size();
+    let (format, data) = channel.pixels();
+    let texture = ImageTexture::new(
+        &format!("PixelShader::channels[{}]", index),
+        device,
+        size,
+        format,
+    )?;
+    unsafe {
+        let uploader = {
+            let uploader =
+                UploadBuffer::new("PixelShader::uploader", device, texture.upload_size())?;
+            let mapped = uploader.0.map::<u8>()?;
+            let dest = mapped.as_mut() as *mut u8;
+            let footprint = texture.footprint();
+            let row_size = data.len() / size.height as usize;
+            for (y, row) in data.chunks_exact(row_size).enumerate() {
+                std::ptr::copy_nonoverlapping(
+                    row.as_ptr(),
+                    dest.add(footprint.Offset as usize + y * footprint.Footprint.RowPitch as usize),
+                    row_size,
+                );
+            }
+            std::mem::drop(mapped);
+            uploader
+        };
+        let cmd_allocator: ID3D12CommandAllocator =
+            device.CreateCommandAllocator(D3D12_COMMAND_LIST_TYPE_COPY)?;
+        let cmd_list = CopyCommandList::new("PixelShader::cmd_list", device, &cmd_allocator)?;
+        cmd_list.record(
+            &cmd_allocator,
+            |cmd: CopyCommand<UploadBuffer, ImageTexture>| {
+                cmd.barrier([texture.enter()]);
+                cmd.copy(&uploader, &texture);
+                cmd.barrier([texture.leave()]);
+            },
+        )?;
+        copy_queue.execute([&cmd_list])?.wait().await?;
+    }
+    Ok(texture)
+}
+
+pub struct OutputResource {
+    resource: ID3D12Resource,
+    handle: D3D12_GPU_DESCRIPTOR_HANDLE,
+}
+
+impl Resource for OutputResource {
+    fn resource(&self) -> &ID3D12Resource {
+        &self.resource
+    }
+}
+
+impl UnorderedAccess for OutputResource {
+    fn record(&self, cmd_list: &ID3D12GraphicsCommandList) {
+        unsafe {
+            cmd_list.SetComputeRootDescriptorTable(5, self.handle);
         }
     }
 }
@@ -176,14 +911,52 @@ pub struct State<'a> {
     root_signature: &'a ID3D12RootSignature,
     pipeline: &'a Pipeline,
     parameters: u64,
+    user_parameters: u64,
+    heap: &'a ID3D12DescriptorHeap,
+    sampler_heap: &'a ID3D12DescriptorHeap,
+    passes: D3D12_GPU_DESCRIPTOR_HANDLE,
 }
 
 impl<'a> Shader for State<'a> {
     fn record(&self, cmd_list: &ID3D12GraphicsCommandList) {
+        if self.pipeline.thread_group_size.is_some() {
+            self.record_compute(cmd_list);
+            return;
+        }
         unsafe {
             cmd_list.SetGraphicsRootSignature(self.root_signature);
-            cmd_list.SetPipelineState(&self.pipeline.0);
+            cmd_list.SetPipelineState(&self.pipeline.state);
             cmd_list.SetGraphicsRootConstantBufferView(0, self.parameters);
+            cmd_list.SetGraphicsRootConstantBufferView(1, self.user_parameters);
+            cmd_list
+                .SetDescriptorHeaps(&[Some(self.heap.clone()), Some(self.sampler_heap.clone())]);
+            cmd_list
+                .SetGraphicsRootDescriptorTable(2, self.heap.GetGPUDescriptorHandleForHeapStart());
+            cmd_list.SetGraphicsRootDescriptorTable(
+                3,
+                self.sampler_heap.GetGPUDescriptorHandleForHeapStart(),
+            );
+            cmd_list.SetGraphicsRootDescriptorTable(4, self.passes);
+        }
+    }
+}
+
+impl<'a> State<'a> {
+    fn record_compute(&self, cmd_list: &ID3D12GraphicsCommandList) {
+        unsafe {
+            cmd_list.SetComputeRootSignature(self.root_signature);
+            cmd_list.SetPipelineState(&self.pipeline.state);
+            cmd_list.SetComputeRootConstantBufferView(0, self.parameters);
+            cmd_list.SetComputeRootConstantBufferView(1, self.user_parameters);
+            cmd_list
+                .SetDescriptorHeaps(&[Some(self.heap.clone()), Some(self.sampler_heap.clone())]);
+            cmd_list
+                .SetComputeRootDescriptorTable(2, self.heap.GetGPUDescriptorHandleForHeapStart());
+            cmd_list.SetComputeRootDescriptorTable(
+                3,
+                self.sampler_heap.GetGPUDescriptorHandleForHeapStart(),
+            );
+            cmd_list.SetComputeRootDescriptorTable(4, self.passes);
         }
     }
 }
@@ -0,0 +1,247 @@
+use super::*;
+
+pub(super) struct Scene {
+    pixel_shader: PixelShader,
+    depth_buffer: DepthBuffer,
+    filling_plane: plane::Buffer,
+    pass_buffers: PassBuffers,
+    pass_frame: Cell<usize>,
+    pass_cleared: Cell<bool>,
+    mesh: Option<mesh::Buffer>,
+}
+
+impl Scene {
+    pub async fn new(
+        device: &ID3D12Device,
+        copy_queue: &CommandQueue<CopyCommandList>,
+        compiler: &hlsl::Compiler,
+        shader_model: hlsl::ShaderModel,
+        render_target: &RenderTargetBuffers,
+    ) -> Result<Self, Error> {
+        let pixel_shader = PixelShader::new(device, compiler, shader_model)?;
+        pixel_shader.set_outputs(device, Some(render_target));
+        Ok(Self {
+            pixel_shader,
+            depth_buffer: DepthBuffer::new(device, render_target.size())?,
+            filling_plane: plane::Buffer::new(device, copy_queue).await?,
+            pass_buffers: PassBuffers::new(device, [None; hlsl::PASS_COUNT])?,
+            pass_frame: Cell::new(0),
+            pass_cleared: Cell::new(false),
+            mesh: None,
+        })
+    }
+
+    pub fn filling_plane(&self) -> &plane::Buffer {
+        &self.filling_plane
+    }
+
+    pub fn recreate_pixel_shader(
+        &mut self,
+        device: &ID3D12Device,
+        compiler: &hlsl::Compiler,
+        shader_model: hlsl::ShaderModel,
+        render_target: &RenderTargetBuffers,
+    ) -> Result<(), Error> {
+        let pixel_shader = PixelShader::new(device, compiler, shader_model)?;
+        pixel_shader.set_pass_buffers(device, Some(&self.pass_buffers));
+        self.pixel_shader = pixel_shader;
+        self.set_render_target(device, render_target)
+    }
+
+    pub fn set_render_target(
+        &mut self,
+        device: &ID3D12Device,
+        render_target: &RenderTargetBuffers,
+    ) -> Result<(), Error> {
+        self.pixel_shader.set_outputs(device, Some(render_target));
+        self.depth_buffer = DepthBuffer::new(device, render_target.size())?;
+        Ok(())
+    }
+
+    pub fn create_pixel_shader_pipeline(
+        &self,
+        name: &str,
+        device: &ID3D12Device,
+        ps: &hlsl::Blob,
+        reflection: &hlsl::Reflection,
+        vs: Option<(&hlsl::Blob, &hlsl::Reflection)>,
+        mesh: bool,
+    ) -> Result<Pipeline, Error> {
+        pixel_shader::check_bindings(reflection)?;
+        if let Some(thread_group_size) = reflection.thread_group_size {
+            return self
+                .pixel_shader
+                .create_compute_pipeline(name, device, ps, thread_group_size);
+        }
+        if let Some((_, reflection)) = vs {
+            pixel_shader::check_bindings(reflection)?;
+        }
+        if mesh {
+            return self.pixel_shader.create_mesh_pipeline(
+                name,
+                device,
+                vs.map(|(blob, _)| blob),
+                ps,
+                DXGI_FORMAT_R8G8B8A8_UNORM,
+            );
+        }
+        self.pixel_shader
+            .create_pipeline(name, device, ps, DXGI_FORMAT_R8G8B8A8_UNORM)
+    }
+
+    pub fn create_pass_pipeline(
+        &self,
+        name: &str,
+        device: &ID3D12Device,
+        ps: &hlsl::Blob,
+        reflection: &hlsl::Reflection,
+    ) -> Result<Pipeline, Error> {
+        if reflection.thread_group_size.is_some() {
+            return Err(Error::Compile(vec![hlsl::Diagnostic::new(
+                hlsl::Severity::Error,
+                "compute shaders cannot be used as passes".to_string(),
+            )]));
+        }
+        pixel_shader::check_bindings(reflection)?;
+        self.pixel_shader
+            .create_pipeline(name, device, ps, PassBuffers::FORMAT)
+    }
+
+    pub fn pass_sizes(&self) -> &[Option<wita::PhysicalSize<u32>>; hlsl::PASS_COUNT] {
+        self.pass_buffers.sizes()
+    }
+
+    pub fn set_passes(
+        &mut self,
+        device: &ID3D12Device,
+        sizes: [Option<wita::PhysicalSize<u32>>; hlsl::PASS_COUNT],
+    ) -> Result<(), Error> {
+        let pass_buffers = PassBuffers::new(device, sizes)?;
+        self.pixel_shader
+            .set_pass_buffers(device, Some(&pass_buffers));
+        self.pass_buffers = pass_buffers;
+        self.clear_passes();
+        Ok(())
+    }
+
+    pub fn clear_passes(&self) {
+        self.pass_frame.set(0);
+        self.pass_cleared.set(false);
+    }
+
+    pub async fn set_mesh(
+        &mut self,
+        device: &ID3D12Device,
+        copy_queue: &CommandQueue<CopyCommandList>,
+        mesh: Option<&mesh::Mesh>,
+    ) -> Result<(), Error> {
+        self.mesh = match mesh {
+            Some(mesh) => Some(mesh::Buffer::new(device, copy_queue, mesh).await?),
+            None => None,
+        };
+        Ok(())
+    }
+
+    pub async fn set_channels(
+        &mut self,
+        device: &ID3D12Device,
+        copy_queue: &CommandQueue<CopyCommandList>,
+        channels: &[Option<pixel_shader::Channel>],
+    ) -> Result<(), Error> {
+        self.pixel_shader
+            .set_channels(device, copy_queue, channels)
+            .await
+    }
+
+    pub fn update_keyboard(&self, data: &[u8]) -> Result<(), Error> {
+        self.pixel_shader.update_keyboard(data)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn record(
+        &self,
+        cmd: &DirectCommand,
+        render_target: &RenderTargetBuffers,
+        index: usize,
+        ps: Option<&Pipeline>,
+        parameters: Option<&pixel_shader::Parameters>,
+        user_parameters: &[u8],
+        passes: &[pixel_shader::Pass],
+    ) {
+        if !self.pass_cleared.replace(true) {
+            for pass in 0..hlsl::PASS_COUNT {
+                for side in 0..2 {
+                    if let Some(target) = self.pass_buffers.target(pass, side) {
+                        cmd.barrier([target.enter()]);
+                        cmd.clear(&target, [0.0, 0.0, 0.0, 0.0]);
+                        cmd.barrier([target.leave()]);
+                    }
+                }
+            }
+        }
+        let (ps, parameters) = match (ps, parameters) {
+            (Some(ps), Some(parameters)) => (ps, parameters),
+            _ => return,
+        };
+        let frame = self.pass_frame.get();
+        for pass in passes {
+            let side = PassBuffers::write_side(frame);
+            let target = match self.pass_buffers.target(pass.index, side) {
+                Some(target) => target,
+                None => continue,
+            };
+            let parameters = pixel_shader::Parameters {
+                resolution: [target.size.width as _, target.size.height as _],
+                ..*parameters
+            };
+            let shader = self.pixel_shader.apply(
+                &pass.pipeline,
+                &parameters,
+                &pass.user_parameters,
+                pass.index,
+                frame,
+            );
+            cmd.barrier([target.enter()]);
+            cmd.draw(&shader, &target, &self.filling_plane);
+            cmd.barrier([target.leave()]);
+        }
+        let frame = if passes.is_empty() {
+            frame.wrapping_sub(1)
+        } else {
+            self.pass_frame.set(frame.wrapping_add(1));
+            frame
+        };
+        let shader = self.pixel_shader.apply(
+            ps,
+            parameters,
+            user_parameters,
+            pixel_shader::FINAL_PASS,
+            frame,
+        );
+        let target = render_target.target(index);
+        cmd.barrier([target.enter()]);
+        let groups = ps.thread_groups(render_target.size());
+        let mesh = self.mesh.as_ref().filter(|_| ps.uses_mesh());
+        match (groups, mesh) {
+            (Some(groups), _) => {
+                let output = self.pixel_shader.output(render_target, index);
+                cmd.clear(&target, [0.0, 0.0, 0.0, 0.0]);
+                cmd.barrier([target.leave()]);
+                cmd.barrier([output.enter()]);
+                cmd.dispatch(&shader, &output, groups);
+                cmd.barrier([output.leave()]);
+            }
+            (None, Some(mesh)) => {
+                let target = self.depth_buffer.target(target);
+                cmd.clear(&target, [0.0, 0.0, 0.0, 0.0]);
+                cmd.draw_mesh(&shader, &target, mesh);
+                cmd.barrier([target.leave()]);
+            }
+            (None, None) => {
+                cmd.clear(&target, [0.0, 0.0, 0.0, 0.0]);
+                cmd.draw(&shader, &target, &self.filling_plane);
+                cmd.barrier([target.leave()]);
+            }
+        }
+    }
+}
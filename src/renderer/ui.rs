@@ -163,6 +163,7 @@ impl Ui {
                     device,
                     size.width as _,
                     size.height as _,
+                    DXGI_FORMAT_R8G8B8A8_UNORM,
                     D3D12_RESOURCE_STATE_COMMON,
                     None,
                     Some(
@@ -238,6 +238,7 @@ impl Texture2D {
         device: &ID3D12Device,
         width: u64,
         height: u32,
+        format: DXGI_FORMAT,
         init_state: D3D12_RESOURCE_STATES,
         heap_flags: Option<D3D12_HEAP_FLAGS>,
         flags: Option<D3D12_RESOURCE_FLAGS>,
@@ -250,7 +251,7 @@ impl Texture2D {
             Height: height,
             DepthOrArraySize: 1,
             MipLevels: 1,
-            Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+            Format: format,
             Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
             Flags: flags.unwrap_or(D3D12_RESOURCE_FLAG_NONE),
             SampleDesc: SampleDesc::default().into(),
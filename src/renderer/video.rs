@@ -4,6 +4,7 @@ use tokio::sync::mpsc;
 use windows::Win32::Media::MediaFoundation::*;
 
 const MF_VERSION: u32 = (MF_SDK_VERSION << 16) | 0x0070;
+pub const BIT_RATE: u32 = 1_500_000;
 
 struct Context;
 
@@ -208,6 +209,37 @@ impl Timer {
     }
 }
 
+pub struct VideoFile {
+    writer: Writer,
+    frame: u64,
+    _context: Context,
+}
+
+impl VideoFile {
+    pub fn new(
+        path: impl AsRef<Path>,
+        resolution: wita::PhysicalSize<u32>,
+        fps: u32,
+    ) -> anyhow::Result<Self> {
+        let context = Context::new()?;
+        Ok(Self {
+            writer: context.create_writer(path, resolution, fps, BIT_RATE)?,
+            frame: 0,
+            _context: context,
+        })
+    }
+
+    pub fn write(&mut self, img: &image::RgbaImage) -> anyhow::Result<()> {
+        self.writer.write(img, self.frame)?;
+        self.frame += 1;
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), Error> {
+        self.writer.finalize()
+    }
+}
+
 pub struct Video {
     context: Context,
     worker: Option<Worker>,
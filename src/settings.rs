@@ -1,7 +1,8 @@
 use crate::*;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 const DEFAULT_SETTINGS: &str = include_str!("default_settings.toml");
 const DEFAULT_WINDOW: &str = include_str!("default_window.toml");
@@ -25,12 +26,26 @@ impl std::fmt::Display for Version {
     }
 }
 
-#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
 pub struct Resolution {
     pub width: u32,
     pub height: u32,
 }
 
+impl std::str::FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = || {
+            let (width, height) = s.split_once('x')?;
+            let width = width.trim().parse().ok()?;
+            let height = height.trim().parse().ok()?;
+            (width > 0 && height > 0).then_some(Self { width, height })
+        };
+        parse().ok_or_else(|| format!("invalid resolution '{}'", s))
+    }
+}
+
 impl From<Resolution> for wita::PhysicalSize<u32> {
     fn from(src: Resolution) -> Self {
         Self::new(src.width, src.height)
@@ -40,6 +55,9 @@ impl From<Resolution> for wita::PhysicalSize<u32> {
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct Shader {
     pub version: Option<String>,
+    pub dxc_path: Option<String>,
+    #[serde(default)]
+    pub include_paths: Vec<String>,
     pub vs_args: Vec<String>,
     pub ps_args: Vec<String>,
 }
@@ -56,6 +74,50 @@ pub struct Video {
     pub end_frame: u64,
 }
 
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Monitor {
+    pub debounce_ms: u64,
+    pub polling: bool,
+    pub polling_interval_ms: u64,
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        Self {
+            debounce_ms: 100,
+            polling: false,
+            polling_interval_ms: 500,
+        }
+    }
+}
+
+impl From<&Monitor> for reload::MonitorOptions {
+    fn from(src: &Monitor) -> Self {
+        Self {
+            polling: src.polling,
+            interval: std::time::Duration::from_millis(src.polling_interval_ms),
+            delay: std::time::Duration::from_millis(src.debounce_ms),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Cache {
+    pub enabled: bool,
+    pub max_size_mb: u64,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_size_mb: 64,
+        }
+    }
+}
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct ScrollBar {
     pub width: f32,
@@ -79,6 +141,179 @@ pub struct Appearance {
     pub scroll_bar: ScrollBar,
 }
 
+const MODIFIER_KEYS: [(&str, wita::VirtualKey); 3] = [
+    ("Shift", wita::VirtualKey::Shift),
+    ("Ctrl", wita::VirtualKey::Ctrl),
+    ("Alt", wita::VirtualKey::Alt),
+];
+
+const KEY_NAMES: &[(&str, wita::VirtualKey)] = &[
+    ("Esc", wita::VirtualKey::Esc),
+    ("Tab", wita::VirtualKey::Tab),
+    ("Space", wita::VirtualKey::Space),
+    ("Enter", wita::VirtualKey::Enter),
+    ("BackSpace", wita::VirtualKey::BackSpace),
+    ("PrintScreen", wita::VirtualKey::PrintScreen),
+    ("Pause", wita::VirtualKey::Pause),
+    ("Insert", wita::VirtualKey::Insert),
+    ("Delete", wita::VirtualKey::Delete),
+    ("Home", wita::VirtualKey::Home),
+    ("End", wita::VirtualKey::End),
+    ("PageUp", wita::VirtualKey::PageUp),
+    ("PageDown", wita::VirtualKey::PageDown),
+    ("Up", wita::VirtualKey::Up),
+    ("Down", wita::VirtualKey::Down),
+    ("Left", wita::VirtualKey::Left),
+    ("Right", wita::VirtualKey::Right),
+];
+
+fn parse_key(name: &str) -> Option<wita::VirtualKey> {
+    if let Some((_, key)) = KEY_NAMES.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+        return Some(*key);
+    }
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return c
+            .is_ascii_alphanumeric()
+            .then(|| wita::VirtualKey::Char(c.to_ascii_uppercase()));
+    }
+    name.strip_prefix(&['F', 'f'][..])?
+        .parse()
+        .ok()
+        .filter(|n| (1..=24).contains(n))
+        .map(wita::VirtualKey::F)
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Shortcut(pub Vec<wita::VirtualKey>);
+
+impl std::str::FromStr for Shortcut {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid shortcut '{}'", src);
+        if src.trim().is_empty() {
+            return Ok(Self(vec![]));
+        }
+        let mut modifiers = [false; MODIFIER_KEYS.len()];
+        let mut key = None;
+        for name in src.split('+').map(str::trim) {
+            if let Some(i) = MODIFIER_KEYS
+                .iter()
+                .position(|(n, _)| n.eq_ignore_ascii_case(name))
+            {
+                modifiers[i] = true;
+            } else if key.is_none() {
+                key = Some(parse_key(name).ok_or_else(err)?);
+            } else {
+                return Err(err());
+            }
+        }
+        let mut keys = MODIFIER_KEYS
+            .iter()
+            .zip(modifiers)
+            .filter(|(_, m)| *m)
+            .map(|((_, k), _)| *k)
+            .collect::<Vec<_>>();
+        keys.push(key.ok_or_else(err)?);
+        Ok(Self(keys))
+    }
+}
+
+impl TryFrom<String> for Shortcut {
+    type Error = String;
+
+    fn try_from(src: String) -> Result<Self, Self::Error> {
+        src.parse()
+    }
+}
+
+impl From<Shortcut> for String {
+    fn from(src: Shortcut) -> Self {
+        src.to_string()
+    }
+}
+
+impl std::fmt::Display for Shortcut {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let names = self.0.iter().map(|key| {
+            match MODIFIER_KEYS
+                .iter()
+                .chain(KEY_NAMES.iter())
+                .find(|(_, k)| k == key)
+            {
+                Some((name, _)) => name.to_string(),
+                None => match key {
+                    wita::VirtualKey::Char(c) => c.to_string(),
+                    wita::VirtualKey::F(n) => format!("F{}", n),
+                    _ => format!("{:?}", key),
+                },
+            }
+        });
+        write!(fmt, "{}", names.collect::<Vec<_>>().join("+"))
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Shortcuts {
+    pub open_dialog: Shortcut,
+    pub frame_counter: Shortcut,
+    pub screen_shot: Shortcut,
+    pub play: Shortcut,
+    pub head: Shortcut,
+    pub record_video: Shortcut,
+    pub flatten: Shortcut,
+    pub parameter_panel: Shortcut,
+    pub save_params: Shortcut,
+    pub add_preset: Shortcut,
+    pub next_preset: Shortcut,
+    pub close_warnings: Shortcut,
+    pub exit: Shortcut,
+}
+
+impl Shortcuts {
+    pub fn methods(&self) -> [(&Shortcut, Method); 13] {
+        [
+            (&self.open_dialog, Method::OpenDialog),
+            (&self.frame_counter, Method::FrameCounter),
+            (&self.screen_shot, Method::ScreenShot),
+            (&self.play, Method::Play),
+            (&self.head, Method::Head),
+            (&self.record_video, Method::RecordVideo),
+            (&self.flatten, Method::Flatten),
+            (&self.parameter_panel, Method::ParameterPanel),
+            (&self.save_params, Method::SaveParams),
+            (&self.add_preset, Method::AddPreset),
+            (&self.next_preset, Method::NextPreset),
+            (&self.close_warnings, Method::CloseWarnings),
+            (&self.exit, Method::Exit),
+        ]
+    }
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        let key = |s: &str| s.parse().unwrap();
+        Self {
+            open_dialog: key("Ctrl+O"),
+            frame_counter: key("Ctrl+F"),
+            screen_shot: key("PrintScreen"),
+            play: key("Space"),
+            head: key("R"),
+            record_video: key("Ctrl+V"),
+            flatten: key("Ctrl+E"),
+            parameter_panel: key("Ctrl+P"),
+            save_params: key("Ctrl+S"),
+            add_preset: key("Ctrl+N"),
+            next_preset: key("Tab"),
+            close_warnings: key("Esc"),
+            exit: key("Ctrl+Q"),
+        }
+    }
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct Settings {
     pub version: Version,
@@ -90,6 +325,12 @@ pub struct Settings {
     pub shader: Shader,
     pub swap_chain: SwapChain,
     pub video: Video,
+    #[serde(default)]
+    pub monitor: Monitor,
+    #[serde(default)]
+    pub cache: Cache,
+    #[serde(default)]
+    pub shortcuts: Shortcuts,
     pub appearance: Appearance,
 }
 
@@ -163,6 +404,95 @@ impl Default for Window {
     }
 }
 
+#[derive(Clone, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Scalar(f32),
+    Vector(Vec<f32>),
+}
+
+impl ParamValue {
+    pub fn new(values: &[f32]) -> Self {
+        match values {
+            [v] => Self::Scalar(*v),
+            _ => Self::Vector(values.to_vec()),
+        }
+    }
+
+    pub fn values(&self) -> &[f32] {
+        match self {
+            Self::Scalar(v) => std::slice::from_ref(v),
+            Self::Vector(v) => v,
+        }
+    }
+}
+
+pub type Preset = BTreeMap<String, ParamValue>;
+
+#[derive(Clone, PartialEq, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Params {
+    #[serde(default)]
+    pub preset: String,
+    #[serde(default)]
+    pub presets: BTreeMap<String, Preset>,
+}
+
+impl Params {
+    const DEFAULT_PRESET: &'static str = "default";
+
+    pub fn path(hlsl_path: impl AsRef<Path>) -> PathBuf {
+        let mut path = hlsl_path.as_ref().as_os_str().to_os_string();
+        path.push(".params.toml");
+        path.into()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let data = std::fs::read_to_string(path).map_err(|_| Error::ReadFile(path.into()))?;
+        Ok(toml::from_str(&data)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        save_file(path.as_ref(), self)
+    }
+
+    pub fn current(&self) -> Option<&Preset> {
+        self.presets.get(&self.preset)
+    }
+
+    pub fn set_current(&mut self, preset: Preset) -> &str {
+        if self.preset.is_empty() {
+            self.preset = Self::DEFAULT_PRESET.to_string();
+        }
+        self.presets.insert(self.preset.clone(), preset);
+        &self.preset
+    }
+
+    pub fn add(&mut self, preset: Preset) -> &str {
+        let name = (self.presets.len() + 1..)
+            .map(|i| format!("preset{}", i))
+            .find(|name| !self.presets.contains_key(name))
+            .unwrap();
+        self.presets.insert(name.clone(), preset);
+        self.preset = name;
+        &self.preset
+    }
+
+    pub fn next(&mut self) -> Option<(&str, &Preset)> {
+        let name = self
+            .presets
+            .keys()
+            .find(|name| **name > self.preset)
+            .or_else(|| self.presets.keys().next())?
+            .clone();
+        self.preset = name;
+        Some((&self.preset, &self.presets[&self.preset]))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -172,8 +502,109 @@ mod tests {
         Settings::default();
     }
 
+    #[test]
+    fn parse_resolution() {
+        let resolution: Resolution = "1280x720".parse().unwrap();
+        assert!(resolution.width == 1280 && resolution.height == 720);
+        assert!("1280".parse::<Resolution>().is_err());
+        assert!("0x720".parse::<Resolution>().is_err());
+        assert!("axb".parse::<Resolution>().is_err());
+    }
+
+    #[test]
+    fn settings_serde_defaults() {
+        let default: toml::Value = toml::from_str(DEFAULT_SETTINGS).unwrap();
+        let omitted: [&[&str]; 8] = [
+            &["monitor"],
+            &["monitor", "polling"],
+            &["monitor", "polling_interval_ms"],
+            &["cache"],
+            &["cache", "max_size_mb"],
+            &["shader", "include_paths"],
+            &["shortcuts"],
+            &["shortcuts", "play"],
+        ];
+        for path in omitted {
+            let mut src = default.clone();
+            let (key, parents) = path.split_last().unwrap();
+            let table = parents
+                .iter()
+                .fold(&mut src, |v, k| &mut v[*k])
+                .as_table_mut()
+                .unwrap();
+            table.remove(*key).unwrap();
+            let settings: Settings = src.try_into().unwrap();
+            let settings = toml::Value::try_from(&settings).unwrap();
+            assert!(settings[path[0]] == default[path[0]], "{}", path.join("."));
+        }
+    }
+
+    #[test]
+    fn parse_shortcut() {
+        use wita::VirtualKey;
+        let shortcut = |s: &str| s.parse::<Shortcut>().map(|s| s.0);
+        assert!(shortcut("Ctrl+O") == Ok(vec![VirtualKey::Ctrl, VirtualKey::Char('O')]));
+        assert!(
+            shortcut("alt + ctrl + shift + f5")
+                == Ok(vec![
+                    VirtualKey::Shift,
+                    VirtualKey::Ctrl,
+                    VirtualKey::Alt,
+                    VirtualKey::F(5)
+                ])
+        );
+        assert!(shortcut("pageup") == Ok(vec![VirtualKey::PageUp]));
+        assert!(shortcut("") == Ok(vec![]));
+        assert!(shortcut("Ctrl") == Err("invalid shortcut 'Ctrl'".to_string()));
+        assert!(shortcut("A+B").is_err());
+        assert!(shortcut("F25").is_err());
+        assert!(shortcut("Ctrl+?").is_err());
+        let src = "Shift+Ctrl+PrintScreen".parse::<Shortcut>().unwrap();
+        assert!(src.to_string() == "Shift+Ctrl+PrintScreen");
+        let mut src: toml::Value = toml::from_str(DEFAULT_SETTINGS).unwrap();
+        src["shortcuts"]["play"] = toml::Value::String("Ctrl+Space+X".to_string());
+        assert!(src.try_into::<Settings>().is_err());
+    }
+
     #[test]
     fn default_window_setting() {
         Window::default();
     }
+
+    #[test]
+    fn params_path() {
+        let path = Params::path(Path::new("shaders/foo.hlsl"));
+        assert!(path == Path::new("shaders/foo.hlsl.params.toml"));
+    }
+
+    #[test]
+    fn params_presets() {
+        let mut params: Params = toml::from_str(
+            r#"
+preset = "warm"
+
+[presets.warm]
+speed = 1.5
+tint = [1.0, 0.5, 0.0]
+
+[presets.cold]
+speed = 0.5
+"#,
+        )
+        .unwrap();
+        let warm = params.current().unwrap();
+        assert!(warm["speed"].values() == [1.5]);
+        assert!(warm["tint"].values() == [1.0, 0.5, 0.0]);
+        assert!(params.next().unwrap().0 == "cold");
+        assert!(params.next().unwrap().0 == "warm");
+        let mut preset = Preset::new();
+        preset.insert("speed".to_string(), ParamValue::new(&[2.0]));
+        assert!(params.add(preset.clone()) == "preset3");
+        assert!(params.current() == Some(&preset));
+        let src = toml::to_string(&params).unwrap();
+        assert!(toml::from_str::<Params>(&src).unwrap() == params);
+        let mut params = Params::default();
+        assert!(params.next().is_none());
+        assert!(params.set_current(preset) == "default");
+    }
 }
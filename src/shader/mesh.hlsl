@@ -0,0 +1,11 @@
+#include "hlsl_box.hlsli"
+
+MeshInput main(MeshVertex input) {
+    float4 world_position = mul(HLSLBox.model, float4(input.position, 1.0));
+    MeshInput output;
+    output.position = mul(HLSLBox.projection, mul(HLSLBox.view, world_position));
+    output.coord = input.coord * HLSLBox.resolution;
+    output.normal = normalize(mul((float3x3)HLSLBox.model, input.normal));
+    output.world_position = world_position.xyz;
+    return output;
+}
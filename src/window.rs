@@ -21,6 +21,8 @@ pub struct WindowManager {
     sync_event: mpsc::Receiver<WindowEvent>,
     cursor_position: Arc<Mutex<wita::PhysicalPosition<i32>>>,
     resolution: Arc<Mutex<settings::Resolution>>,
+    key_map: Arc<Mutex<KeyboardMap>>,
+    keyboard: Arc<Mutex<Keyboard>>,
 }
 
 impl WindowManager {
@@ -39,6 +41,18 @@ impl WindowManager {
         let mut r = self.resolution.lock().unwrap();
         *r = resolution;
     }
+
+    pub fn update_key_map(&self, key_map: KeyboardMap) {
+        let mut k = self.key_map.lock().unwrap();
+        *k = key_map;
+    }
+
+    pub fn next_keyboard_frame(&self) -> Keyboard {
+        let mut keyboard = self.keyboard.lock().unwrap();
+        let current = keyboard.clone();
+        keyboard.next_frame();
+        current
+    }
 }
 
 pub struct KeyboardMap(HashMap<Vec<wita::VirtualKey>, Method>);
@@ -48,33 +62,164 @@ impl KeyboardMap {
         Self(HashMap::new())
     }
 
+    pub fn from_shortcuts(shortcuts: &settings::Shortcuts) -> Self {
+        let mut this = Self::new();
+        for (shortcut, method) in shortcuts.methods() {
+            if !shortcut.0.is_empty() {
+                this.insert(shortcut.0.clone(), method);
+            }
+        }
+        this
+    }
+
     pub fn insert(&mut self, keys: Vec<wita::VirtualKey>, v: Method) {
-        let mut special_key = |sk, l, r| {
+        const SPECIAL_KEYS: [(wita::VirtualKey, wita::VirtualKey, wita::VirtualKey); 3] = [
+            (
+                wita::VirtualKey::Ctrl,
+                wita::VirtualKey::LCtrl,
+                wita::VirtualKey::RCtrl,
+            ),
+            (
+                wita::VirtualKey::Alt,
+                wita::VirtualKey::LAlt,
+                wita::VirtualKey::RAlt,
+            ),
+            (
+                wita::VirtualKey::Shift,
+                wita::VirtualKey::LShift,
+                wita::VirtualKey::RShift,
+            ),
+        ];
+        for (sk, l, r) in SPECIAL_KEYS {
             if let Some(p) = keys.iter().position(|k| k == &sk) {
                 let mut tmp = keys.clone();
                 tmp[p] = l;
-                self.0.insert(tmp.clone(), v);
+                self.insert(tmp.clone(), v);
                 tmp[p] = r;
-                self.0.insert(tmp, v);
+                self.insert(tmp, v);
             }
-        };
-        special_key(
-            wita::VirtualKey::Ctrl,
-            wita::VirtualKey::LCtrl,
-            wita::VirtualKey::RCtrl,
-        );
-        special_key(
-            wita::VirtualKey::Alt,
-            wita::VirtualKey::LAlt,
-            wita::VirtualKey::RAlt,
-        );
-        special_key(
-            wita::VirtualKey::Shift,
-            wita::VirtualKey::LShift,
-            wita::VirtualKey::RShift,
-        );
+        }
         self.0.insert(keys, v);
     }
+
+    /// `keys` is the keyboard state at the time `key` is pressed or released.
+    fn shortcut(&self, keys: &mut Vec<wita::VirtualKey>, key: wita::VirtualKey) -> Option<Method> {
+        keys.retain(|k| {
+            if let wita::VirtualKey::Other(a) = k {
+                *a < 240
+            } else {
+                let ctrl = k == &wita::VirtualKey::Ctrl;
+                let alt = k == &wita::VirtualKey::Alt;
+                let shift = k == &wita::VirtualKey::Shift;
+                !(ctrl || alt || shift || k == &key)
+            }
+        });
+        keys.push(key);
+        debug!("keys: {:?}", keys);
+        self.0.get(keys).copied()
+    }
+}
+
+fn key_code(key: wita::VirtualKey) -> Option<u8> {
+    use wita::VirtualKey;
+    let code = match key {
+        VirtualKey::Char(c) if c.is_ascii_alphanumeric() => c.to_ascii_uppercase() as u8,
+        VirtualKey::Char(c) => match c {
+            ';' => 0xba,
+            '=' => 0xbb,
+            ',' => 0xbc,
+            '-' => 0xbd,
+            '.' => 0xbe,
+            '/' => 0xbf,
+            '`' => 0xc0,
+            '[' => 0xdb,
+            '\\' => 0xdc,
+            ']' => 0xdd,
+            '\'' => 0xde,
+            _ => return None,
+        },
+        VirtualKey::Esc => 0x1b,
+        VirtualKey::Tab => 0x09,
+        VirtualKey::CapsLock => 0x14,
+        VirtualKey::Shift | VirtualKey::LShift | VirtualKey::RShift => 0x10,
+        VirtualKey::Ctrl | VirtualKey::LCtrl | VirtualKey::RCtrl => 0x11,
+        VirtualKey::Alt | VirtualKey::LAlt | VirtualKey::RAlt => 0x12,
+        VirtualKey::BackSpace => 0x08,
+        VirtualKey::Enter => 0x0d,
+        VirtualKey::Space => 0x20,
+        VirtualKey::PrintScreen => 0x2c,
+        VirtualKey::ScrollLock => 0x91,
+        VirtualKey::Pause => 0x13,
+        VirtualKey::Insert => 0x2d,
+        VirtualKey::Delete => 0x2e,
+        VirtualKey::Home => 0x24,
+        VirtualKey::End => 0x23,
+        VirtualKey::PageUp => 0x21,
+        VirtualKey::PageDown => 0x22,
+        VirtualKey::Left => 0x25,
+        VirtualKey::Up => 0x26,
+        VirtualKey::Right => 0x27,
+        VirtualKey::Down => 0x28,
+        VirtualKey::NumLock => 0x90,
+        VirtualKey::NumPad(n) if n < 10 => 0x60 + n,
+        VirtualKey::NumMul => 0x6a,
+        VirtualKey::NumAdd => 0x6b,
+        VirtualKey::NumSub => 0x6d,
+        VirtualKey::NumDecimal => 0x6e,
+        VirtualKey::NumDiv => 0x6f,
+        VirtualKey::F(n) if (1..=24).contains(&n) => 0x6f + n,
+        VirtualKey::Other(n) if n < 256 => n as u8,
+        _ => return None,
+    };
+    Some(code)
+}
+
+pub const KEYBOARD_WIDTH: u32 = 256;
+pub const KEYBOARD_HEIGHT: u32 = 3;
+
+#[derive(Clone)]
+pub struct Keyboard([u8; (KEYBOARD_WIDTH * KEYBOARD_HEIGHT) as usize]);
+
+impl Keyboard {
+    const HELD: usize = 0;
+    const PRESSED: usize = 1;
+    const TOGGLED: usize = 2;
+
+    fn at(&mut self, row: usize, code: u8) -> &mut u8 {
+        &mut self.0[row * KEYBOARD_WIDTH as usize + code as usize]
+    }
+
+    fn key_input(&mut self, key: wita::VirtualKey, state: wita::KeyState) {
+        let code = match key_code(key) {
+            Some(code) => code,
+            None => return,
+        };
+        match state {
+            wita::KeyState::Pressed => {
+                if *self.at(Self::HELD, code) == 0 {
+                    *self.at(Self::PRESSED, code) = 0xff;
+                    *self.at(Self::TOGGLED, code) ^= 0xff;
+                }
+                *self.at(Self::HELD, code) = 0xff;
+            }
+            wita::KeyState::Released => *self.at(Self::HELD, code) = 0,
+        }
+    }
+
+    fn next_frame(&mut self) {
+        let row = Self::PRESSED * KEYBOARD_WIDTH as usize;
+        self.0[row..row + KEYBOARD_WIDTH as usize].fill(0);
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self([0; (KEYBOARD_WIDTH * KEYBOARD_HEIGHT) as usize])
+    }
 }
 
 struct Window {
@@ -112,8 +257,9 @@ pub struct WindowHandler {
     event: mpsc::Sender<WindowEvent>,
     sync_event: mpsc::SyncSender<WindowEvent>,
     cursor_position: Arc<Mutex<wita::PhysicalPosition<i32>>>,
-    key_map: KeyboardMap,
+    key_map: Arc<Mutex<KeyboardMap>>,
     keys: Vec<wita::VirtualKey>,
+    keyboard: Arc<Mutex<Keyboard>>,
 }
 
 impl WindowHandler {
@@ -150,6 +296,8 @@ impl WindowHandler {
             },
             |settings| settings.resolution,
         )));
+        let key_map = Arc::new(Mutex::new(key_map));
+        let keyboard = Arc::new(Mutex::new(Keyboard::default()));
         (
             Self {
                 resolution: resolution.clone(),
@@ -157,8 +305,9 @@ impl WindowHandler {
                 event: tx,
                 sync_event: sync_tx,
                 cursor_position: cursor_position.clone(),
-                key_map,
+                key_map: key_map.clone(),
                 keys: Vec::with_capacity(5),
+                keyboard: keyboard.clone(),
             },
             WindowManager {
                 main_window,
@@ -166,6 +315,8 @@ impl WindowHandler {
                 sync_event: sync_rx,
                 cursor_position,
                 resolution,
+                key_map,
+                keyboard,
             },
         )
     }
@@ -174,22 +325,17 @@ impl WindowHandler {
 impl wita::EventHandler for WindowHandler {
     fn key_input(&mut self, ev: wita::event::KeyInput) {
         if ev.window == &self.main_window {
+            let key = ev.key_code.vkey;
+            wita::keyboard_state(&mut self.keys);
+            let method = self.key_map.lock().unwrap().shortcut(&mut self.keys, key);
+            // Keys taken by a shortcut do not reach the keyboard texture.
+            match ev.state {
+                wita::KeyState::Pressed if method.is_some() => {}
+                state => self.keyboard.lock().unwrap().key_input(key, state),
+            }
             if ev.state == wita::KeyState::Released {
-                wita::keyboard_state(&mut self.keys);
-                self.keys.retain(|k| {
-                    if let wita::VirtualKey::Other(a) = k {
-                        *a < 240
-                    } else {
-                        let ctrl = k == &wita::VirtualKey::Ctrl;
-                        let alt = k == &wita::VirtualKey::Alt;
-                        let shift = k == &wita::VirtualKey::Shift;
-                        !(ctrl || alt || shift)
-                    }
-                });
-                self.keys.push(ev.key_code.vkey);
-                debug!("keys: {:?}", &self.keys);
-                if let Some(m) = self.key_map.0.get(&self.keys) {
-                    self.event.send(WindowEvent::KeyInput(*m)).ok();
+                if let Some(m) = method {
+                    self.event.send(WindowEvent::KeyInput(m)).ok();
                 }
             }
             debug!("main_window key_input");
@@ -310,3 +456,89 @@ impl wita::EventHandler for WindowHandler {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyboard_map_from_shortcuts() {
+        use wita::VirtualKey;
+        let mut shortcuts = settings::Shortcuts::default();
+        shortcuts.save_params = "Ctrl+Shift+S".parse().unwrap();
+        shortcuts.head = "".parse().unwrap();
+        let key_map = KeyboardMap::from_shortcuts(&shortcuts);
+        let get = |keys: &[VirtualKey]| key_map.0.get(keys).copied();
+        assert!(get(&[VirtualKey::RCtrl, VirtualKey::Char('O')]) == Some(Method::OpenDialog));
+        assert!(
+            get(&[VirtualKey::LShift, VirtualKey::RCtrl, VirtualKey::Char('S')])
+                == Some(Method::SaveParams)
+        );
+        assert!(get(&[VirtualKey::LCtrl, VirtualKey::Char('S')]).is_none());
+        assert!(get(&[VirtualKey::Char('R')]).is_none());
+    }
+
+    #[test]
+    fn keyboard_map_shortcut() {
+        use wita::VirtualKey;
+        let mut shortcuts = settings::Shortcuts::default();
+        shortcuts.save_params = "Ctrl+Shift+S".parse().unwrap();
+        let key_map = KeyboardMap::from_shortcuts(&shortcuts);
+        let shortcut = |keys: &[VirtualKey], key| key_map.shortcut(&mut keys.to_vec(), key);
+        assert!(
+            shortcut(
+                &[VirtualKey::Ctrl, VirtualKey::LCtrl, VirtualKey::Other(0xff)],
+                VirtualKey::Char('O')
+            ) == Some(Method::OpenDialog)
+        );
+        assert!(
+            shortcut(
+                &[
+                    VirtualKey::Shift,
+                    VirtualKey::Ctrl,
+                    VirtualKey::RShift,
+                    VirtualKey::LCtrl
+                ],
+                VirtualKey::Char('S')
+            ) == Some(Method::SaveParams)
+        );
+        assert!(shortcut(
+            &[VirtualKey::Ctrl, VirtualKey::LCtrl],
+            VirtualKey::Char('S')
+        )
+        .is_none());
+        assert!(shortcut(&[], VirtualKey::Space) == Some(Method::Play));
+        assert!(shortcut(&[VirtualKey::LAlt], VirtualKey::Space).is_none());
+        assert!(
+            shortcut(
+                &[VirtualKey::LCtrl, VirtualKey::Char('O')],
+                VirtualKey::Char('O')
+            ) == Some(Method::OpenDialog)
+        );
+        assert!(shortcut(&[VirtualKey::Space], VirtualKey::Space) == Some(Method::Play));
+        assert!(shortcut(&[VirtualKey::Char('A')], VirtualKey::Char('A')).is_none());
+    }
+
+    #[test]
+    fn keyboard_state() {
+        let mut keyboard = Keyboard::default();
+        let at = |keyboard: &Keyboard, row: usize, code: usize| {
+            keyboard.data()[row * KEYBOARD_WIDTH as usize + code]
+        };
+        keyboard.key_input(wita::VirtualKey::Char('a'), wita::KeyState::Pressed);
+        keyboard.key_input(wita::VirtualKey::Char('a'), wita::KeyState::Pressed);
+        keyboard.key_input(wita::VirtualKey::LShift, wita::KeyState::Pressed);
+        assert!(at(&keyboard, 0, 0x41) == 0xff);
+        assert!(at(&keyboard, 1, 0x41) == 0xff);
+        assert!(at(&keyboard, 2, 0x41) == 0xff);
+        assert!(at(&keyboard, 0, 0x10) == 0xff);
+        keyboard.next_frame();
+        keyboard.key_input(wita::VirtualKey::Char('A'), wita::KeyState::Released);
+        assert!(at(&keyboard, 0, 0x41) == 0);
+        assert!(at(&keyboard, 1, 0x41) == 0);
+        assert!(at(&keyboard, 2, 0x41) == 0xff);
+        keyboard.key_input(wita::VirtualKey::Char('A'), wita::KeyState::Pressed);
+        assert!(at(&keyboard, 1, 0x41) == 0xff);
+        assert!(at(&keyboard, 2, 0x41) == 0);
+    }
+}